@@ -1,12 +1,148 @@
+pub mod action;
+pub mod command;
+pub mod focus;
+pub mod mouse_dispatch;
+
 use super::core::Clock;
 use super::core::Updatable;
+use super::error;
+use super::error::{ErrorPolicy, HandlerErrors};
 use super::numeric;
 use ggez::input;
 use ggez::input::mouse::MouseButton;
 use ggez::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 
+///
+/// # イベントハンドラの型
+/// 入力状態を提供するInputSourceと、現在の時刻を受け取る
+///
+pub type InputEventHandler = Box<dyn Fn(&dyn InputSource, Clock) -> Result<(), String>>;
+
+///
+/// # 入力状態を提供するトレイト
+/// KeyboardListener, MouseListenerはこのトレイトを通して入力状態を読み取る
+///
+/// ggez::Contextから読み取るGgezInputSourceと、
+/// 任意の入力を与えられるScriptedInputSourceが用意されている
+///
+pub trait InputSource {
+    /// 指定したデバイスで、VirtualKeyが押されているかを返す
+    fn vkey_status(&self, device: &KeyInputDevice, vkey: &VirtualKey) -> KeyStatus;
+
+    /// 現在のマウスの座標を返す
+    fn mouse_position(&self) -> numeric::Point2f;
+
+    /// 指定したマウスのボタンの状態を返す
+    fn mouse_button_status(&self, button: MouseButton) -> MouseButtonStatus;
+}
+
+///
+/// # ggez::Contextから入力状態を読み取るInputSource
+///
+pub struct GgezInputSource<'a> {
+    ctx: &'a ggez::Context,
+}
+
+impl<'a> GgezInputSource<'a> {
+    pub fn new(ctx: &'a ggez::Context) -> GgezInputSource<'a> {
+        GgezInputSource { ctx: ctx }
+    }
+}
+
+impl<'a> InputSource for GgezInputSource<'a> {
+    fn vkey_status(&self, device: &KeyInputDevice, vkey: &VirtualKey) -> KeyStatus {
+        vkey_input_check(self.ctx, device, vkey)
+    }
+
+    fn mouse_position(&self) -> numeric::Point2f {
+        MouseListener::get_position(self.ctx)
+    }
+
+    fn mouse_button_status(&self, button: MouseButton) -> MouseButtonStatus {
+        MouseListener::check_button(self.ctx, button)
+    }
+}
+
+///
+/// # 入力状態を直接設定できるInputSource
+/// ggez::Contextを必要としないため、ウィンドウの無い環境でリスナを動作させることができる
+///
+/// ## Example
+/// ```
+/// use torifune::device::*;
+///
+/// let mut input = ScriptedInputSource::new();
+/// input.press_key(VirtualKey::Action1);
+/// assert_eq!(
+///     input.vkey_status(&KeyInputDevice::GenericKeyboard, &VirtualKey::Action1),
+///     KeyStatus::Pressed
+/// );
+/// ```
+///
+pub struct ScriptedInputSource {
+    pressed_keys: HashSet<VirtualKey>,
+    pressed_buttons: HashSet<MouseButton>,
+    mouse_position: numeric::Point2f,
+}
+
+impl ScriptedInputSource {
+    pub fn new() -> ScriptedInputSource {
+        ScriptedInputSource {
+            pressed_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            mouse_position: numeric::Point2f::new(0.0, 0.0),
+        }
+    }
+
+    pub fn press_key(&mut self, vkey: VirtualKey) {
+        self.pressed_keys.insert(vkey);
+    }
+
+    pub fn release_key(&mut self, vkey: VirtualKey) {
+        self.pressed_keys.remove(&vkey);
+    }
+
+    pub fn press_button(&mut self, button: MouseButton) {
+        self.pressed_buttons.insert(button);
+    }
+
+    pub fn release_button(&mut self, button: MouseButton) {
+        self.pressed_buttons.remove(&button);
+    }
+
+    pub fn set_mouse_position(&mut self, pos: numeric::Point2f) {
+        self.mouse_position = pos;
+    }
+
+    ///
+    /// 全てのキーとボタンを離された状態に戻すメソッド
+    ///
+    pub fn release_all(&mut self) {
+        self.pressed_keys.clear();
+        self.pressed_buttons.clear();
+    }
+}
+
+impl InputSource for ScriptedInputSource {
+    fn vkey_status(&self, _device: &KeyInputDevice, vkey: &VirtualKey) -> KeyStatus {
+        KeyStatus::positive_logic(self.pressed_keys.contains(vkey))
+    }
+
+    fn mouse_position(&self) -> numeric::Point2f {
+        self.mouse_position
+    }
+
+    fn mouse_button_status(&self, button: MouseButton) -> MouseButtonStatus {
+        if self.pressed_buttons.contains(&button) {
+            MouseButtonStatus::MousePressed
+        } else {
+            MouseButtonStatus::MouseReleased
+        }
+    }
+}
+
 ///
 /// # マウスのボタンの状態
 /// マウスのボタンの状態を表す
@@ -14,7 +150,7 @@ use std::hash::Hash;
 /// MousePressed: 押されている
 /// MouseReleased: 離されている
 ///
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum MouseButtonStatus {
     MousePressed,
     MouseReleased,
@@ -24,17 +160,69 @@ pub enum MouseButtonStatus {
 /// # マウスイベント
 /// マウスイベント, イベントハンドラはこれらと関連付けて登録する
 ///
-/// Clicked: クリックされた
+/// Clicked: クリックされた（ドラッグ後に離された場合は発生しない）
+/// DoubleClicked: ダブルクリックされた（Clickedの後に発生する）
 /// Pressed: 押された
-/// Dragged: ドラッグされた
+/// Dragged: 押され続けている
+/// DragStart: 押された位置から閾値以上動いた
+/// DragEnd: ドラッグ中に離された
 ///
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 pub enum MouseButtonEvent {
     Clicked,
+    DoubleClicked,
     Pressed,
     Dragged,
+    DragStart,
+    DragEnd,
+}
+
+impl MouseButtonEvent {
+    fn all() -> [MouseButtonEvent; 6] {
+        [
+            MouseButtonEvent::Clicked,
+            MouseButtonEvent::DoubleClicked,
+            MouseButtonEvent::Pressed,
+            MouseButtonEvent::Dragged,
+            MouseButtonEvent::DragStart,
+            MouseButtonEvent::DragEnd,
+        ]
+    }
+}
+
+///
+/// # マウスカーソルの重なりに関するイベント
+///
+/// Enter: カーソルが重なり始めた
+/// Hover: カーソルが重なっている
+/// Leave: カーソルが離れた
+///
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum MouseHoverEvent {
+    Enter,
+    Hover,
+    Leave,
+}
+
+///
+/// # マウスイベントの処理結果
+///
+/// Consumed: イベントを処理した。背面のオブジェクトへは伝播しない
+/// Ignored: イベントを処理しなかった。背面のオブジェクトへ伝播する
+///
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum MouseEventStatus {
+    Consumed,
+    Ignored,
 }
 
+///
+/// # 移動量を伴うマウスイベントのハンドラの型
+/// ホイールの回転量, カーソルの移動量を受け取る
+///
+pub type MouseDeltaEventHandler =
+    Box<dyn Fn(&dyn InputSource, numeric::Vector2f, Clock) -> Result<(), String>>;
+
 ///
 /// # マウスの状態を監視しイベントハンドラを実行する構造体
 /// イベントハンドラを登録し、呼び出すことが出来る
@@ -43,105 +231,105 @@ pub enum MouseButtonEvent {
 /// ### last_clicked
 /// 各ボタンが最後にクリックした座標
 ///
+/// ### last_clicked_time
+/// 各ボタンが最後にクリックした時刻。ダブルクリックの判定に用いる
+///
+/// ### drag_origin
+/// 各ボタンが押された座標。ドラッグの判定に用いる
+///
+/// ### last_drag_origin
+/// 各ボタンで最後に終了したドラッグの開始座標。DragEndのハンドラを実行した後に参照する
+///
+/// ### dragging
+/// ドラッグ中のボタン
+///
 /// ### button_map
-/// 最後に記録した各ボタンの状態
+/// 最後に記録した各ボタンの状態。このマップに含まれるボタンが監視される
 ///
 /// ### event_handlers
 /// event_handlers[MouseButton][MouseButtonEvent]  ====>  クロージャのベクタ
 ///
+/// ### last_position
+/// 最後に記録したカーソルの座標
+///
+/// ### pending_wheel
+/// 次のupdateで通知するホイールの回転量
+///
+/// ### handler_errors
+/// イベントハンドラが返したエラー
+///
 pub struct MouseListener {
     last_clicked: HashMap<MouseButton, numeric::Point2f>,
+    last_clicked_time: HashMap<MouseButton, Clock>,
+    drag_origin: HashMap<MouseButton, numeric::Point2f>,
+    last_drag_origin: HashMap<MouseButton, numeric::Point2f>,
+    dragging: HashSet<MouseButton>,
     button_map: HashMap<MouseButton, MouseButtonStatus>,
-    event_handlers: HashMap<
-        MouseButton,
-        HashMap<MouseButtonEvent, Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>>,
-    >,
+    event_handlers: HashMap<MouseButton, HashMap<MouseButtonEvent, Vec<InputEventHandler>>>,
+    wheel_handlers: Vec<MouseDeltaEventHandler>,
+    motion_handlers: Vec<MouseDeltaEventHandler>,
+    last_position: Option<numeric::Point2f>,
+    pending_wheel: numeric::Vector2f,
+    double_click_interval: Clock,
+    drag_threshold: f32,
+    handler_errors: HandlerErrors,
 }
 
 impl MouseListener {
     /// ScheduledEvent構造体の生成メソッド
     pub fn new() -> MouseListener {
-        let mut button_map = HashMap::new();
-
-        button_map.insert(MouseButton::Left, MouseButtonStatus::MouseReleased);
-        button_map.insert(MouseButton::Middle, MouseButtonStatus::MouseReleased);
-        button_map.insert(MouseButton::Right, MouseButtonStatus::MouseReleased);
-
-        let mut events = HashMap::new();
-        events.insert(
-            MouseButton::Left,
-            hash![
-                (
-                    MouseButtonEvent::Clicked,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Pressed,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Dragged,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                )
-            ],
-        );
-
-        events.insert(
-            MouseButton::Middle,
-            hash![
-                (
-                    MouseButtonEvent::Clicked,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Pressed,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Dragged,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                )
-            ],
-        );
-
-        events.insert(
-            MouseButton::Right,
-            hash![
-                (
-                    MouseButtonEvent::Clicked,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Pressed,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                ),
-                (
-                    MouseButtonEvent::Dragged,
-                    Vec::<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>::new()
-                )
-            ],
-        );
+        let mut listener = MouseListener {
+            last_clicked: HashMap::new(),
+            last_clicked_time: HashMap::new(),
+            drag_origin: HashMap::new(),
+            last_drag_origin: HashMap::new(),
+            dragging: HashSet::new(),
+            button_map: HashMap::new(),
+            event_handlers: HashMap::new(),
+            wheel_handlers: Vec::new(),
+            motion_handlers: Vec::new(),
+            last_position: None,
+            pending_wheel: numeric::Vector2f::new(0.0, 0.0),
+            double_click_interval: 20,
+            drag_threshold: 4.0,
+            handler_errors: HandlerErrors::default(),
+        };
 
-        MouseListener {
-            last_clicked: hash![
-                (MouseButton::Left, numeric::Point2f::new(0.0, 0.0)),
-                (MouseButton::Middle, numeric::Point2f::new(0.0, 0.0)),
-                (MouseButton::Right, numeric::Point2f::new(0.0, 0.0))
-            ],
-            button_map: button_map,
-            event_handlers: events,
-        }
+        listener.listen_button(MouseButton::Left);
+        listener.listen_button(MouseButton::Middle);
+        listener.listen_button(MouseButton::Right);
+
+        listener
+    }
+
+    ///
+    /// 監視するボタンを追加するメソッド
+    /// Left, Middle, Right以外のボタンも監視できる
+    ///
+    pub fn listen_button(&mut self, button: MouseButton) {
+        self.button_map
+            .entry(button)
+            .or_insert(MouseButtonStatus::MouseReleased);
+        self.event_handlers.entry(button).or_insert_with(|| {
+            let mut events = HashMap::new();
+            for event in MouseButtonEvent::all().iter() {
+                events.insert(*event, Vec::new());
+            }
+            events
+        });
     }
 
     ///
     /// マウスのイベントハンドラを登録するためのメソッド
+    /// 監視していないボタンが指定された場合は、そのボタンの監視を開始する
     ///
     pub fn register_event_handler(
         &mut self,
         button: MouseButton,
         event: MouseButtonEvent,
-        f: Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>,
+        f: InputEventHandler,
     ) {
+        self.listen_button(button);
         self.event_handlers
             .get_mut(&button)
             .unwrap()
@@ -150,6 +338,45 @@ impl MouseListener {
             .push(f);
     }
 
+    ///
+    /// ホイールのイベントハンドラを登録するためのメソッド
+    ///
+    pub fn register_wheel_handler(&mut self, f: MouseDeltaEventHandler) {
+        self.wheel_handlers.push(f);
+    }
+
+    ///
+    /// カーソル移動のイベントハンドラを登録するためのメソッド
+    /// ハンドラには前回のupdateからの移動量が渡される
+    ///
+    pub fn register_motion_handler(&mut self, f: MouseDeltaEventHandler) {
+        self.motion_handlers.push(f);
+    }
+
+    ///
+    /// ダブルクリックと判定する、クリックの最大間隔を設定する
+    ///
+    pub fn set_double_click_interval(&mut self, interval: Clock) {
+        self.double_click_interval = interval;
+    }
+
+    ///
+    /// ドラッグ開始と判定する、押された位置からの移動距離を設定する
+    ///
+    pub fn set_drag_threshold(&mut self, threshold: f32) {
+        self.drag_threshold = threshold;
+    }
+
+    ///
+    /// ホイールの回転量を通知するメソッド
+    /// ggez::event::EventHandler::mouse_wheel_eventから呼び出すことを想定している
+    /// 回転量は次のupdateでまとめてハンドラに渡される
+    ///
+    pub fn notify_wheel(&mut self, x: f32, y: f32) {
+        self.pending_wheel.x += x;
+        self.pending_wheel.y += y;
+    }
+
     //
     // 現在のマウスの座標を得るメソッド
     //
@@ -170,82 +397,187 @@ impl MouseListener {
     // 最後のクリック座標を返すメソッド
     //
     pub fn get_last_clicked(&self, button: MouseButton) -> numeric::Point2f {
-        match button {
-            MouseButton::Left | MouseButton::Middle | MouseButton::Right => {
-                self.last_clicked[&button]
-            }
-            _ => panic!("Other MouseButton is detected!!"),
+        match self.last_clicked.get(&button) {
+            Some(point) => *point,
+            None => numeric::Point2f::new(0.0, 0.0),
+        }
+    }
+
+    //
+    // ボタンが押されている場合、押された座標を返すメソッド
+    //
+    pub fn get_drag_origin(&self, button: MouseButton) -> Option<numeric::Point2f> {
+        self.drag_origin.get(&button).copied()
+    }
+
+    //
+    // 最後に終了したドラッグの開始座標を返すメソッド
+    // ドロップ時の処理で、ドラッグが始まった座標を知るために用いる
+    //
+    pub fn get_last_drag_origin(&self, button: MouseButton) -> Option<numeric::Point2f> {
+        self.last_drag_origin.get(&button).copied()
+    }
+
+    //
+    // ボタンがドラッグ中かを返すメソッド
+    //
+    pub fn is_dragging(&self, button: MouseButton) -> bool {
+        self.dragging.contains(&button)
+    }
+
+    fn call_event_handlers(
+        &mut self,
+        input: &dyn InputSource,
+        t: Clock,
+        button: MouseButton,
+        event: MouseButtonEvent,
+    ) {
+        // ボタン・操作の情報を利用してクロージャのリストの要素を全て実行
+        for f in &self.event_handlers[&button][&event] {
+            self.handler_errors.handle(f(input, t));
         }
     }
 
     fn __flush_button_event(
         &mut self,
-        ctx: &ggez::Context,
+        input: &dyn InputSource,
         t: Clock,
         button: MouseButton,
         current_state: &MouseButtonStatus,
     ) {
+        let position = input.mouse_position();
+
         // 入力内容が以前と異なる
-        let event = if *current_state != self.button_map[&button] {
+        if *current_state != self.button_map[&button] {
             // 操作を検知
             match *current_state {
-                MouseButtonStatus::MousePressed => MouseButtonEvent::Pressed,
+                MouseButtonStatus::MousePressed => {
+                    self.drag_origin.insert(button, position);
+                    self.call_event_handlers(input, t, button, MouseButtonEvent::Pressed);
+                }
                 MouseButtonStatus::MouseReleased => {
+                    // ドラッグ中に離された場合はクリックとしない
+                    // 開始座標はDragEndのハンドラを実行した後に取り除く
+                    if self.dragging.remove(&button) {
+                        if let Some(origin) = self.drag_origin.get(&button).copied() {
+                            self.last_drag_origin.insert(button, origin);
+                        }
+                        self.call_event_handlers(input, t, button, MouseButtonEvent::DragEnd);
+                        self.drag_origin.remove(&button);
+                        return ();
+                    }
+                    self.drag_origin.remove(&button);
+
                     // clickされた場合、last_clickにセット
-                    self.last_clicked.insert(button, Self::get_position(ctx));
+                    self.last_clicked.insert(button, position);
+                    self.call_event_handlers(input, t, button, MouseButtonEvent::Clicked);
 
-                    MouseButtonEvent::Clicked
+                    // 前回のクリックから一定時間内であれば、ダブルクリック
+                    // 時刻が巻き戻った場合は、前回のクリックを無効とする
+                    match self.last_clicked_time.remove(&button) {
+                        Some(last) if t >= last && t - last <= self.double_click_interval => {
+                            self.call_event_handlers(
+                                input,
+                                t,
+                                button,
+                                MouseButtonEvent::DoubleClicked,
+                            );
+                        }
+                        _ => {
+                            self.last_clicked_time.insert(button, t);
+                        }
+                    }
                 }
             }
-        } else {
+        } else if current_state == &MouseButtonStatus::MousePressed {
             // マウスのドラッグの判定
-            if current_state == &MouseButtonStatus::MousePressed {
-                MouseButtonEvent::Dragged
-            } else {
-                // どの動作の種類にも反応しない
+            if !self.dragging.contains(&button) {
+                let origin = self.drag_origin.get(&button).copied().unwrap_or(position);
+                if distance!(origin, position) >= self.drag_threshold {
+                    self.dragging.insert(button);
+                    self.call_event_handlers(input, t, button, MouseButtonEvent::DragStart);
+                }
+            }
+
+            self.call_event_handlers(input, t, button, MouseButtonEvent::Dragged);
+        }
+    }
+
+    fn flush_wheel_event(&mut self, input: &dyn InputSource, t: Clock) {
+        if self.pending_wheel.x == 0.0 && self.pending_wheel.y == 0.0 {
+            return ();
+        }
+
+        let wheel = self.pending_wheel;
+        self.pending_wheel = numeric::Vector2f::new(0.0, 0.0);
+
+        for f in &self.wheel_handlers {
+            self.handler_errors.handle(f(input, wheel, t));
+        }
+    }
+
+    fn flush_motion_event(&mut self, input: &dyn InputSource, t: Clock) {
+        let position = input.mouse_position();
+        let last_position = self.last_position.replace(position);
+
+        if let Some(last_position) = last_position {
+            let delta = position - last_position;
+            if delta.x == 0.0 && delta.y == 0.0 {
                 return ();
             }
-        };
 
-        // ボタン・操作の情報を利用してクロージャのリストの要素を全て実行
-        for f in &self.event_handlers[&button][&event] {
-            match f(ctx, t) {
-                Err(x) => panic!(x),
-                _ => (),
+            for f in &self.motion_handlers {
+                self.handler_errors.handle(f(input, delta, t));
             }
         }
     }
 
-    fn flush_button_event(
-        &mut self,
-        ctx: &ggez::Context,
-        t: Clock,
-        l_state: &MouseButtonStatus,
-        m_state: &MouseButtonStatus,
-        r_state: &MouseButtonStatus,
-    ) {
-        self.__flush_button_event(ctx, t, MouseButton::Left, l_state);
-        self.__flush_button_event(ctx, t, MouseButton::Middle, m_state);
-        self.__flush_button_event(ctx, t, MouseButton::Right, r_state);
+    ///
+    /// イベントハンドラがエラーを返した時の振る舞いを設定するメソッド
+    ///
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.handler_errors.set_policy(policy);
     }
-}
 
-impl Updatable for MouseListener {
-    fn update(&mut self, ctx: &mut ggez::Context, t: Clock) {
-        let (l_status, m_status, r_status) = (
-            MouseListener::check_button(ctx, MouseButton::Left),
-            MouseListener::check_button(ctx, MouseButton::Middle),
-            MouseListener::check_button(ctx, MouseButton::Right),
-        );
+    ///
+    /// Updatable::updateの実行中に蓄積されたエラーを取り出すメソッド
+    ///
+    pub fn take_error(&mut self) -> Option<error::Error> {
+        self.handler_errors.take()
+    }
+
+    ///
+    /// InputSourceから入力状態を読み取り、イベントハンドラを実行するメソッド
+    /// ErrorPolicy::Propagateの場合、イベントハンドラが返したエラーをまとめて返す
+    ///
+    pub fn update_with_input(&mut self, input: &dyn InputSource, t: Clock) -> error::Result<()> {
+        self.flush_input(input, t);
+        self.handler_errors.finish()
+    }
+
+    fn flush_input(&mut self, input: &dyn InputSource, t: Clock) {
+        self.flush_motion_event(input, t);
+        self.flush_wheel_event(input, t);
+
+        let buttons: Vec<MouseButton> = self.button_map.keys().copied().collect();
 
         //
         // 入力のイベントハンドラを実行する
         //
-        self.flush_button_event(ctx, t, &l_status, &m_status, &r_status);
+        for button in buttons {
+            let status = input.mouse_button_status(button);
+            self.__flush_button_event(input, t, button, &status);
+            self.button_map.insert(button, status);
+        }
+    }
+}
 
-        self.button_map.insert(MouseButton::Left, l_status);
-        self.button_map.insert(MouseButton::Middle, m_status);
-        self.button_map.insert(MouseButton::Right, r_status);
+impl Updatable for MouseListener {
+    ///
+    /// イベントハンドラが返したエラーは、take_errorで取り出すまで蓄積される
+    ///
+    fn update(&mut self, ctx: &mut ggez::Context, t: Clock) {
+        self.flush_input(&GgezInputSource::new(ctx), t);
     }
 }
 
@@ -447,11 +779,19 @@ fn vkey_input_check(ctx: &Context, device: &KeyInputDevice, vkey: &VirtualKey) -
 /// ### event_handlers
 /// event_handlers[VirtualKey][KeyStatus]  ====>  クロージャのベクタ
 ///
+/// ### last_events
+/// 直前のupdateで発生したイベントのリスト。KeepReleasedは含まない
+///
+/// ### handler_errors
+/// イベントハンドラが返したエラー
+///
 pub struct KeyboardListener {
     devices: Vec<KeyInputDevice>,
     listening: Vec<VirtualKey>,
     key_map: Vec<KeyStatus>,
-    event_handlers: Vec<Vec<Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>>>,
+    event_handlers: Vec<Vec<Vec<InputEventHandler>>>,
+    last_events: Vec<(VirtualKey, KeyboardEvent)>,
+    handler_errors: HandlerErrors,
 }
 
 impl KeyboardListener {
@@ -461,11 +801,9 @@ impl KeyboardListener {
         let key_map = vec![KeyStatus::Released; (VirtualKey::Unknown as usize) + 1];
         let mut listening = Vec::new();
 
-        let mut events: Vec<Vec<Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>>> =
-            Vec::new();
+        let mut events: Vec<Vec<Vec<InputEventHandler>>> = Vec::new();
         for vkey_raw in 0..(VirtualKey::Unknown as i32 + 1) {
-            let mut tmp: Vec<Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>> =
-                Vec::new();
+            let mut tmp: Vec<Vec<InputEventHandler>> = Vec::new();
             for _ in 0..(KeyboardEvent::Unknown as i32 + 1) {
                 tmp.push(Vec::new());
             }
@@ -480,6 +818,8 @@ impl KeyboardListener {
             listening: listening,
             key_map: key_map,
             event_handlers: events,
+            last_events: Vec::new(),
+            handler_errors: HandlerErrors::default(),
         }
     }
 
@@ -493,11 +833,9 @@ impl KeyboardListener {
         // key_mapは全てReleasedで初期化
         let key_map = vec![KeyStatus::Released; (VirtualKey::Unknown as usize) + 1];
 
-        let mut events: Vec<Vec<Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>>> =
-            Vec::new();
+        let mut events: Vec<Vec<Vec<InputEventHandler>>> = Vec::new();
         for _ in 0..(VirtualKey::Unknown as i32 + 1) {
-            let mut tmp: Vec<Vec<Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>>> =
-                Vec::new();
+            let mut tmp: Vec<Vec<InputEventHandler>> = Vec::new();
             for _ in 0..(KeyboardEvent::Unknown as i32 + 1) {
                 tmp.push(Vec::new());
             }
@@ -509,6 +847,8 @@ impl KeyboardListener {
             listening: listening,
             key_map: key_map,
             event_handlers: events,
+            last_events: Vec::new(),
+            handler_errors: HandlerErrors::default(),
         }
     }
 
@@ -519,7 +859,7 @@ impl KeyboardListener {
         &mut self,
         key: VirtualKey,
         event: KeyboardEvent,
-        f: Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>>,
+        f: InputEventHandler,
     ) {
         self.event_handlers
             .get_mut(key as usize)
@@ -533,12 +873,12 @@ impl KeyboardListener {
     /// キー入力に応じてイベントハンドラを呼び出すメソッド
     ///
     fn flush_key_event(
-        &self,
-        ctx: &ggez::Context,
+        &mut self,
+        input: &dyn InputSource,
         t: Clock,
         vkey: &VirtualKey,
         current_state: &KeyStatus,
-    ) {
+    ) -> KeyboardEvent {
         let event = if *current_state != *self.key_map.get(*vkey as usize).unwrap() {
             match current_state {
                 &KeyStatus::Pressed => KeyboardEvent::FirstPressed,
@@ -560,34 +900,87 @@ impl KeyboardListener {
             .get(event as usize)
             .unwrap()
         {
-            match f(ctx, t) {
-                Err(x) => panic!(x),
-                _ => (),
-            }
+            self.handler_errors.handle(f(input, t));
         }
+
+        event
+    }
+
+    ///
+    /// 直前のupdateで発生したイベントを返すメソッド
+    /// KeepReleasedは含まれない
+    ///
+    pub fn last_events(&self) -> &[(VirtualKey, KeyboardEvent)] {
+        &self.last_events
     }
 
     ///
     /// 複数のキー入力デバイスの状態をミックスするメソッドs
     ///
     pub fn current_key_status(&self, ctx: &ggez::Context, vkey: &VirtualKey) -> KeyStatus {
+        self.current_key_status_with_input(&GgezInputSource::new(ctx), vkey)
+    }
+
+    ///
+    /// InputSourceから読み取った複数のキー入力デバイスの状態をミックスするメソッド
+    ///
+    pub fn current_key_status_with_input(
+        &self,
+        input: &dyn InputSource,
+        vkey: &VirtualKey,
+    ) -> KeyStatus {
         for device in &self.devices {
-            if vkey_input_check(ctx, device, vkey) == KeyStatus::Pressed {
+            if input.vkey_status(device, vkey) == KeyStatus::Pressed {
                 return KeyStatus::Pressed;
             }
         }
 
         KeyStatus::Released
     }
+
+    ///
+    /// イベントハンドラがエラーを返した時の振る舞いを設定するメソッド
+    ///
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.handler_errors.set_policy(policy);
+    }
+
+    ///
+    /// Updatable::updateの実行中に蓄積されたエラーを取り出すメソッド
+    ///
+    pub fn take_error(&mut self) -> Option<error::Error> {
+        self.handler_errors.take()
+    }
+
+    ///
+    /// InputSourceから入力状態を読み取り、イベントハンドラを実行するメソッド
+    /// ErrorPolicy::Propagateの場合、イベントハンドラが返したエラーをまとめて返す
+    ///
+    pub fn update_with_input(&mut self, input: &dyn InputSource, t: Clock) -> error::Result<()> {
+        self.flush_input(input, t);
+        self.handler_errors.finish()
+    }
+
+    fn flush_input(&mut self, input: &dyn InputSource, t: Clock) {
+        self.last_events.clear();
+
+        for vkey in self.listening.clone() {
+            let current_state = self.current_key_status_with_input(input, &vkey);
+            let event = self.flush_key_event(input, t, &vkey, &current_state);
+            if event != KeyboardEvent::KeepReleased {
+                self.last_events.push((vkey, event));
+            }
+            self.key_map[vkey as usize] = current_state;
+        }
+    }
 }
 
 impl Updatable for KeyboardListener {
+    ///
+    /// イベントハンドラが返したエラーは、take_errorで取り出すまで蓄積される
+    ///
     fn update(&mut self, ctx: &mut ggez::Context, t: Clock) {
-        for vkey in &self.listening {
-            let current_state = self.current_key_status(ctx, vkey);
-            self.flush_key_event(ctx, t, &vkey, &current_state);
-            self.key_map[*vkey as usize] = current_state;
-        }
+        self.flush_input(&GgezInputSource::new(ctx), t);
     }
 }
 
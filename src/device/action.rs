@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::Clock;
+use crate::device::*;
+use crate::error;
+use crate::error::{ErrorPolicy, HandlerErrors};
+
+///
+/// # アクションに対応するイベントハンドラの型
+/// 現在の時刻を受け取る
+///
+pub type ActionHandler = Box<dyn Fn(Clock) -> Result<(), String>>;
+
+///
+/// # 入力コンテキストの振る舞い
+///
+/// Blocking: このコンテキストより下のコンテキストへは入力を渡さない
+/// PassThrough: このコンテキストで処理した後、下のコンテキストへも入力を渡す
+///
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ContextMode {
+    Blocking,
+    PassThrough,
+}
+
+///
+/// # 名前付きアクションとVirtualKeyの対応をまとめたもの
+///
+/// メニュー, フィールド, 会話など、場面ごとに生成してActionMapに積む
+///
+/// ## フィールド
+/// ### bindings
+/// アクション名 ====> 割り当てられたVirtualKey
+///
+/// ### handlers
+/// (アクション名, KeyboardEvent) ====> イベントハンドラのベクタ
+///
+pub struct InputContext {
+    name: String,
+    mode: ContextMode,
+    bindings: HashMap<String, Vec<VirtualKey>>,
+    handlers: HashMap<(String, KeyboardEvent), Vec<ActionHandler>>,
+}
+
+impl InputContext {
+    pub fn new(name: &str, mode: ContextMode) -> Self {
+        InputContext {
+            name: name.to_string(),
+            mode: mode,
+            bindings: HashMap::new(),
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_mode(&self) -> ContextMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ContextMode) {
+        self.mode = mode;
+    }
+
+    ///
+    /// アクションにVirtualKeyを割り当てるメソッド
+    /// 一つのアクションに複数のVirtualKeyを割り当てることができる
+    ///
+    pub fn bind(&mut self, action: &str, vkey: VirtualKey) {
+        let keys = self
+            .bindings
+            .entry(action.to_string())
+            .or_insert_with(Vec::new);
+        if !keys.contains(&vkey) {
+            keys.push(vkey);
+        }
+    }
+
+    ///
+    /// アクションへのVirtualKeyの割り当てを全て解除するメソッド
+    ///
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    pub fn get_bindings(&self, action: &str) -> &[VirtualKey] {
+        match self.bindings.get(action) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+
+    ///
+    /// アクションのイベントハンドラを登録するメソッド
+    ///
+    pub fn register_action_handler(
+        &mut self,
+        action: &str,
+        event: KeyboardEvent,
+        f: ActionHandler,
+    ) {
+        self.handlers
+            .entry((action.to_string(), event))
+            .or_insert_with(Vec::new)
+            .push(f);
+    }
+
+    ///
+    /// VirtualKeyが割り当てられたアクションの名前を返すメソッド
+    ///
+    fn actions_for(&self, vkey: VirtualKey) -> Vec<String> {
+        let mut actions: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, keys)| keys.contains(&vkey))
+            .map(|(action, _)| action.clone())
+            .collect();
+        actions.sort();
+        actions
+    }
+
+    fn fire(&self, action: &str, event: KeyboardEvent, t: Clock, errors: &mut HandlerErrors) {
+        if let Some(handlers) = self.handlers.get(&(action.to_string(), event)) {
+            for f in handlers {
+                errors.handle(f(t));
+            }
+        }
+    }
+}
+
+///
+/// # InputContextのスタック
+///
+/// キーイベントは最も上に積まれたコンテキストから順に処理され、
+/// Blockingのコンテキストに到達した時点で処理を終える
+///
+/// ## フィールド
+/// ### contexts
+/// 積まれたコンテキスト。最後の要素が最も上にある
+///
+/// ### held
+/// 現在押されているVirtualKey
+///
+pub struct ActionMap {
+    contexts: Vec<InputContext>,
+    held: HashSet<VirtualKey>,
+    handler_errors: HandlerErrors,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap {
+            contexts: Vec::new(),
+            held: HashSet::new(),
+            handler_errors: HandlerErrors::default(),
+        }
+    }
+
+    pub fn push_context(&mut self, context: InputContext) {
+        self.contexts.push(context);
+    }
+
+    pub fn pop_context(&mut self) -> Option<InputContext> {
+        self.contexts.pop()
+    }
+
+    pub fn top_context(&self) -> Option<&InputContext> {
+        self.contexts.last()
+    }
+
+    pub fn top_context_mut(&mut self) -> Option<&mut InputContext> {
+        self.contexts.last_mut()
+    }
+
+    ///
+    /// 名前でコンテキストを探すメソッド
+    /// 同じ名前のコンテキストが複数ある場合は、上にあるものを返す
+    ///
+    pub fn get_context_mut(&mut self, name: &str) -> Option<&mut InputContext> {
+        self.contexts
+            .iter_mut()
+            .rev()
+            .find(|context| context.name == name)
+    }
+
+    ///
+    /// イベントハンドラがエラーを返した時の振る舞いを設定するメソッド
+    ///
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.handler_errors.set_policy(policy);
+    }
+
+    ///
+    /// feedの実行中に蓄積されたエラーを取り出すメソッド
+    ///
+    pub fn take_error(&mut self) -> Option<error::Error> {
+        self.handler_errors.take()
+    }
+
+    ///
+    /// 入力を受け取るコンテキストを上から順に返す
+    ///
+    fn active_contexts(contexts: &[InputContext]) -> Vec<&InputContext> {
+        let mut active = Vec::new();
+        for context in contexts.iter().rev() {
+            active.push(context);
+            if context.mode == ContextMode::Blocking {
+                break;
+            }
+        }
+
+        active
+    }
+
+    ///
+    /// アクションに割り当てられたVirtualKeyが押されているかを返すメソッド
+    /// 入力を受け取らないコンテキストのアクションは常にfalseとなる
+    ///
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        Self::active_contexts(&self.contexts).iter().any(|context| {
+            context
+                .get_bindings(action)
+                .iter()
+                .any(|vkey| self.held.contains(vkey))
+        })
+    }
+
+    ///
+    /// キーイベントを与えるメソッド
+    /// 処理されたアクションの名前を返す
+    ///
+    pub fn feed(&mut self, vkey: VirtualKey, event: KeyboardEvent, t: Clock) -> Vec<String> {
+        match event {
+            KeyboardEvent::FirstPressed | KeyboardEvent::KeepPressed => {
+                self.held.insert(vkey);
+            }
+            KeyboardEvent::Typed => {
+                self.held.remove(&vkey);
+            }
+            _ => (),
+        }
+
+        let mut fired = Vec::new();
+        for context in Self::active_contexts(&self.contexts) {
+            for action in context.actions_for(vkey) {
+                context.fire(&action, event, t, &mut self.handler_errors);
+                fired.push(action);
+            }
+        }
+
+        fired
+    }
+
+    ///
+    /// KeyboardListenerが直前のupdateで検出したイベントを与えるメソッド
+    ///
+    pub fn feed_listener(&mut self, listener: &KeyboardListener, t: Clock) -> Vec<String> {
+        let mut fired = Vec::new();
+        for (vkey, event) in listener.last_events() {
+            fired.extend(self.feed(*vkey, *event, t));
+        }
+
+        fired
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::core::Clock;
+use crate::device::*;
+use crate::error;
+use crate::error::{ErrorPolicy, HandlerErrors};
+
+pub type CommandId = usize;
+
+///
+/// # コマンド成立時に呼び出されるイベントハンドラの型
+/// 成立した時刻を受け取る
+///
+pub type CommandEventHandler = Box<dyn Fn(Clock) -> Result<(), String>>;
+
+///
+/// # コマンドを構成する入力の一段階
+///
+/// ## フィールド
+/// ### keys
+/// 同時に押されている必要があるVirtualKey
+///
+/// ### max_interval
+/// 一つ前の段階から、この段階が成立するまでに許される時間。最初の段階では無視される
+///
+#[derive(Debug, Clone)]
+pub struct CommandStep {
+    keys: HashSet<VirtualKey>,
+    max_interval: Clock,
+}
+
+impl CommandStep {
+    pub fn new(keys: Vec<VirtualKey>, max_interval: Clock) -> Self {
+        CommandStep {
+            keys: keys.into_iter().collect(),
+            max_interval: max_interval,
+        }
+    }
+
+    ///
+    /// 単一のVirtualKeyからなる段階を生成する
+    ///
+    pub fn key(vkey: VirtualKey, max_interval: Clock) -> Self {
+        Self::new(vec![vkey], max_interval)
+    }
+
+    fn is_satisfied_by(&self, held: &HashSet<VirtualKey>) -> bool {
+        self.keys.is_subset(held)
+    }
+}
+
+struct CommandPattern {
+    id: CommandId,
+    steps: Vec<CommandStep>,
+}
+
+///
+/// # 入力の列からコマンドを検出する構造体
+///
+/// キーが押される, 離されるたびに、押されているキーの集合を時刻と共に記録し、
+/// 登録されたパターンと照合する。パターンの最後の段階は、キーが押された瞬間にのみ成立する
+///
+/// ## フィールド
+/// ### held
+/// 現在押されているキー
+///
+/// ### history
+/// 押されているキーの集合の履歴。新しいものほど後ろにある
+///
+/// ### history_limit
+/// 保持する履歴の最大数
+///
+pub struct CommandDetector {
+    held: HashSet<VirtualKey>,
+    history: VecDeque<(HashSet<VirtualKey>, Clock)>,
+    history_limit: usize,
+    patterns: Vec<CommandPattern>,
+    event_handlers: HashMap<CommandId, Vec<CommandEventHandler>>,
+    next_id: CommandId,
+    handler_errors: HandlerErrors,
+}
+
+impl CommandDetector {
+    pub fn new() -> Self {
+        CommandDetector {
+            held: HashSet::new(),
+            history: VecDeque::new(),
+            history_limit: 32,
+            patterns: Vec::new(),
+            event_handlers: HashMap::new(),
+            next_id: 0,
+            handler_errors: HandlerErrors::default(),
+        }
+    }
+
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit.max(1);
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+
+    ///
+    /// コマンドのパターンを登録するメソッド
+    ///
+    pub fn register_command(&mut self, steps: Vec<CommandStep>) -> CommandId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.patterns.push(CommandPattern {
+            id: id,
+            steps: steps,
+        });
+
+        id
+    }
+
+    ///
+    /// コマンド成立時のイベントハンドラを登録するメソッド
+    ///
+    pub fn register_event_handler(&mut self, id: CommandId, f: CommandEventHandler) {
+        self.event_handlers
+            .entry(id)
+            .or_insert_with(Vec::new)
+            .push(f);
+    }
+
+    ///
+    /// イベントハンドラがエラーを返した時の振る舞いを設定するメソッド
+    ///
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.handler_errors.set_policy(policy);
+    }
+
+    ///
+    /// feedの実行中に蓄積されたエラーを取り出すメソッド
+    ///
+    pub fn take_error(&mut self) -> Option<error::Error> {
+        self.handler_errors.take()
+    }
+
+    ///
+    /// 入力の履歴を消去するメソッド
+    ///
+    pub fn reset(&mut self) {
+        self.held.clear();
+        self.history.clear();
+    }
+
+    fn record(&mut self, t: Clock) {
+        self.history.push_back((self.held.clone(), t));
+        if self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+
+    ///
+    /// パターンが履歴の末尾で成立しているかを調べるメソッド
+    /// 直前の段階と隣の段階のキーだけからなる集合は、同時押しの途中や離す途中とみなして読み飛ばす
+    ///
+    fn is_matched(&self, pattern: &CommandPattern, trigger: VirtualKey) -> bool {
+        let mut steps = pattern.steps.iter().rev();
+        let mut snapshots = self.history.iter().rev();
+
+        let last_step = match steps.next() {
+            Some(step) => step,
+            None => return false,
+        };
+
+        let (latest, mut matched_time) = match snapshots.next() {
+            Some((held, t)) => (held, *t),
+            None => return false,
+        };
+
+        if !last_step.keys.contains(&trigger) || !last_step.is_satisfied_by(latest) {
+            return false;
+        }
+
+        let mut later_step = last_step;
+        for step in steps {
+            let skippable: HashSet<VirtualKey> =
+                step.keys.union(&later_step.keys).copied().collect();
+
+            loop {
+                let (held, t) = match snapshots.next() {
+                    Some((held, t)) => (held, *t),
+                    None => return false,
+                };
+
+                // 時刻が巻き戻っている場合もパニックしないよう、差は0で打ち止めにする
+                if matched_time.saturating_sub(t) > later_step.max_interval {
+                    return false;
+                }
+
+                if step.is_satisfied_by(held) {
+                    matched_time = t;
+                    break;
+                }
+
+                if !held.is_subset(&skippable) {
+                    return false;
+                }
+            }
+
+            later_step = step;
+        }
+
+        true
+    }
+
+    ///
+    /// キーイベントを与えるメソッド
+    /// 成立したコマンドのIDを、段階の多いものから順に返す
+    ///
+    pub fn feed(&mut self, vkey: VirtualKey, event: KeyboardEvent, t: Clock) -> Vec<CommandId> {
+        match event {
+            KeyboardEvent::FirstPressed => {
+                self.held.insert(vkey);
+                self.record(t);
+            }
+            KeyboardEvent::Typed => {
+                self.held.remove(&vkey);
+                self.record(t);
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        }
+
+        let mut matched: Vec<(CommandId, usize)> = self
+            .patterns
+            .iter()
+            .filter(|pattern| self.is_matched(pattern, vkey))
+            .map(|pattern| (pattern.id, pattern.steps.len()))
+            .collect();
+
+        if matched.is_empty() {
+            return Vec::new();
+        }
+
+        // 同じ入力で再びコマンドが成立しないように、最新のもの以外の履歴を消去する
+        let latest = self.history.pop_back();
+        self.history.clear();
+        self.history.extend(latest);
+
+        matched.sort_by(|a, b| b.1.cmp(&a.1));
+        let matched: Vec<CommandId> = matched.into_iter().map(|(id, _)| id).collect();
+
+        for id in &matched {
+            if let Some(handlers) = self.event_handlers.get(id) {
+                for f in handlers {
+                    self.handler_errors.handle(f(t));
+                }
+            }
+        }
+
+        matched
+    }
+
+    ///
+    /// KeyboardListenerが直前のupdateで検出したイベントを与えるメソッド
+    ///
+    pub fn feed_listener(&mut self, listener: &KeyboardListener, t: Clock) -> Vec<CommandId> {
+        let mut matched = Vec::new();
+        for (vkey, event) in listener.last_events() {
+            matched.extend(self.feed(*vkey, *event, t));
+        }
+
+        matched
+    }
+}
@@ -0,0 +1,405 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::device::*;
+use crate::graphics::drawable::*;
+use crate::graphics::object::sub_screen::SubScreen;
+use crate::graphics::object::*;
+use crate::numeric;
+
+///
+/// # フォーカスを受け取ることができることを保証させるトレイト
+///
+/// フォーカスを持つオブジェクトにのみ、virtual_key_eventが配送される
+///
+pub trait Focusable: DrawableComponent {
+    /// フォーカス移動の計算に用いる領域を返す
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect;
+
+    /// フォーカスを得た時の動作
+    fn focus_gained(&mut self, _ctx: &mut ggez::Context) {
+        // Nothing
+    }
+
+    /// フォーカスを失った時の動作
+    fn focus_lost(&mut self, _ctx: &mut ggez::Context) {
+        // Nothing
+    }
+
+    /// フォーカスを受け取ることができるかを返す
+    /// 無効化された状態を表す場合は、これをオーバーライドする
+    fn is_focusable(&self) -> bool {
+        self.is_visible()
+    }
+
+    /// フォーカスを持つ間、vkeyをフォーカス移動より優先して受け取るかを返す
+    /// テキスト入力のキャレット移動などに用いる
+    fn consumes_key(&self, _vkey: VirtualKey) -> bool {
+        false
+    }
+}
+
+///
+/// # フォーカスの移動方向
+///
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+pub type FocusHandler = usize;
+
+struct FocusEntry {
+    handler: FocusHandler,
+    target: Rc<RefCell<dyn Focusable>>,
+    neighbours: HashMap<FocusDirection, FocusHandler>,
+}
+
+///
+/// fromの領域からdirectionの方向にある最も近い領域を探す関数
+/// 進行方向の距離に加え、進行方向と直交する方向のずれを重く評価する
+///
+pub fn find_spatial_neighbour(
+    from: numeric::Rect,
+    candidates: &[(FocusHandler, numeric::Rect)],
+    direction: FocusDirection,
+) -> Option<FocusHandler> {
+    let from_center = numeric::Point2f::new(from.x + (from.w / 2.0), from.y + (from.h / 2.0));
+
+    let mut nearest: Option<(FocusHandler, f32)> = None;
+
+    for (handler, rect) in candidates {
+        let center = numeric::Point2f::new(rect.x + (rect.w / 2.0), rect.y + (rect.h / 2.0));
+        let dx = center.x - from_center.x;
+        let dy = center.y - from_center.y;
+
+        let (forward, side) = match direction {
+            FocusDirection::Left => (-dx, dy),
+            FocusDirection::Right => (dx, dy),
+            FocusDirection::Up => (-dy, dx),
+            FocusDirection::Down => (dy, dx),
+        };
+
+        // 進行方向に存在しないものは候補から外す
+        if forward <= 0.0 {
+            continue;
+        }
+
+        let score = forward + (side.abs() * 2.0);
+        match nearest {
+            Some((_, nearest_score)) if nearest_score <= score => (),
+            _ => nearest = Some((*handler, score)),
+        }
+    }
+
+    nearest.map(|(handler, _)| handler)
+}
+
+///
+/// # キーボードのフォーカスを管理する構造体
+///
+/// フォーカスを持つオブジェクトにのみキーイベントを配送する。
+/// フォーカス移動用のVirtualKeyが押された場合は、明示的に設定された隣接オブジェクト、
+/// 設定されていない場合は配置上最も近いオブジェクトへフォーカスを移動する。
+/// ただし、フォーカスを持つオブジェクトがconsumes_keyでtrueを返すキーは、移動に用いずそのまま配送する
+///
+/// ## フィールド
+/// ### entries
+/// フォーカスを受け取ることができるオブジェクト
+///
+/// ### focused
+/// 現在フォーカスを持つオブジェクト
+///
+/// ### navigation_keys
+/// フォーカス移動に用いるVirtualKeyと移動方向の対応
+///
+pub struct FocusManager {
+    entries: Vec<FocusEntry>,
+    next_handler: FocusHandler,
+    focused: Option<FocusHandler>,
+    navigation_keys: HashMap<VirtualKey, FocusDirection>,
+}
+
+impl FocusManager {
+    pub fn new() -> Self {
+        FocusManager {
+            entries: Vec::new(),
+            next_handler: 0,
+            focused: None,
+            navigation_keys: hash![
+                (VirtualKey::Left, FocusDirection::Left),
+                (VirtualKey::Right, FocusDirection::Right),
+                (VirtualKey::Up, FocusDirection::Up),
+                (VirtualKey::Down, FocusDirection::Down)
+            ],
+        }
+    }
+
+    ///
+    /// フォーカスを受け取るオブジェクトを登録するメソッド
+    ///
+    pub fn register(&mut self, target: Rc<RefCell<dyn Focusable>>) -> FocusHandler {
+        let handler = self.next_handler;
+        self.next_handler += 1;
+
+        self.entries.push(FocusEntry {
+            handler: handler,
+            target: target,
+            neighbours: HashMap::new(),
+        });
+
+        handler
+    }
+
+    ///
+    /// オブジェクトの登録を解除するメソッド
+    /// フォーカスを持っていた場合は、focus_lostを呼び出す
+    ///
+    pub fn unregister(&mut self, ctx: &mut ggez::Context, handler: FocusHandler) {
+        if self.focused == Some(handler) {
+            self.blur(ctx);
+        }
+
+        self.entries.retain(|entry| entry.handler != handler);
+        for entry in &mut self.entries {
+            entry
+                .neighbours
+                .retain(|_, neighbour| *neighbour != handler);
+        }
+    }
+
+    ///
+    /// fromからdirectionの方向へフォーカスを移動した時の移動先を明示的に設定するメソッド
+    ///
+    pub fn set_neighbour(
+        &mut self,
+        from: FocusHandler,
+        direction: FocusDirection,
+        to: FocusHandler,
+    ) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.handler == from) {
+            entry.neighbours.insert(direction, to);
+        }
+    }
+
+    ///
+    /// フォーカス移動に用いるVirtualKeyを設定するメソッド
+    ///
+    pub fn set_navigation_key(&mut self, vkey: VirtualKey, direction: FocusDirection) {
+        self.navigation_keys.insert(vkey, direction);
+    }
+
+    ///
+    /// フォーカス移動に用いるVirtualKeyの設定を全て解除するメソッド
+    ///
+    pub fn clear_navigation_keys(&mut self) {
+        self.navigation_keys.clear();
+    }
+
+    pub fn get_focused(&self) -> Option<FocusHandler> {
+        self.focused
+    }
+
+    fn find_target(&self, handler: FocusHandler) -> Option<Rc<RefCell<dyn Focusable>>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.handler == handler)
+            .map(|entry| entry.target.clone())
+    }
+
+    fn is_focusable_handler(&self, handler: FocusHandler) -> bool {
+        self.find_target(handler)
+            .map_or(false, |target| target.borrow().is_focusable())
+    }
+
+    ///
+    /// 登録されたオブジェクトのうち、フォーカスを受け取ることができる最初のものを返すメソッド
+    ///
+    pub fn first_focusable(&self) -> Option<FocusHandler> {
+        self.entries
+            .iter()
+            .find(|entry| entry.target.borrow().is_focusable())
+            .map(|entry| entry.handler)
+    }
+
+    ///
+    /// vkeyがフォーカス移動に用いられる場合、その移動方向を返すメソッド
+    /// フォーカスを持つオブジェクトがvkeyを受け取る場合はNoneを返す
+    ///
+    pub fn navigation_direction(&self, vkey: VirtualKey) -> Option<FocusDirection> {
+        let direction = self.navigation_keys.get(&vkey).copied()?;
+
+        let consumed = self
+            .focused
+            .and_then(|handler| self.find_target(handler))
+            .map_or(false, |target| target.borrow().consumes_key(vkey));
+
+        if consumed {
+            None
+        } else {
+            Some(direction)
+        }
+    }
+
+    ///
+    /// 指定したオブジェクトにフォーカスを移すメソッド
+    ///
+    pub fn focus(&mut self, ctx: &mut ggez::Context, handler: FocusHandler) {
+        if self.focused == Some(handler) {
+            return;
+        }
+
+        let target = match self.find_target(handler) {
+            Some(target) => target,
+            None => return,
+        };
+
+        self.blur(ctx);
+        target.borrow_mut().focus_gained(ctx);
+        self.focused = Some(handler);
+    }
+
+    ///
+    /// フォーカスを外すメソッド
+    ///
+    pub fn blur(&mut self, ctx: &mut ggez::Context) {
+        if let Some(handler) = self.focused.take() {
+            if let Some(target) = self.find_target(handler) {
+                target.borrow_mut().focus_lost(ctx);
+            }
+        }
+    }
+
+    ///
+    /// directionの方向へフォーカスを移動するメソッド
+    /// 移動した場合はtrueを返す
+    ///
+    pub fn move_focus(&mut self, ctx: &mut ggez::Context, direction: FocusDirection) -> bool {
+        let focused = match self.focused {
+            Some(focused) => focused,
+            None => {
+                // フォーカスが無い場合は、フォーカスを受け取ることができる最初のオブジェクトへ移動する
+                if let Some(handler) = self.first_focusable() {
+                    self.focus(ctx, handler);
+                    return true;
+                }
+                return false;
+            }
+        };
+
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.handler == focused)
+            .unwrap();
+
+        let neighbour = entry
+            .neighbours
+            .get(&direction)
+            .copied()
+            .filter(|neighbour| self.is_focusable_handler(*neighbour));
+
+        let next = match neighbour {
+            Some(neighbour) => Some(neighbour),
+            None => {
+                let from = entry.target.borrow().focus_area(ctx);
+                let candidates: Vec<(FocusHandler, numeric::Rect)> = self
+                    .entries
+                    .iter()
+                    .filter(|entry| {
+                        entry.handler != focused && entry.target.borrow().is_focusable()
+                    })
+                    .map(|entry| (entry.handler, entry.target.borrow().focus_area(ctx)))
+                    .collect();
+                find_spatial_neighbour(from, &candidates, direction)
+            }
+        };
+
+        match next {
+            Some(next) => {
+                self.focus(ctx, next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///
+    /// キーイベントを配送するメソッド
+    /// フォーカス移動用のキーが押され、フォーカスが移動した場合は、フォーカスを持つオブジェクトへ配送しない
+    /// フォーカスを持つオブジェクトが受け取るキーは、フォーカス移動より先に配送する
+    ///
+    pub fn dispatch_key_event(
+        &mut self,
+        ctx: &mut ggez::Context,
+        vkey: VirtualKey,
+        event: KeyboardEvent,
+    ) {
+        if event == KeyboardEvent::FirstPressed {
+            if let Some(direction) = self.navigation_direction(vkey) {
+                if self.move_focus(ctx, direction) {
+                    return;
+                }
+            }
+        }
+
+        if let Some(handler) = self.focused {
+            if let Some(target) = self.find_target(handler) {
+                target.borrow_mut().virtual_key_event(ctx, event, vkey);
+            }
+        }
+    }
+
+    ///
+    /// KeyboardListenerが直前のupdateで検出したイベントを配送するメソッド
+    ///
+    pub fn dispatch_listener_events(
+        &mut self,
+        ctx: &mut ggez::Context,
+        listener: &KeyboardListener,
+    ) {
+        for (vkey, event) in listener.last_events() {
+            self.dispatch_key_event(ctx, *vkey, *event);
+        }
+    }
+}
+
+impl Focusable for UniTexture {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
+
+impl Focusable for UniText {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
+
+impl Focusable for VerticalText {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
+
+impl<T: ?Sized + TextureObject> Focusable for MovableWrap<T> {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
+
+impl<T: MovableObject + TextureObject> Focusable for EffectableWrap<T> {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
+
+impl Focusable for SubScreen {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        self.get_drawing_area(ctx)
+    }
+}
@@ -0,0 +1,467 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use ggez::input::mouse::MouseButton;
+
+use crate::core::{Clock, Updatable};
+use crate::device::*;
+use crate::graphics::drawable::*;
+use crate::graphics::object::sub_screen::SubScreen;
+use crate::graphics::object::*;
+use crate::numeric;
+
+///
+/// # マウスイベントの配送対象となることを保証させるトレイト
+///
+/// 子オブジェクトを持つ場合は、child_pointとchild_dispatcherを実装することで、
+/// SubScreenの内側などに配置された子オブジェクトへイベントを配送できる
+///
+pub trait MouseEventTarget: DrawableComponent {
+    /// pointがこのオブジェクトの上にあるかを返す
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool;
+
+    /// ggez::Contextなしで判定できる場合は、判定に用いる矩形を返す
+    /// Someを返した場合、hit_testより優先される
+    fn hit_area(&self) -> Option<numeric::Rect> {
+        None
+    }
+
+    /// 子オブジェクトの座標系へ変換した座標を返す
+    fn child_point(&self, point: numeric::Point2f) -> numeric::Point2f {
+        point
+    }
+
+    /// 子オブジェクトへイベントを配送するディスパッチャを返す
+    fn child_dispatcher(&mut self) -> Option<&mut MouseEventDispatcher> {
+        None
+    }
+}
+
+pub type MouseTargetHandler = usize;
+
+#[derive(Clone)]
+struct MouseTargetEntry {
+    handler: MouseTargetHandler,
+    target: Rc<RefCell<dyn MouseEventTarget>>,
+}
+
+///
+/// # 登録されたオブジェクトへマウスイベントを配送する構造体
+///
+/// カーソルの下にあるオブジェクトのうち、最も手前に描画されるものから順にイベントを配送する。
+/// 描画順序は描画深度で決まり、同じ深度の場合は後に登録されたものが手前となる
+///
+/// ## フィールド
+/// ### targets
+/// 登録されたオブジェクト
+///
+/// ### hovered
+/// 現在カーソルが重なっているオブジェクト
+///
+/// ### button_map
+/// 最後に記録した各ボタンの状態
+///
+/// ### press_targets
+/// 各ボタンが押された時にイベントを受け取ったオブジェクト
+/// Clickedは、離された位置が同じオブジェクトの上である場合のみ配送する
+///
+#[derive(Clone)]
+pub struct MouseEventDispatcher {
+    targets: Vec<MouseTargetEntry>,
+    next_handler: MouseTargetHandler,
+    hovered: Option<MouseTargetHandler>,
+    button_map: HashMap<MouseButton, MouseButtonStatus>,
+    press_targets: HashMap<MouseButton, MouseTargetHandler>,
+}
+
+impl MouseEventDispatcher {
+    pub fn new() -> Self {
+        MouseEventDispatcher {
+            targets: Vec::new(),
+            next_handler: 0,
+            hovered: None,
+            button_map: hash![
+                (MouseButton::Left, MouseButtonStatus::MouseReleased),
+                (MouseButton::Middle, MouseButtonStatus::MouseReleased),
+                (MouseButton::Right, MouseButtonStatus::MouseReleased)
+            ],
+            press_targets: HashMap::new(),
+        }
+    }
+
+    ///
+    /// イベントの配送対象を登録するメソッド
+    ///
+    pub fn register(&mut self, target: Rc<RefCell<dyn MouseEventTarget>>) -> MouseTargetHandler {
+        let handler = self.next_handler;
+        self.next_handler += 1;
+
+        self.targets.push(MouseTargetEntry {
+            handler: handler,
+            target: target,
+        });
+
+        handler
+    }
+
+    ///
+    /// イベントの配送対象から外すメソッド
+    ///
+    pub fn unregister(&mut self, handler: MouseTargetHandler) {
+        self.targets.retain(|entry| entry.handler != handler);
+
+        if self.hovered == Some(handler) {
+            self.hovered = None;
+        }
+        self.press_targets.retain(|_, target| *target != handler);
+    }
+
+    pub fn clear(&mut self) {
+        self.targets.clear();
+        self.hovered = None;
+        self.press_targets.clear();
+    }
+
+    ///
+    /// 現在カーソルが重なっているオブジェクトを返すメソッド
+    ///
+    pub fn get_hovered(&self) -> Option<MouseTargetHandler> {
+        self.hovered
+    }
+
+    ///
+    /// 手前に描画されるものから順に並べたオブジェクトのリストを返す
+    ///
+    fn front_to_back(&self) -> Vec<(MouseTargetHandler, Rc<RefCell<dyn MouseEventTarget>>)> {
+        let mut targets: Vec<(MouseTargetHandler, Rc<RefCell<dyn MouseEventTarget>>)> = self
+            .targets
+            .iter()
+            .rev()
+            .map(|entry| (entry.handler, entry.target.clone()))
+            .collect();
+
+        // 深度が浅いものほど手前に描画される
+        targets.sort_by_key(|(_, target)| target.borrow().get_drawing_depth());
+        targets
+    }
+
+    fn find_target(
+        &self,
+        handler: MouseTargetHandler,
+    ) -> Option<Rc<RefCell<dyn MouseEventTarget>>> {
+        self.targets
+            .iter()
+            .find(|entry| entry.handler == handler)
+            .map(|entry| entry.target.clone())
+    }
+
+    ///
+    /// 手前に描画されるものから順に、pointと重なる表示中のオブジェクトを返す
+    ///
+    fn hits_with<F>(
+        &self,
+        point: numeric::Point2f,
+        mut hit: F,
+    ) -> Vec<(MouseTargetHandler, Rc<RefCell<dyn MouseEventTarget>>)>
+    where
+        F: FnMut(&dyn MouseEventTarget, numeric::Point2f) -> bool,
+    {
+        self.front_to_back()
+            .into_iter()
+            .filter(|(_, target)| {
+                let target = target.borrow();
+                target.is_visible()
+                    && match target.hit_area() {
+                        Some(area) => area.contains(point),
+                        None => hit(&*target, point),
+                    }
+            })
+            .collect()
+    }
+
+    fn hits(
+        &self,
+        ctx: &mut ggez::Context,
+        point: numeric::Point2f,
+    ) -> Vec<(MouseTargetHandler, Rc<RefCell<dyn MouseEventTarget>>)> {
+        self.hits_with(point, |target, point| target.hit_test(ctx, point))
+    }
+
+    ///
+    /// hit_areaを持つオブジェクトのうち、pointと重なるものを手前から順に返すメソッド
+    /// ggez::Contextを必要としないため、配送順の確認に用いる
+    ///
+    pub fn hit_handlers(&self, point: numeric::Point2f) -> Vec<MouseTargetHandler> {
+        self.hits_with(point, |_, _| false)
+            .into_iter()
+            .map(|(handler, _)| handler)
+            .collect()
+    }
+
+    ///
+    /// pointの最も手前にあるオブジェクトから、子オブジェクトを辿った経路を返すメソッド
+    /// 判定にはhit_areaのみを用いる
+    ///
+    pub fn hit_path(&self, point: numeric::Point2f) -> Vec<MouseTargetHandler> {
+        let (handler, target) = match self.hits_with(point, |_, _| false).into_iter().next() {
+            Some(hit) => hit,
+            None => return Vec::new(),
+        };
+
+        let mut path = vec![handler];
+        let mut target = target.borrow_mut();
+        let child_point = target.child_point(point);
+        if let Some(child) = target.child_dispatcher() {
+            path.extend(child.hit_path(child_point));
+        }
+        path
+    }
+
+    ///
+    /// カーソルが重なっているオブジェクトを更新するメソッド
+    /// 変化した場合は(Leaveを送るオブジェクト, Enterを送るオブジェクト)を返す
+    ///
+    pub fn transition_hover(
+        &mut self,
+        topmost: Option<MouseTargetHandler>,
+    ) -> Option<(Option<MouseTargetHandler>, Option<MouseTargetHandler>)> {
+        if self.hovered == topmost {
+            return None;
+        }
+
+        let leave = self.hovered;
+        self.hovered = topmost;
+        Some((leave, topmost))
+    }
+
+    ///
+    /// ボタンが押された時にイベントを受け取ったオブジェクトを記録するメソッド
+    ///
+    pub fn record_press(&mut self, button: MouseButton, handler: Option<MouseTargetHandler>) {
+        match handler {
+            Some(handler) => self.press_targets.insert(button, handler),
+            None => self.press_targets.remove(&button),
+        };
+    }
+
+    pub fn get_press_target(&self, button: MouseButton) -> Option<MouseTargetHandler> {
+        self.press_targets.get(&button).copied()
+    }
+
+    ///
+    /// ボタンが離された時に、Clickedを配送するオブジェクトを返すメソッド
+    /// hitsは離された位置と重なるオブジェクト。押された時のオブジェクトが含まれない場合はNoneを返す
+    ///
+    pub fn take_click_target(
+        &mut self,
+        button: MouseButton,
+        hits: &[MouseTargetHandler],
+    ) -> Option<MouseTargetHandler> {
+        self.press_targets
+            .remove(&button)
+            .filter(|handler| hits.contains(handler))
+    }
+
+    ///
+    /// イベントを配送し、(処理結果, イベントを受け取ったオブジェクト)を返す
+    /// onlyがSomeの場合は、そのオブジェクトのみに配送する
+    /// 受け取ったオブジェクトは、Consumedを返したもの, なければ最も手前のもの
+    ///
+    fn deliver(
+        &mut self,
+        ctx: &mut ggez::Context,
+        event: MouseButtonEvent,
+        button: MouseButton,
+        point: numeric::Point2f,
+        only: Option<MouseTargetHandler>,
+    ) -> (MouseEventStatus, Option<MouseTargetHandler>) {
+        let mut receiver = None;
+
+        for (handler, target) in self.hits(ctx, point) {
+            if only.map_or(false, |only| only != handler) {
+                continue;
+            }
+            if receiver.is_none() {
+                receiver = Some(handler);
+            }
+
+            let mut target = target.borrow_mut();
+            let child_point = target.child_point(point);
+            if let Some(child) = target.child_dispatcher() {
+                if child.dispatch_button_event(ctx, event, button, child_point)
+                    == MouseEventStatus::Consumed
+                {
+                    return (MouseEventStatus::Consumed, Some(handler));
+                }
+            }
+
+            if target.mouse_button_event(ctx, event, button, point) == MouseEventStatus::Consumed {
+                return (MouseEventStatus::Consumed, Some(handler));
+            }
+        }
+
+        (MouseEventStatus::Ignored, receiver)
+    }
+
+    ///
+    /// ボタンのイベントを配送するメソッド
+    /// 子オブジェクト, オブジェクト自身の順に配送し、Consumedが返された時点で配送を終える
+    /// Clickedは、Pressedを受け取ったオブジェクトの上で離された場合のみ、そのオブジェクトに配送する
+    ///
+    pub fn dispatch_button_event(
+        &mut self,
+        ctx: &mut ggez::Context,
+        event: MouseButtonEvent,
+        button: MouseButton,
+        point: numeric::Point2f,
+    ) -> MouseEventStatus {
+        match event {
+            MouseButtonEvent::Pressed => {
+                let (status, receiver) = self.deliver(ctx, event, button, point, None);
+                self.record_press(button, receiver);
+                status
+            }
+            MouseButtonEvent::Clicked => {
+                let hits: Vec<MouseTargetHandler> = self
+                    .hits(ctx, point)
+                    .into_iter()
+                    .map(|(handler, _)| handler)
+                    .collect();
+
+                match self.take_click_target(button, &hits) {
+                    Some(handler) => self.deliver(ctx, event, button, point, Some(handler)).0,
+                    None => MouseEventStatus::Ignored,
+                }
+            }
+            _ => self.deliver(ctx, event, button, point, None).0,
+        }
+    }
+
+    ///
+    /// カーソルの移動を配送するメソッド
+    /// 最も手前にあるオブジェクトにHoverを配送し、重なるオブジェクトが変化した場合は
+    /// Leave, Enterを配送する
+    ///
+    pub fn dispatch_motion(&mut self, ctx: &mut ggez::Context, point: numeric::Point2f) {
+        let topmost = self.hits(ctx, point).into_iter().next();
+        let topmost_handler = topmost.as_ref().map(|(handler, _)| *handler);
+
+        if let Some((leave, enter)) = self.transition_hover(topmost_handler) {
+            if let Some(leave) = leave {
+                self.send_leave(ctx, leave, point);
+            }
+
+            if let (Some(_), Some((_, target))) = (enter, topmost.as_ref()) {
+                target
+                    .borrow_mut()
+                    .mouse_hover_event(ctx, MouseHoverEvent::Enter, point);
+            }
+        }
+
+        if let Some((_, target)) = topmost {
+            let mut target = target.borrow_mut();
+            target.mouse_hover_event(ctx, MouseHoverEvent::Hover, point);
+
+            let child_point = target.child_point(point);
+            if let Some(child) = target.child_dispatcher() {
+                child.dispatch_motion(ctx, child_point);
+            }
+        }
+    }
+
+    fn send_leave(
+        &mut self,
+        ctx: &mut ggez::Context,
+        handler: MouseTargetHandler,
+        point: numeric::Point2f,
+    ) {
+        if let Some(target) = self.find_target(handler) {
+            let mut target = target.borrow_mut();
+            target.mouse_hover_event(ctx, MouseHoverEvent::Leave, point);
+
+            let child_point = target.child_point(point);
+            if let Some(child) = target.child_dispatcher() {
+                child.leave_all(ctx, child_point);
+            }
+        }
+    }
+
+    ///
+    /// カーソルが重なっているオブジェクトとその子オブジェクトにLeaveを配送するメソッド
+    ///
+    pub fn leave_all(&mut self, ctx: &mut ggez::Context, point: numeric::Point2f) {
+        if let Some(handler) = self.hovered.take() {
+            self.send_leave(ctx, handler, point);
+        }
+    }
+}
+
+impl Updatable for MouseEventDispatcher {
+    fn update(&mut self, ctx: &mut ggez::Context, _t: Clock) {
+        let point = MouseListener::get_position(ctx);
+        self.dispatch_motion(ctx, point);
+
+        for button in &[MouseButton::Left, MouseButton::Middle, MouseButton::Right] {
+            let current = GgezInputSource::new(ctx).mouse_button_status(*button);
+            if current == self.button_map[button] {
+                continue;
+            }
+
+            let event = match current {
+                MouseButtonStatus::MousePressed => MouseButtonEvent::Pressed,
+                MouseButtonStatus::MouseReleased => MouseButtonEvent::Clicked,
+            };
+            self.dispatch_button_event(ctx, event, *button, point);
+            self.button_map.insert(*button, current);
+        }
+    }
+}
+
+impl MouseEventTarget for UniTexture {
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.contains(ctx, point)
+    }
+}
+
+impl MouseEventTarget for UniText {
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.contains(ctx, point)
+    }
+}
+
+impl MouseEventTarget for VerticalText {
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.contains(ctx, point)
+    }
+}
+
+impl<T: ?Sized + TextureObject> MouseEventTarget for MovableWrap<T> {
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.contains(ctx, point)
+    }
+}
+
+impl<T: MovableObject + TextureObject> MouseEventTarget for EffectableWrap<T> {
+    fn hit_test(&self, ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.contains(ctx, point)
+    }
+}
+
+impl MouseEventTarget for SubScreen {
+    fn hit_test(&self, _ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        SubScreen::contains(self, point)
+    }
+
+    fn hit_area(&self) -> Option<numeric::Rect> {
+        Some(self.get_area())
+    }
+
+    fn child_point(&self, point: numeric::Point2f) -> numeric::Point2f {
+        self.relative_point(point)
+    }
+
+    fn child_dispatcher(&mut self) -> Option<&mut MouseEventDispatcher> {
+        Some(self.get_mouse_dispatcher_mut())
+    }
+}
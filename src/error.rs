@@ -0,0 +1,161 @@
+use std::error;
+use std::fmt;
+
+use crate::sound::SoundHandler;
+
+///
+/// # torifuneのエラー
+///
+/// Ggez: ggezが返したエラー
+/// EventHandler: イベントハンドラが返したエラーメッセージのリスト
+/// UnknownSoundHandler: 存在しないSoundHandlerが指定された
+/// ResourceNotFound: 指定されたリソースが見つからない
+/// InvalidManifest: マニフェストの記述が正しくない
+/// InvalidAtlas: テクスチャアトラスの生成, または読み込みに失敗した
+/// InvalidTileMap: タイルマップの記述が正しくない, または対応していない形式である
+///
+#[derive(Debug)]
+pub enum Error {
+    Ggez(ggez::GameError),
+    EventHandler(Vec<String>),
+    UnknownSoundHandler(SoundHandler),
+    ResourceNotFound(String),
+    InvalidManifest(String),
+    InvalidAtlas(String),
+    InvalidTileMap(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Ggez(e) => write!(f, "ggez error: {}", e),
+            Error::EventHandler(messages) => {
+                write!(f, "event handler error: {}", messages.join(", "))
+            }
+            Error::UnknownSoundHandler(handler) => write!(f, "unknown sound handler: {}", handler),
+            Error::ResourceNotFound(name) => write!(f, "resource not found: {}", name),
+            Error::InvalidManifest(message) => write!(f, "invalid manifest: {}", message),
+            Error::InvalidAtlas(message) => write!(f, "invalid atlas: {}", message),
+            Error::InvalidTileMap(message) => write!(f, "invalid tile map: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Ggez(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ggez::GameError> for Error {
+    fn from(e: ggez::GameError) -> Self {
+        Error::Ggez(e)
+    }
+}
+
+///
+/// # イベントハンドラがエラーを返した時の振る舞い
+///
+/// Log: エラーメッセージを標準エラー出力へ出力して処理を続ける
+/// Propagate: エラーを蓄積し、呼び出し元へ返す。蓄積されるのは最新のMAX_HANDLER_ERRORS件まで
+/// Panic: その場でpanicする
+///
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ErrorPolicy {
+    Log,
+    Propagate,
+    Panic,
+}
+
+///
+/// ErrorPolicy::Propagateで蓄積するエラーの最大件数
+/// 取り出されないまま超えた場合は、古いものから捨てる
+///
+pub const MAX_HANDLER_ERRORS: usize = 64;
+
+///
+/// # イベントハンドラが返したエラーをErrorPolicyに従って処理する構造体
+///
+/// Default::defaultではErrorPolicy::Logとなる
+///
+/// ## フィールド
+/// ### policy
+/// エラーを処理する方針
+///
+/// ### errors
+/// Propagateの場合に蓄積されたエラーメッセージ
+///
+pub struct HandlerErrors {
+    policy: ErrorPolicy,
+    errors: Vec<String>,
+}
+
+impl HandlerErrors {
+    pub fn new(policy: ErrorPolicy) -> Self {
+        HandlerErrors {
+            policy: policy,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn get_policy(&self) -> ErrorPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: ErrorPolicy) {
+        self.policy = policy;
+    }
+
+    ///
+    /// イベントハンドラの実行結果を処理するメソッド
+    ///
+    pub fn handle(&mut self, result: std::result::Result<(), String>) {
+        if let Err(x) = result {
+            match self.policy {
+                ErrorPolicy::Log => eprintln!("event handler error: {}", x),
+                ErrorPolicy::Propagate => {
+                    if self.errors.len() >= MAX_HANDLER_ERRORS {
+                        self.errors.remove(0);
+                    }
+                    self.errors.push(x);
+                }
+                ErrorPolicy::Panic => panic!("{}", x),
+            }
+        }
+    }
+
+    ///
+    /// 蓄積されたエラーを取り出すメソッド
+    ///
+    pub fn take(&mut self) -> Option<Error> {
+        if self.errors.is_empty() {
+            None
+        } else {
+            Some(Error::EventHandler(std::mem::replace(
+                &mut self.errors,
+                Vec::new(),
+            )))
+        }
+    }
+
+    ///
+    /// 蓄積されたエラーを取り出し、Resultとして返すメソッド
+    ///
+    pub fn finish(&mut self) -> Result<()> {
+        match self.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for HandlerErrors {
+    fn default() -> Self {
+        HandlerErrors::new(ErrorPolicy::Log)
+    }
+}
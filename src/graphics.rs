@@ -1,2 +1,7 @@
+pub mod atlas;
+pub mod camera;
+pub mod draw_layer;
 pub mod drawable;
 pub mod object;
+pub mod particle;
+pub mod scene_graph;
@@ -0,0 +1,571 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::rc::Rc;
+
+use ggez::graphics as ggraphics;
+
+use crate::error;
+use crate::graphics::object::{TextureObject, TextureRegion, UniTexture};
+use crate::numeric;
+
+///
+/// # ページ上に配置された矩形
+///
+/// ## フィールド
+/// ### page
+/// 配置されたページの番号
+///
+/// ### x, y, w, h
+/// ページ上の位置と大きさ。単位はピクセル
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedRect {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+///
+/// 矩形をシェルフ方式でページに詰める関数
+///
+/// 高さの大きいものから順に、左から右へ並べ、幅が足りなくなったら次の段へ、
+/// 高さが足りなくなったら次のページへ配置する。結果はsizesと同じ順序で返される
+///
+/// ```
+/// use torifune::graphics::atlas::*;
+///
+/// let packed = pack_rects(&[(32, 32), (64, 16), (32, 32)], (64, 64), 0).unwrap();
+/// assert_eq!(packed[0].page, 0);
+/// assert_eq!((packed[1].x, packed[1].y), (0, 32));
+/// ```
+///
+pub fn pack_rects(
+    sizes: &[(u32, u32)],
+    page_size: (u32, u32),
+    padding: u32,
+) -> error::Result<Vec<PackedRect>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1).then(a.cmp(b)));
+
+    let mut packed = vec![
+        PackedRect {
+            page: 0,
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        };
+        sizes.len()
+    ];
+
+    let (mut page, mut x, mut y, mut shelf_height) = (0, 0_u32, 0_u32, 0_u32);
+
+    for index in order {
+        let (w, h) = sizes[index];
+        if w > page_size.0 || h > page_size.1 {
+            return Err(error::Error::InvalidAtlas(format!(
+                "{}x{} image does not fit in {}x{} page",
+                w, h, page_size.0, page_size.1
+            )));
+        }
+
+        if x.saturating_add(w) > page_size.0 {
+            x = 0;
+            y = y.saturating_add(shelf_height);
+            shelf_height = 0;
+        }
+
+        if y.saturating_add(h) > page_size.1 {
+            page += 1;
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+
+        packed[index] = PackedRect {
+            page: page,
+            x: x,
+            y: y,
+            w: w,
+            h: h,
+        };
+
+        x = x.saturating_add(w).saturating_add(padding);
+        shelf_height = shelf_height.max(h.saturating_add(padding));
+    }
+
+    Ok(packed)
+}
+
+///
+/// # TexturePacker形式のJSONに記述された一つの画像
+///
+/// ## フィールド
+/// ### name
+/// 画像の名前
+///
+/// ### rect
+/// ページ上の位置と大きさ。単位はピクセル
+///
+/// ### offset
+/// 余白を取り除いて詰められた場合の、元の画像上での位置(spriteSourceSizeのx, y)
+///
+/// ### source_size
+/// 余白を取り除く前の画像の大きさ(sourceSize)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasFrame {
+    pub name: String,
+    pub rect: numeric::Rect,
+    pub offset: numeric::Vector2f,
+    pub source_size: numeric::Vector2f,
+}
+
+///
+/// TexturePacker形式のJSONを解析する関数
+/// (ページの画像ファイル名, 画像の一覧)を返す
+///
+/// framesはハッシュ形式と配列形式のどちらにも対応する
+/// 回転して詰められた画像(rotatedがtrue)には対応しておらず、InvalidAtlasを返す
+///
+pub fn parse_atlas_json(text: &str) -> error::Result<(String, Vec<AtlasFrame>)> {
+    let invalid = |message: &str| error::Error::InvalidAtlas(message.to_string());
+
+    let root: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| error::Error::InvalidAtlas(e.to_string()))?;
+
+    let image = root["meta"]["image"]
+        .as_str()
+        .ok_or_else(|| invalid("meta.image is missing"))?
+        .to_string();
+
+    let parse_frame = |name: &str, frame: &serde_json::Value| -> error::Result<AtlasFrame> {
+        let value = |object: &str, key: &str| {
+            frame[object][key]
+                .as_f64()
+                .map(|v| v as f32)
+                .ok_or_else(|| {
+                    error::Error::InvalidAtlas(format!("{}: {}.{} is missing", name, object, key))
+                })
+        };
+
+        if frame["rotated"].as_bool().unwrap_or(false) {
+            return Err(error::Error::InvalidAtlas(format!(
+                "{}: rotated frames are not supported",
+                name
+            )));
+        }
+
+        let rect = numeric::Rect::new(
+            value("frame", "x")?,
+            value("frame", "y")?,
+            value("frame", "w")?,
+            value("frame", "h")?,
+        );
+
+        let (offset, source_size) = if frame["trimmed"].as_bool().unwrap_or(false) {
+            (
+                numeric::Vector2f::new(
+                    value("spriteSourceSize", "x")?,
+                    value("spriteSourceSize", "y")?,
+                ),
+                numeric::Vector2f::new(value("sourceSize", "w")?, value("sourceSize", "h")?),
+            )
+        } else {
+            (
+                numeric::Vector2f::new(0.0, 0.0),
+                numeric::Vector2f::new(rect.w, rect.h),
+            )
+        };
+
+        Ok(AtlasFrame {
+            name: name.to_string(),
+            rect: rect,
+            offset: offset,
+            source_size: source_size,
+        })
+    };
+
+    let mut frames = Vec::new();
+    match &root["frames"] {
+        serde_json::Value::Object(map) => {
+            for (name, frame) in map {
+                frames.push(parse_frame(name, frame)?);
+            }
+        }
+        serde_json::Value::Array(list) => {
+            for frame in list {
+                let name = frame["filename"]
+                    .as_str()
+                    .ok_or_else(|| invalid("filename is missing"))?;
+                frames.push(parse_frame(name, frame)?);
+            }
+        }
+        _ => return Err(invalid("frames is missing")),
+    }
+
+    Ok((image, frames))
+}
+
+///
+/// # アトラス上の一つの画像
+///
+/// ## フィールド
+/// ### page
+/// 画像が配置されたページのテクスチャ
+///
+/// ### crop
+/// ページ上の位置。0.0 ~ 1.0に正規化されている
+///
+/// ### size
+/// ページ上に詰められた部分の大きさ。単位はピクセル
+///
+/// ### offset
+/// 余白を取り除いて詰められた場合の、元の画像上での位置。単位はピクセル
+///
+/// ### source_size
+/// 余白を取り除く前の画像の大きさ。単位はピクセル
+///
+#[derive(Clone)]
+pub struct AtlasRegion {
+    pub page: Rc<ggraphics::Image>,
+    pub crop: numeric::Rect,
+    pub size: numeric::Vector2f,
+    pub offset: numeric::Vector2f,
+    pub source_size: numeric::Vector2f,
+}
+
+impl AtlasRegion {
+    ///
+    /// ページ上のピクセル単位の矩形からAtlasRegionを生成する
+    ///
+    pub fn new(page: Rc<ggraphics::Image>, rect: numeric::Rect) -> Self {
+        Self::trimmed(
+            page,
+            rect,
+            numeric::Vector2f::new(0.0, 0.0),
+            numeric::Vector2f::new(rect.w, rect.h),
+        )
+    }
+
+    ///
+    /// 余白を取り除いて詰められた画像のAtlasRegionを生成する
+    ///
+    pub fn trimmed(
+        page: Rc<ggraphics::Image>,
+        rect: numeric::Rect,
+        offset: numeric::Vector2f,
+        source_size: numeric::Vector2f,
+    ) -> Self {
+        let (w, h) = (page.width() as f32, page.height() as f32);
+        AtlasRegion {
+            crop: numeric::Rect::new(rect.x / w, rect.y / h, rect.w / w, rect.h / h),
+            size: numeric::Vector2f::new(rect.w, rect.h),
+            offset: offset,
+            source_size: source_size,
+            page: page,
+        }
+    }
+
+    ///
+    /// UniTextureに設定するTextureRegionを返すメソッド
+    ///
+    pub fn texture_region(&self) -> TextureRegion {
+        TextureRegion {
+            crop: self.crop,
+            size: self.size,
+            offset: self.offset,
+            source_size: self.source_size,
+        }
+    }
+
+    ///
+    /// この領域を描画するUniTextureを生成するメソッド
+    ///
+    pub fn to_texture(
+        &self,
+        pos: numeric::Point2f,
+        scale: numeric::Vector2f,
+        rotation: f32,
+        drawing_depth: i8,
+    ) -> UniTexture {
+        let mut texture = UniTexture::new(self.page.clone(), pos, scale, rotation, drawing_depth);
+        texture.set_atlas_region(self);
+        texture
+    }
+}
+
+///
+/// # 複数の画像をまとめたテクスチャ
+///
+/// 同じページの画像は同じテクスチャを共有するため、描画をまとめやすくなる
+///
+pub struct TextureAtlas {
+    pages: Vec<Rc<ggraphics::Image>>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    ///
+    /// TexturePacker形式のJSONからTextureAtlasを生成する
+    /// ページの画像はJSONと同じディレクトリから読み込まれる
+    ///
+    pub fn from_json(ctx: &mut ggez::Context, path: &str) -> error::Result<TextureAtlas> {
+        if !ggez::filesystem::exists(ctx, path) {
+            return Err(error::Error::ResourceNotFound(path.to_string()));
+        }
+
+        let mut text = String::new();
+        ggez::filesystem::open(ctx, path)?
+            .read_to_string(&mut text)
+            .map_err(|e| error::Error::InvalidAtlas(format!("{}: {}", path, e)))?;
+
+        let (image, frames) = parse_atlas_json(&text)?;
+        let image_path = match path.rfind('/') {
+            Some(index) => format!("{}/{}", &path[..index], image),
+            None => image,
+        };
+
+        if !ggez::filesystem::exists(ctx, &image_path) {
+            return Err(error::Error::ResourceNotFound(image_path));
+        }
+        let page = Rc::new(ggraphics::Image::new(ctx, &image_path)?);
+
+        let regions = frames
+            .into_iter()
+            .map(|frame| {
+                (
+                    frame.name,
+                    AtlasRegion::trimmed(page.clone(), frame.rect, frame.offset, frame.source_size),
+                )
+            })
+            .collect();
+
+        Ok(TextureAtlas {
+            pages: vec![page],
+            regions: regions,
+        })
+    }
+
+    pub fn get_pages(&self) -> &[Rc<ggraphics::Image>] {
+        &self.pages
+    }
+
+    pub fn get_region(&self, key: &str) -> Option<&AtlasRegion> {
+        self.regions.get(key)
+    }
+
+    ///
+    /// 存在しないキーが指定された場合にErrorを返すget_region
+    ///
+    pub fn try_get_region(&self, key: &str) -> error::Result<&AtlasRegion> {
+        self.regions
+            .get(key)
+            .ok_or_else(|| error::Error::ResourceNotFound(format!("atlas region '{}'", key)))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.regions.contains_key(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.regions.keys()
+    }
+
+    ///
+    /// キーで指定した画像を描画するUniTextureを生成するメソッド
+    ///
+    pub fn texture(
+        &self,
+        key: &str,
+        pos: numeric::Point2f,
+        scale: numeric::Vector2f,
+        rotation: f32,
+        drawing_depth: i8,
+    ) -> error::Result<UniTexture> {
+        self.try_get_region(key)
+            .map(|region| region.to_texture(pos, scale, rotation, drawing_depth))
+    }
+}
+
+struct AtlasSource {
+    key: String,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+///
+/// # 読み込み時に画像をページへ詰めてTextureAtlasを生成する構造体
+///
+/// ## フィールド
+/// ### page_size
+/// ページの大きさ。単位はピクセル
+///
+/// ### padding
+/// 隣り合う画像の間隔。フィルタリングによるにじみを防ぐ
+///
+pub struct TextureAtlasBuilder {
+    page_size: (u32, u32),
+    padding: u32,
+    sources: Vec<AtlasSource>,
+}
+
+impl TextureAtlasBuilder {
+    pub fn new(page_size: (u32, u32), padding: u32) -> Self {
+        TextureAtlasBuilder {
+            page_size: page_size,
+            padding: padding,
+            sources: Vec::new(),
+        }
+    }
+
+    ///
+    /// RGBAのピクセル列を追加するメソッド
+    ///
+    pub fn add_rgba(
+        &mut self,
+        key: &str,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    ) -> error::Result<()> {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| {
+                error::Error::InvalidAtlas(format!(
+                    "{}: {}x{} image is too large",
+                    key, width, height
+                ))
+            })?;
+
+        if rgba.len() != expected {
+            return Err(error::Error::InvalidAtlas(format!(
+                "{}: expected {} bytes, but got {}",
+                key,
+                expected,
+                rgba.len()
+            )));
+        }
+
+        self.sources.push(AtlasSource {
+            key: key.to_string(),
+            width: width,
+            height: height,
+            rgba: rgba,
+        });
+
+        Ok(())
+    }
+
+    ///
+    /// ggezのファイルシステムから画像を読み込み、追加するメソッド
+    ///
+    pub fn add_image(
+        &mut self,
+        ctx: &mut ggez::Context,
+        key: &str,
+        path: &str,
+    ) -> error::Result<()> {
+        if !ggez::filesystem::exists(ctx, path) {
+            return Err(error::Error::ResourceNotFound(path.to_string()));
+        }
+
+        let image = ggraphics::Image::new(ctx, path)?;
+        let rgba = image.to_rgba8(ctx)?;
+        self.add_rgba(key, image.width() as u32, image.height() as u32, rgba)
+    }
+
+    ///
+    /// 追加された画像のページ上の配置を返すメソッド
+    /// ページの大きさがテクスチャとして扱える範囲(u16)を超える場合はErrorを返す
+    ///
+    pub fn layout(&self) -> error::Result<Vec<PackedRect>> {
+        let max = u16::max_value() as u32;
+        if self.page_size.0 > max || self.page_size.1 > max {
+            return Err(error::Error::InvalidAtlas(format!(
+                "{}x{} page is larger than {}x{}",
+                self.page_size.0, self.page_size.1, max, max
+            )));
+        }
+
+        let sizes: Vec<(u32, u32)> = self
+            .sources
+            .iter()
+            .map(|source| (source.width, source.height))
+            .collect();
+        pack_rects(&sizes, self.page_size, self.padding)
+    }
+
+    ///
+    /// 追加された画像をページへ詰め、TextureAtlasを生成するメソッド
+    ///
+    pub fn build(self, ctx: &mut ggez::Context) -> error::Result<TextureAtlas> {
+        let packed = self.layout()?;
+
+        let page_count = packed.iter().map(|rect| rect.page + 1).max().unwrap_or(0);
+        let (page_w, page_h) = (self.page_size.0 as usize, self.page_size.1 as usize);
+        let mut page_pixels = vec![vec![0_u8; page_w * page_h * 4]; page_count];
+
+        for (source, rect) in self.sources.iter().zip(packed.iter()) {
+            let pixels = &mut page_pixels[rect.page];
+            let row_len = source.width as usize * 4;
+            for row in 0..source.height as usize {
+                let src_begin = row * row_len;
+                let dst_begin = (((rect.y as usize + row) * page_w) + rect.x as usize) * 4;
+                pixels[dst_begin..(dst_begin + row_len)]
+                    .copy_from_slice(&source.rgba[src_begin..(src_begin + row_len)]);
+            }
+        }
+
+        let mut pages = Vec::new();
+        for pixels in page_pixels {
+            pages.push(Rc::new(ggraphics::Image::from_rgba8(
+                ctx,
+                page_w as u16,
+                page_h as u16,
+                &pixels,
+            )?));
+        }
+
+        let regions = self
+            .sources
+            .iter()
+            .zip(packed.iter())
+            .map(|(source, rect)| {
+                (
+                    source.key.clone(),
+                    AtlasRegion::new(
+                        pages[rect.page].clone(),
+                        numeric::Rect::new(
+                            rect.x as f32,
+                            rect.y as f32,
+                            rect.w as f32,
+                            rect.h as f32,
+                        ),
+                    ),
+                )
+            })
+            .collect();
+
+        Ok(TextureAtlas {
+            pages: pages,
+            regions: regions,
+        })
+    }
+}
+
+impl UniTexture {
+    ///
+    /// アトラス上の領域を描画するように、テクスチャと領域を設定するメソッド
+    /// get_texture_sizeは余白を取り除く前の元の画像の大きさを返し、set_cropやfit_cropは元の画像に対する切り抜きとなる
+    /// 余白を取り除いて詰められた画像は、元の画像上の位置にずらして描画される
+    ///
+    pub fn set_atlas_region(&mut self, region: &AtlasRegion) {
+        self.replace_texture(region.page.clone());
+        self.set_texture_region(Some(region.texture_region()));
+    }
+}
@@ -0,0 +1,397 @@
+use ggez::graphics as ggraphics;
+
+use crate::core::{Clock, Updatable};
+use crate::graphics::object::sub_screen::{self, SubScreen};
+use crate::graphics::object::TextureObject;
+use crate::numeric;
+
+///
+/// Clockで進行する画面の揺れ
+///
+#[derive(Debug, Clone, Copy)]
+struct CameraShake {
+    amplitude: f32,
+    frequency: f32,
+    start: Clock,
+    duration: Clock,
+    seed: f32,
+}
+
+impl CameraShake {
+    ///
+    /// 時刻tでの揺れの大きさを返す。同じ時刻に対しては常に同じ値を返す
+    ///
+    fn offset_at(&self, t: Clock) -> numeric::Vector2f {
+        let elapsed = t.saturating_sub(self.start);
+        if elapsed >= self.duration {
+            return numeric::Vector2f::new(0.0, 0.0);
+        }
+
+        let decay = 1.0 - (elapsed as f32 / self.duration as f32);
+        let phase = elapsed as f32 * self.frequency * std::f32::consts::PI * 2.0;
+        numeric::Vector2f::new(
+            (phase + self.seed).sin() * self.amplitude * decay,
+            ((phase * 1.37) + (self.seed * 2.0)).sin() * self.amplitude * decay,
+        )
+    }
+
+    fn is_finished(&self, t: Clock) -> bool {
+        t.saturating_sub(self.start) >= self.duration
+    }
+}
+
+///
+/// # ワールド座標系の一部を画面に映すカメラ
+///
+/// ## フィールド
+/// ### position
+/// 画面の中央に映るワールド座標
+///
+/// ### viewport
+/// 映す先の画面の大きさ
+///
+/// ### zoom, rotation
+/// 拡大率と回転角(ラジアン)
+///
+/// ### bounds
+/// カメラが映してよいワールド座標の範囲
+///
+/// ### target
+/// 追従する対象の位置
+///
+/// ### smoothing
+/// 追従の滑らかさ。updateごとに目標位置との差のこの割合だけ近づく。1.0の場合は即座に追従する
+///
+/// ### deadzone
+/// 画面中央を基準とした、対象が動いてもカメラが動かない範囲の大きさ
+///
+pub struct Camera {
+    position: numeric::Point2f,
+    viewport: numeric::Vector2f,
+    zoom: f32,
+    rotation: f32,
+    bounds: Option<numeric::Rect>,
+    target: Option<numeric::Point2f>,
+    smoothing: f32,
+    deadzone: numeric::Vector2f,
+    shake: Option<CameraShake>,
+    shake_offset: numeric::Vector2f,
+    shake_count: u32,
+    now: Clock,
+}
+
+impl Camera {
+    pub fn new(viewport: numeric::Vector2f) -> Self {
+        Camera {
+            position: numeric::Point2f::new(viewport.x / 2.0, viewport.y / 2.0),
+            viewport: viewport,
+            zoom: 1.0,
+            rotation: 0.0,
+            bounds: None,
+            target: None,
+            smoothing: 1.0,
+            deadzone: numeric::Vector2f::new(0.0, 0.0),
+            shake: None,
+            shake_offset: numeric::Vector2f::new(0.0, 0.0),
+            shake_count: 0,
+            now: 0,
+        }
+    }
+
+    ///
+    /// SubScreenの大きさを画面の大きさとするカメラを生成する
+    ///
+    pub fn for_sub_screen(ctx: &mut ggez::Context, screen: &SubScreen) -> Self {
+        Camera::new(screen.get_texture_size(ctx))
+    }
+
+    pub fn set_position(&mut self, position: numeric::Point2f) {
+        self.position = position;
+        self.clamp_to_bounds();
+    }
+
+    pub fn get_position(&self) -> numeric::Point2f {
+        self.position
+    }
+
+    pub fn move_diff(&mut self, offset: numeric::Vector2f) {
+        let position = self.position + offset;
+        self.set_position(position);
+    }
+
+    pub fn set_viewport(&mut self, viewport: numeric::Vector2f) {
+        self.viewport = viewport;
+        self.clamp_to_bounds();
+    }
+
+    pub fn get_viewport(&self) -> numeric::Vector2f {
+        self.viewport
+    }
+
+    ///
+    /// 拡大率を設定する。0以下の値は無視される
+    ///
+    pub fn set_zoom(&mut self, zoom: f32) {
+        if zoom > 0.0 {
+            self.zoom = zoom;
+            self.clamp_to_bounds();
+        }
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_rotation(&mut self, rad: f32) {
+        self.rotation = rad;
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    ///
+    /// カメラが映してよい範囲を設定する。Noneの場合は制限しない
+    /// 範囲が映す大きさより小さい場合は、範囲の中央を映す
+    ///
+    pub fn set_bounds(&mut self, bounds: Option<numeric::Rect>) {
+        self.bounds = bounds;
+        self.clamp_to_bounds();
+    }
+
+    pub fn get_bounds(&self) -> Option<numeric::Rect> {
+        self.bounds
+    }
+
+    ///
+    /// 追従する対象の位置を設定する。Noneの場合は追従しない
+    /// 対象が動いた場合は、毎フレームこのメソッドで位置を更新する
+    ///
+    pub fn follow(&mut self, target: Option<numeric::Point2f>) {
+        self.target = target;
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.max(0.0).min(1.0);
+    }
+
+    ///
+    /// デッドゾーンの大きさを設定する。単位はワールド座標
+    ///
+    pub fn set_deadzone(&mut self, size: numeric::Vector2f) {
+        self.deadzone = size;
+    }
+
+    ///
+    /// 画面を揺らすメソッド
+    /// amplitudeの大きさで揺れ始め、durationの時間をかけて収まる。frequencyは時間1あたりの揺れの回数
+    ///
+    pub fn shake(&mut self, amplitude: f32, duration: Clock, frequency: f32) {
+        self.shake_count += 1;
+        self.shake = Some(CameraShake {
+            amplitude: amplitude,
+            frequency: frequency,
+            start: self.now,
+            duration: duration,
+            seed: self.shake_count as f32 * 1.618,
+        });
+    }
+
+    pub fn stop_shake(&mut self) {
+        self.shake = None;
+        self.shake_offset = numeric::Vector2f::new(0.0, 0.0);
+    }
+
+    pub fn is_shaking(&self) -> bool {
+        self.shake.is_some()
+    }
+
+    ///
+    /// 現在映しているワールド座標の範囲を返すメソッド。回転は考慮しない
+    ///
+    pub fn visible_area(&self) -> numeric::Rect {
+        let half = self.half_extent();
+        let center = self.position + self.shake_offset;
+        numeric::Rect::new(
+            center.x - half.x,
+            center.y - half.y,
+            half.x * 2.0,
+            half.y * 2.0,
+        )
+    }
+
+    fn half_extent(&self) -> numeric::Vector2f {
+        numeric::Vector2f::new(
+            self.viewport.x / (2.0 * self.zoom),
+            self.viewport.y / (2.0 * self.zoom),
+        )
+    }
+
+    fn clamp_to_bounds(&mut self) {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        let half = self.half_extent();
+        let clamp = |v: f32, min: f32, size: f32, half: f32| {
+            if size <= half * 2.0 {
+                min + (size / 2.0)
+            } else {
+                v.max(min + half).min(min + size - half)
+            }
+        };
+
+        self.position = numeric::Point2f::new(
+            clamp(self.position.x, bounds.x, bounds.w, half.x),
+            clamp(self.position.y, bounds.y, bounds.h, half.y),
+        );
+    }
+
+    ///
+    /// 追従とデッドゾーンから、カメラが向かうべき位置を計算する
+    ///
+    fn follow_destination(&self, target: numeric::Point2f) -> numeric::Point2f {
+        let axis = |position: f32, target: f32, half: f32| {
+            if target > position + half {
+                target - half
+            } else if target < position - half {
+                target + half
+            } else {
+                position
+            }
+        };
+
+        numeric::Point2f::new(
+            axis(self.position.x, target.x, self.deadzone.x / 2.0),
+            axis(self.position.y, target.y, self.deadzone.y / 2.0),
+        )
+    }
+
+    ///
+    /// 時刻tまでカメラを進めるメソッド
+    /// 追従, 範囲の制限, 揺れを反映する
+    ///
+    pub fn advance(&mut self, t: Clock) {
+        self.now = t;
+
+        if let Some(target) = self.target {
+            let destination = self.follow_destination(target);
+            let position = self.position + ((destination - self.position) * self.smoothing);
+            self.position = position;
+        }
+        self.clamp_to_bounds();
+
+        if let Some(shake) = self.shake {
+            self.shake_offset = shake.offset_at(t);
+            if shake.is_finished(t) {
+                self.stop_shake();
+            }
+        }
+    }
+
+    ///
+    /// ワールド座標を画面上の座標に変換するメソッド
+    ///
+    pub fn world_to_screen(&self, point: numeric::Point2f) -> numeric::Point2f {
+        let (sin, cos) = (-self.rotation).sin_cos();
+        let center = self.position + self.shake_offset;
+        let (x, y) = (
+            (point.x - center.x) * self.zoom,
+            (point.y - center.y) * self.zoom,
+        );
+        numeric::Point2f::new(
+            (self.viewport.x / 2.0) + (x * cos) - (y * sin),
+            (self.viewport.y / 2.0) + (x * sin) + (y * cos),
+        )
+    }
+
+    ///
+    /// 画面上の座標をワールド座標に変換するメソッド
+    ///
+    pub fn screen_to_world(&self, point: numeric::Point2f) -> numeric::Point2f {
+        let (sin, cos) = self.rotation.sin_cos();
+        let center = self.position + self.shake_offset;
+        let (x, y) = (
+            point.x - (self.viewport.x / 2.0),
+            point.y - (self.viewport.y / 2.0),
+        );
+        numeric::Point2f::new(
+            center.x + (((x * cos) - (y * sin)) / self.zoom),
+            center.y + (((x * sin) + (y * cos)) / self.zoom),
+        )
+    }
+
+    ///
+    /// ワールド座標を画面上の座標に変換するDrawParamを返すメソッド
+    ///
+    pub fn to_draw_param(&self) -> ggraphics::DrawParam {
+        let origin = self.world_to_screen(numeric::Point2f::new(0.0, 0.0));
+
+        let mut param = ggraphics::DrawParam::new();
+        param.dest = origin.into();
+        param.rotation = -self.rotation;
+        param.scale = numeric::Vector2f::new(self.zoom, self.zoom).into();
+        param
+    }
+
+    ///
+    /// ワールド座標を画面上の座標に変換する行列を返すメソッド
+    ///
+    pub fn to_matrix(&self) -> nalgebra::Matrix4<f32> {
+        let origin = self.world_to_screen(numeric::Point2f::new(0.0, 0.0));
+
+        nalgebra::Matrix4::new_translation(&nalgebra::Vector3::new(origin.x, origin.y, 0.0))
+            * nalgebra::Matrix4::new_rotation(nalgebra::Vector3::z() * -self.rotation)
+            * nalgebra::Matrix4::new_nonuniform_scaling(&nalgebra::Vector3::new(
+                self.zoom, self.zoom, 1.0,
+            ))
+    }
+
+    ///
+    /// カメラの変換を描画に適用するメソッド
+    /// カメラの変換は現在の変換(SubScreenの変換など)に合成される。
+    /// 以降の描画はワールド座標で行う。end_drawで元に戻す
+    ///
+    pub fn begin_draw(&self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        let current: nalgebra::Matrix4<f32> = ggraphics::transform(ctx).into();
+        ggraphics::push_transform(ctx, Some((current * self.to_matrix()).into()));
+        ggraphics::apply_transformations(ctx)
+    }
+
+    ///
+    /// begin_drawで適用した変換を取り除くメソッド
+    ///
+    pub fn end_draw(&self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        ggraphics::pop_transform(ctx);
+        ggraphics::apply_transformations(ctx)
+    }
+
+    ///
+    /// SubScreenを描画対象にし、カメラの変換を適用してワールドを描画するメソッド
+    /// 描画後は描画対象と変換を元に戻すため、続けて固定のUIを描画できる
+    ///
+    pub fn draw_world<F>(
+        &self,
+        ctx: &mut ggez::Context,
+        screen: &SubScreen,
+        draw: F,
+    ) -> ggez::GameResult<()>
+    where
+        F: FnOnce(&mut ggez::Context) -> ggez::GameResult<()>,
+    {
+        sub_screen::stack_screen(ctx, screen);
+        self.begin_draw(ctx)?;
+        let result = draw(ctx);
+        self.end_draw(ctx)?;
+        sub_screen::pop_screen(ctx);
+
+        result
+    }
+}
+
+impl Updatable for Camera {
+    fn update(&mut self, _ctx: &mut ggez::Context, t: Clock) {
+        self.advance(t);
+    }
+}
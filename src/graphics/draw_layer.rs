@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use crate::graphics::drawable::*;
+
+pub type DrawLayerHandler = usize;
+
+struct DrawLayerEntry {
+    handler: DrawLayerHandler,
+    object: Box<dyn DrawableComponent>,
+    tags: HashSet<String>,
+    depth: i8,
+    serial: u64,
+}
+
+impl DrawLayerEntry {
+    ///
+    /// 描画順のキー。深度が深いものが先、同じ深度の場合は先に追加されたものが先となる
+    ///
+    fn order_key(&self) -> (std::cmp::Reverse<i8>, u64) {
+        (std::cmp::Reverse(self.depth), self.serial)
+    }
+}
+
+///
+/// # 深度順に並んだ描画オブジェクトのリスト
+///
+/// 追加されたオブジェクトは常に深度の深いものから順に並び、同じ深度の場合は追加された順に並ぶ。
+/// オブジェクトはDrawLayerHandlerで指し、タグを付けてまとめて扱うことができる
+///
+/// ## フィールド
+/// ### entries
+/// 描画順に並んだオブジェクト
+///
+/// ### next_handler
+/// 次に発行するDrawLayerHandler
+///
+pub struct DrawLayerList {
+    entries: Vec<DrawLayerEntry>,
+    next_handler: DrawLayerHandler,
+    next_serial: u64,
+    drwob_essential: DrawableObjectEssential,
+}
+
+impl DrawLayerList {
+    pub fn new(drawing_depth: i8) -> Self {
+        DrawLayerList {
+            entries: Vec::new(),
+            next_handler: 0,
+            next_serial: 0,
+            drwob_essential: DrawableObjectEssential::new(true, drawing_depth),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, handler: DrawLayerHandler) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.handler == handler)
+    }
+
+    ///
+    /// 描画順を保つ位置へエントリを挿入する
+    ///
+    fn insert_entry(&mut self, entry: DrawLayerEntry) {
+        let key = entry.order_key();
+        let index = match self
+            .entries
+            .binary_search_by(|other| other.order_key().cmp(&key))
+        {
+            Ok(index) | Err(index) => index,
+        };
+        self.entries.insert(index, entry);
+    }
+
+    ///
+    /// オブジェクトを追加するメソッド
+    ///
+    pub fn add(&mut self, object: Box<dyn DrawableComponent>) -> DrawLayerHandler {
+        self.add_with_tags(object, &[])
+    }
+
+    ///
+    /// タグを付けてオブジェクトを追加するメソッド
+    ///
+    pub fn add_with_tags(
+        &mut self,
+        object: Box<dyn DrawableComponent>,
+        tags: &[&str],
+    ) -> DrawLayerHandler {
+        let handler = self.next_handler;
+        self.next_handler += 1;
+        let serial = self.next_serial;
+        self.next_serial += 1;
+
+        self.insert_entry(DrawLayerEntry {
+            handler: handler,
+            depth: object.get_drawing_depth(),
+            object: object,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            serial: serial,
+        });
+
+        handler
+    }
+
+    ///
+    /// オブジェクトを取り除き、返すメソッド
+    ///
+    pub fn remove(&mut self, handler: DrawLayerHandler) -> Option<Box<dyn DrawableComponent>> {
+        self.position(handler)
+            .map(|index| self.entries.remove(index).object)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn contains(&self, handler: DrawLayerHandler) -> bool {
+        self.position(handler).is_some()
+    }
+
+    pub fn get(&self, handler: DrawLayerHandler) -> Option<&dyn DrawableComponent> {
+        self.position(handler)
+            .map(|index| self.entries[index].object.as_ref())
+    }
+
+    ///
+    /// オブジェクトの可変参照を返すメソッド
+    /// オブジェクトの深度を直接変更した場合、次のdraw, またはrefresh_orderで並び直される
+    ///
+    pub fn get_mut(&mut self, handler: DrawLayerHandler) -> Option<&mut dyn DrawableComponent> {
+        match self.position(handler) {
+            Some(index) => Some(self.entries[index].object.as_mut()),
+            None => None,
+        }
+    }
+
+    ///
+    /// オブジェクトの深度を変更し、描画順を並び直すメソッド
+    ///
+    pub fn set_object_depth(&mut self, handler: DrawLayerHandler, depth: i8) {
+        if let Some(index) = self.position(handler) {
+            let mut entry = self.entries.remove(index);
+            entry.object.set_drawing_depth(depth);
+            entry.depth = depth;
+            self.insert_entry(entry);
+        }
+    }
+
+    ///
+    /// オブジェクトの深度の変化を検出し、描画順を並び直すメソッド
+    ///
+    pub fn refresh_order(&mut self) {
+        let mut changed = false;
+        for entry in self.entries.iter_mut() {
+            let depth = entry.object.get_drawing_depth();
+            if entry.depth != depth {
+                entry.depth = depth;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.entries.sort_by_key(|entry| entry.order_key());
+        }
+    }
+
+    ///
+    /// 描画順にDrawLayerHandlerを返すメソッド
+    ///
+    pub fn handlers(&self) -> Vec<DrawLayerHandler> {
+        self.entries.iter().map(|entry| entry.handler).collect()
+    }
+
+    pub fn add_tag(&mut self, handler: DrawLayerHandler, tag: &str) {
+        if let Some(index) = self.position(handler) {
+            self.entries[index].tags.insert(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, handler: DrawLayerHandler, tag: &str) {
+        if let Some(index) = self.position(handler) {
+            self.entries[index].tags.remove(tag);
+        }
+    }
+
+    pub fn has_tag(&self, handler: DrawLayerHandler, tag: &str) -> bool {
+        self.position(handler)
+            .map_or(false, |index| self.entries[index].tags.contains(tag))
+    }
+
+    ///
+    /// タグが付いたオブジェクトのDrawLayerHandlerを描画順に返すメソッド
+    ///
+    pub fn handlers_with_tag(&self, tag: &str) -> Vec<DrawLayerHandler> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.tags.contains(tag))
+            .map(|entry| entry.handler)
+            .collect()
+    }
+
+    ///
+    /// タグが付いたオブジェクトを全て取り除くメソッド
+    /// 取り除いた数を返す
+    ///
+    pub fn remove_with_tag(&mut self, tag: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| !entry.tags.contains(tag));
+        before - self.entries.len()
+    }
+
+    ///
+    /// タグが付いたオブジェクトを全て表示, または非表示にするメソッド
+    ///
+    pub fn set_visible_with_tag(&mut self, tag: &str, visible: bool) {
+        for entry in self.entries.iter_mut() {
+            if entry.tags.contains(tag) {
+                if visible {
+                    entry.object.appear();
+                } else {
+                    entry.object.hide();
+                }
+            }
+        }
+    }
+
+    ///
+    /// タグが付いたオブジェクトのみを描画順に描画するメソッド
+    ///
+    pub fn draw_with_tag(&mut self, ctx: &mut ggez::Context, tag: &str) -> ggez::GameResult<()> {
+        self.refresh_order();
+
+        for entry in self.entries.iter_mut() {
+            if entry.tags.contains(tag) {
+                entry.object.draw(ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl DrawableComponent for DrawLayerList {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if self.drwob_essential.visible {
+            self.refresh_order();
+
+            for entry in self.entries.iter_mut() {
+                entry.object.draw(ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    #[inline(always)]
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    #[inline(always)]
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    #[inline(always)]
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    #[inline(always)]
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
@@ -42,12 +42,23 @@ pub trait DrawableComponent {
     }
 
     /// マウスイベント時の動作
+    /// Consumedを返した場合、背面のオブジェクトへイベントは伝播しない
     fn mouse_button_event(
         &mut self,
         _ctx: &mut ggez::Context,
         _event_type: MouseButtonEvent,
         _button: MouseButton,
         _point: numeric::Point2f,
+    ) -> MouseEventStatus {
+        MouseEventStatus::Ignored
+    }
+
+    /// マウスカーソルが重なった時の動作
+    fn mouse_hover_event(
+        &mut self,
+        _ctx: &mut ggez::Context,
+        _event_type: MouseHoverEvent,
+        _point: numeric::Point2f,
     ) {
         // Nothing
     }
@@ -1,8 +1,12 @@
 pub mod menu;
+pub mod nine_slice;
 pub mod shadow;
 pub mod shape;
 pub mod sub_screen;
+pub mod text_input;
 pub mod tile_batch;
+pub mod tile_grid;
+pub mod tile_map;
 
 use super::super::numeric;
 use super::drawable::{DrawableComponent, DrawableObject, DrawableObjectEssential};
@@ -381,10 +385,96 @@ impl HasGenericEffectEssential {
     }
 }
 
+///
+/// # テクスチャの一部を一つの画像として扱うための情報
+///
+/// ## フィールド
+/// ### crop
+/// テクスチャ上の位置。0.0 ~ 1.0に正規化されている
+///
+/// ### size
+/// テクスチャ上の部分の大きさ。単位はピクセル
+///
+/// ### offset
+/// 元の画像上での、テクスチャ上の部分の位置。単位はピクセル
+/// 余白を取り除いて詰められた画像で、元の画像上の位置を保つために用いる
+///
+/// ### source_size
+/// 余白を取り除く前の元の画像の大きさ。単位はピクセル
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureRegion {
+    pub crop: numeric::Rect,
+    pub size: numeric::Vector2f,
+    pub offset: numeric::Vector2f,
+    pub source_size: numeric::Vector2f,
+}
+
+impl TextureRegion {
+    ///
+    /// 元の画像に対する切り抜き(0.0 ~ 1.0)から、(テクスチャ全体に対する切り抜き, 描画位置からのずれ)を求めるメソッド
+    /// 切り抜きのうち、余白として取り除かれた部分は描画されない
+    ///
+    /// ```
+    /// use torifune::graphics::object::TextureRegion;
+    /// use torifune::numeric;
+    ///
+    /// let region = TextureRegion {
+    ///     crop: numeric::Rect::new(0.5, 0.0, 0.5, 0.5),
+    ///     size: numeric::Vector2f::new(32.0, 32.0),
+    ///     offset: numeric::Vector2f::new(0.0, 0.0),
+    ///     source_size: numeric::Vector2f::new(32.0, 32.0),
+    /// };
+    /// let (crop, offset) = region.resolve_crop(numeric::Rect::new(0.0, 0.5, 0.5, 0.5));
+    /// assert_eq!(crop, numeric::Rect::new(0.5, 0.25, 0.25, 0.25));
+    /// assert_eq!(offset, numeric::Vector2f::new(0.0, 0.0));
+    /// ```
+    ///
+    pub fn resolve_crop(&self, crop: numeric::Rect) -> (numeric::Rect, numeric::Vector2f) {
+        // 元の画像上の切り抜きの範囲と、テクスチャ上の部分が重なる範囲をピクセル単位で求める
+        let overlap = |begin: f32, length: f32, offset: f32, size: f32| {
+            let low = begin.max(offset).min(offset + size);
+            let high = (begin + length).min(offset + size).max(low);
+            (low, high)
+        };
+        let (left, width) = (crop.x * self.source_size.x, crop.w * self.source_size.x);
+        let (top, height) = (crop.y * self.source_size.y, crop.h * self.source_size.y);
+        let (x0, x1) = overlap(left, width, self.offset.x, self.size.x);
+        let (y0, y1) = overlap(top, height, self.offset.y, self.size.y);
+
+        // テクスチャ上の部分に対する比率を、テクスチャ全体に対する比率へ変換する
+        let ratio = |pixels: f32, size: f32| if size > 0.0 { pixels / size } else { 0.0 };
+        let texture_crop = numeric::Rect::new(
+            self.crop.x + (ratio(x0 - self.offset.x, self.size.x) * self.crop.w),
+            self.crop.y + (ratio(y0 - self.offset.y, self.size.y) * self.crop.h),
+            ratio(x1 - x0, self.size.x) * self.crop.w,
+            ratio(y1 - y0, self.size.y) * self.crop.h,
+        );
+
+        (texture_crop, numeric::Vector2f::new(x0 - left, y0 - top))
+    }
+}
+
+///
+/// # テクスチャを描画する構造体
+///
+/// ## フィールド
+/// ### region
+/// テクスチャの一部のみを描画する場合の、その領域
+///
+/// ### crop
+/// set_cropで設定された切り抜き。regionが設定されている場合は、元の画像に対する切り抜きとして扱う
+///
+/// ### crop_offset
+/// 余白を取り除いて詰められた画像を描画する場合の、描画位置からのずれ
+///
 pub struct UniTexture {
     drwob_essential: DrawableObjectEssential,
     texture: Rc<ggraphics::Image>,
     draw_param: ggraphics::DrawParam,
+    region: Option<TextureRegion>,
+    crop: numeric::Rect,
+    crop_offset: numeric::Vector2f,
 }
 
 impl UniTexture {
@@ -404,14 +494,46 @@ impl UniTexture {
             drwob_essential: DrawableObjectEssential::new(true, drawing_depth),
             texture: texture,
             draw_param: param,
+            region: None,
+            crop: numeric::Rect::new(0.0, 0.0, 1.0, 1.0),
+            crop_offset: numeric::Vector2f::new(0.0, 0.0),
         }
     }
+
+    ///
+    /// テクスチャの一部のみを描画する場合に、その領域を設定するメソッド
+    /// 設定した場合、get_texture_sizeは元の画像の大きさ(source_size)を返し、set_cropは元の画像に対する切り抜きとなる
+    /// 切り抜きは画像全体にリセットされる
+    ///
+    pub fn set_texture_region(&mut self, region: Option<TextureRegion>) {
+        self.region = region;
+        self.set_crop(numeric::Rect::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    pub fn get_texture_region(&self) -> Option<TextureRegion> {
+        self.region
+    }
+
+    fn region_drawing_param(&self) -> ggraphics::DrawParam {
+        let mut param = self.draw_param;
+
+        if self.region.is_some() {
+            // 余白の分のずれは、スケールと回転を適用した上で描画位置に加える
+            let x = self.crop_offset.x * param.scale.x;
+            let y = self.crop_offset.y * param.scale.y;
+            let (sin, cos) = param.rotation.sin_cos();
+            param.dest.x += (x * cos) - (y * sin);
+            param.dest.y += (x * sin) + (y * cos);
+        }
+
+        param
+    }
 }
 
 impl DrawableComponent for UniTexture {
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         if self.drwob_essential.visible {
-            ggraphics::draw(ctx, &*self.texture, self.draw_param)
+            ggraphics::draw(ctx, &*self.texture, self.region_drawing_param())
         } else {
             Ok(())
         }
@@ -484,12 +606,18 @@ impl TextureObject for UniTexture {
 
     #[inline(always)]
     fn set_crop(&mut self, crop: ggraphics::Rect) {
-        self.draw_param.src = crop;
+        self.crop = crop;
+        let (src, offset) = match self.region {
+            Some(region) => region.resolve_crop(crop),
+            None => (crop, numeric::Vector2f::new(0.0, 0.0)),
+        };
+        self.draw_param.src = src;
+        self.crop_offset = offset;
     }
 
     #[inline(always)]
     fn get_crop(&self) -> ggraphics::Rect {
-        self.draw_param.src
+        self.crop
     }
 
     #[inline(always)]
@@ -524,12 +652,20 @@ impl TextureObject for UniTexture {
 
     #[inline(always)]
     fn get_texture_size(&self, _ctx: &mut ggez::Context) -> numeric::Vector2f {
-        numeric::Vector2f::new(self.texture.width() as f32, self.texture.height() as f32)
+        match self.region {
+            Some(region) => region.source_size,
+            None => {
+                numeric::Vector2f::new(self.texture.width() as f32, self.texture.height() as f32)
+            }
+        }
     }
 
     #[inline(always)]
     fn replace_texture(&mut self, texture: Rc<ggraphics::Image>) {
         self.texture = texture;
+        self.region = None;
+        self.draw_param.src = self.crop;
+        self.crop_offset = numeric::Vector2f::new(0.0, 0.0);
     }
 
     #[inline(always)]
@@ -0,0 +1,520 @@
+use std::rc::Rc;
+
+use ggez::graphics as ggraphics;
+
+use crate::graphics::drawable::*;
+use crate::graphics::object::TextureObject;
+use crate::numeric;
+
+///
+/// # 画像の四辺から、伸縮させない部分までの幅
+///
+/// 単位はピクセル
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSliceInsets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl NineSliceInsets {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        NineSliceInsets {
+            left: left,
+            right: right,
+            top: top,
+            bottom: bottom,
+        }
+    }
+
+    ///
+    /// 四辺とも同じ幅のNineSliceInsetsを生成する
+    ///
+    pub fn uniform(inset: f32) -> Self {
+        NineSliceInsets::new(inset, inset, inset, inset)
+    }
+}
+
+///
+/// # 辺と中央の埋め方
+///
+/// Stretch: 画像を引き伸ばして埋める
+/// Tile: 画像を元の大きさのまま並べて埋める。はみ出す部分は切り取る
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NineSliceMode {
+    Stretch,
+    Tile,
+}
+
+///
+/// # 描画する一片
+///
+/// ## フィールド
+/// ### src
+/// テクスチャ上の切り取る範囲。テクスチャ全体を1とした比率
+///
+/// ### dest
+/// 描画する範囲。NineSliceの左上を原点とし、単位はピクセル
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSlicePiece {
+    pub src: numeric::Rect,
+    pub dest: numeric::Rect,
+}
+
+impl NineSlicePiece {
+    ///
+    /// srcの範囲をdestの大きさで描画するためのDrawParamを返すメソッド
+    ///
+    pub fn to_draw_param(
+        &self,
+        texture_size: numeric::Vector2f,
+        color: ggraphics::Color,
+    ) -> ggraphics::DrawParam {
+        ggraphics::DrawParam {
+            src: self.src,
+            dest: numeric::Point2f::new(self.dest.x, self.dest.y).into(),
+            scale: numeric::Vector2f::new(
+                self.dest.w / (self.src.w * texture_size.x),
+                self.dest.h / (self.src.h * texture_size.y),
+            )
+            .into(),
+            color: color,
+            ..Default::default()
+        }
+    }
+}
+
+///
+/// 一つの軸上の区間を(src開始位置, src長さ, dest開始位置, dest長さ)で表す
+///
+type Segment = (f32, f32, f32, f32);
+
+///
+/// 一つの軸上で、伸縮する部分を埋める区間の列を求める関数
+///
+fn fill_segments(
+    src_begin: f32,
+    src_len: f32,
+    dest_begin: f32,
+    dest_len: f32,
+    mode: NineSliceMode,
+) -> Vec<Segment> {
+    if src_len <= 0.0 || dest_len <= 0.0 {
+        return Vec::new();
+    }
+
+    match mode {
+        NineSliceMode::Stretch => vec![(src_begin, src_len, dest_begin, dest_len)],
+        NineSliceMode::Tile => {
+            let mut segments = Vec::new();
+            let mut offset = 0.0;
+            while offset < dest_len {
+                let len = src_len.min(dest_len - offset);
+                segments.push((src_begin, len, dest_begin + offset, len));
+                offset += src_len;
+            }
+            segments
+        }
+    }
+}
+
+///
+/// 一つの軸上の(前の角, 後ろの角)の区間を求める関数
+/// 描画する大きさが角の合計より小さい場合は、角を縮めて描画する
+///
+fn corner_segments(
+    src_begin: f32,
+    src_len: f32,
+    head: f32,
+    tail: f32,
+    dest_len: f32,
+) -> (Segment, Segment, f32) {
+    let ratio = if head + tail > dest_len && head + tail > 0.0 {
+        dest_len / (head + tail)
+    } else {
+        1.0
+    };
+    let (dest_head, dest_tail) = (head * ratio, tail * ratio);
+
+    (
+        (src_begin, head, 0.0, dest_head),
+        (
+            src_begin + src_len - tail,
+            tail,
+            dest_len - dest_tail,
+            dest_tail,
+        ),
+        dest_len - dest_head - dest_tail,
+    )
+}
+
+///
+/// ナインスライスの各片を求める関数
+///
+/// sourceはテクスチャ上の使用する範囲(ピクセル)。texture_sizeはテクスチャ全体の大きさ
+/// 辺はedge_mode, 中央はcenter_modeで埋める
+///
+/// ```
+/// use torifune::graphics::object::nine_slice::*;
+/// use torifune::numeric::{Rect, Vector2f};
+///
+/// let pieces = compute_nine_slice(
+///     Rect::new(0.0, 0.0, 30.0, 30.0),
+///     Vector2f::new(30.0, 30.0),
+///     NineSliceInsets::uniform(10.0),
+///     Vector2f::new(100.0, 50.0),
+///     NineSliceMode::Stretch,
+///     NineSliceMode::Stretch,
+/// );
+/// assert_eq!(pieces.len(), 9);
+/// assert_eq!(pieces[4].dest, Rect::new(10.0, 10.0, 80.0, 30.0));
+/// ```
+///
+pub fn compute_nine_slice(
+    source: numeric::Rect,
+    texture_size: numeric::Vector2f,
+    insets: NineSliceInsets,
+    target: numeric::Vector2f,
+    edge_mode: NineSliceMode,
+    center_mode: NineSliceMode,
+) -> Vec<NineSlicePiece> {
+    let (left, right, x_middle) =
+        corner_segments(source.x, source.w, insets.left, insets.right, target.x);
+    let (top, bottom, y_middle) =
+        corner_segments(source.y, source.h, insets.top, insets.bottom, target.y);
+
+    let x_edge = fill_segments(
+        left.0 + left.1,
+        source.w - left.1 - right.1,
+        left.3,
+        x_middle,
+        edge_mode,
+    );
+    let x_center = fill_segments(
+        left.0 + left.1,
+        source.w - left.1 - right.1,
+        left.3,
+        x_middle,
+        center_mode,
+    );
+    let y_edge = fill_segments(
+        top.0 + top.1,
+        source.h - top.1 - bottom.1,
+        top.3,
+        y_middle,
+        edge_mode,
+    );
+    let y_center = fill_segments(
+        top.0 + top.1,
+        source.h - top.1 - bottom.1,
+        top.3,
+        y_middle,
+        center_mode,
+    );
+
+    let mut pieces = Vec::new();
+    let mut push = |xs: &[Segment], ys: &[Segment]| {
+        for y in ys {
+            for x in xs {
+                if x.1 <= 0.0 || x.3 <= 0.0 || y.1 <= 0.0 || y.3 <= 0.0 {
+                    continue;
+                }
+                pieces.push(NineSlicePiece {
+                    src: numeric::Rect::new(
+                        x.0 / texture_size.x,
+                        y.0 / texture_size.y,
+                        x.1 / texture_size.x,
+                        y.1 / texture_size.y,
+                    ),
+                    dest: numeric::Rect::new(x.2, y.2, x.3, y.3),
+                });
+            }
+        }
+    };
+
+    push(&[left], &[top]);
+    push(&x_edge, &[top]);
+    push(&[right], &[top]);
+    push(&[left], &y_edge);
+    push(&x_center, &y_center);
+    push(&[right], &y_edge);
+    push(&[left], &[bottom]);
+    push(&x_edge, &[bottom]);
+    push(&[right], &[bottom]);
+
+    pieces
+}
+
+///
+/// # 角を伸縮させずに任意の大きさで描画できる枠
+///
+/// 画像を四辺の幅で9つに分け、角はそのまま、辺と中央は伸縮, または並べて描画する
+///
+/// ## フィールド
+/// ### size
+/// 描画する大きさ。スケールは更にこの大きさに掛けられる
+///
+/// ### color
+/// 描画色。各片の色として設定する
+///
+/// ### dirty
+/// 各片を積み直す必要があるかどうか
+///
+pub struct NineSlice {
+    drwob_essential: DrawableObjectEssential,
+    texture: Rc<ggraphics::Image>,
+    sprite_batch: ggraphics::spritebatch::SpriteBatch,
+    insets: NineSliceInsets,
+    size: numeric::Vector2f,
+    edge_mode: NineSliceMode,
+    center_mode: NineSliceMode,
+    crop: ggraphics::Rect,
+    color: ggraphics::Color,
+    draw_param: ggraphics::DrawParam,
+    dirty: bool,
+}
+
+impl NineSlice {
+    pub fn new(
+        texture: Rc<ggraphics::Image>,
+        insets: NineSliceInsets,
+        pos: numeric::Point2f,
+        size: numeric::Vector2f,
+        drawing_depth: i8,
+    ) -> NineSlice {
+        let mut param = ggraphics::DrawParam::new();
+        param.dest = pos.into();
+
+        NineSlice {
+            drwob_essential: DrawableObjectEssential::new(true, drawing_depth),
+            sprite_batch: ggraphics::spritebatch::SpriteBatch::new((*texture).clone()),
+            texture: texture,
+            insets: insets,
+            size: size,
+            edge_mode: NineSliceMode::Stretch,
+            center_mode: NineSliceMode::Stretch,
+            crop: ggraphics::Rect::one(),
+            color: ggraphics::WHITE,
+            draw_param: param,
+            dirty: true,
+        }
+    }
+
+    pub fn set_size(&mut self, size: numeric::Vector2f) {
+        self.size = size;
+        self.dirty = true;
+    }
+
+    pub fn get_size(&self) -> numeric::Vector2f {
+        self.size
+    }
+
+    pub fn set_insets(&mut self, insets: NineSliceInsets) {
+        self.insets = insets;
+        self.dirty = true;
+    }
+
+    pub fn get_insets(&self) -> NineSliceInsets {
+        self.insets
+    }
+
+    ///
+    /// 辺と中央の埋め方を設定するメソッド
+    ///
+    pub fn set_mode(&mut self, edge_mode: NineSliceMode, center_mode: NineSliceMode) {
+        self.edge_mode = edge_mode;
+        self.center_mode = center_mode;
+        self.dirty = true;
+    }
+
+    pub fn get_edge_mode(&self) -> NineSliceMode {
+        self.edge_mode
+    }
+
+    pub fn get_center_mode(&self) -> NineSliceMode {
+        self.center_mode
+    }
+
+    fn image_size(&self) -> numeric::Vector2f {
+        numeric::Vector2f::new(self.texture.width() as f32, self.texture.height() as f32)
+    }
+
+    ///
+    /// 現在の設定での各片を返すメソッド
+    ///
+    pub fn pieces(&self) -> Vec<NineSlicePiece> {
+        let image_size = self.image_size();
+        compute_nine_slice(
+            numeric::Rect::new(
+                self.crop.x * image_size.x,
+                self.crop.y * image_size.y,
+                self.crop.w * image_size.x,
+                self.crop.h * image_size.y,
+            ),
+            image_size,
+            self.insets,
+            self.size,
+            self.edge_mode,
+            self.center_mode,
+        )
+    }
+
+    fn rebuild(&mut self) {
+        let image_size = self.image_size();
+
+        self.sprite_batch.clear();
+        for piece in self.pieces() {
+            self.sprite_batch
+                .add(piece.to_draw_param(image_size, self.color));
+        }
+        self.dirty = false;
+    }
+}
+
+impl DrawableComponent for NineSlice {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if self.drwob_essential.visible {
+            if self.dirty {
+                self.rebuild();
+            }
+            ggraphics::draw(ctx, &self.sprite_batch, self.draw_param)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    #[inline(always)]
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    #[inline(always)]
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    #[inline(always)]
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    #[inline(always)]
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
+
+impl DrawableObject for NineSlice {
+    #[inline(always)]
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.draw_param.dest = pos.into();
+    }
+
+    #[inline(always)]
+    fn get_position(&self) -> numeric::Point2f {
+        self.draw_param.dest.into()
+    }
+
+    #[inline(always)]
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        self.draw_param.dest.x += offset.x;
+        self.draw_param.dest.y += offset.y;
+    }
+}
+
+impl TextureObject for NineSlice {
+    #[inline(always)]
+    fn set_scale(&mut self, scale: numeric::Vector2f) {
+        self.draw_param.scale = scale.into();
+    }
+
+    #[inline(always)]
+    fn get_scale(&self) -> numeric::Vector2f {
+        self.draw_param.scale.into()
+    }
+
+    #[inline(always)]
+    fn set_rotation(&mut self, rad: f32) {
+        self.draw_param.rotation = rad;
+    }
+
+    #[inline(always)]
+    fn get_rotation(&self) -> f32 {
+        self.draw_param.rotation
+    }
+
+    ///
+    /// テクスチャ上の使用する範囲を設定する。アトラス上の一部を枠として使う場合に用いる
+    ///
+    fn set_crop(&mut self, crop: ggraphics::Rect) {
+        self.crop = crop;
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    fn get_crop(&self) -> ggraphics::Rect {
+        self.crop
+    }
+
+    fn set_drawing_color(&mut self, color: ggraphics::Color) {
+        self.color = color;
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    fn get_drawing_color(&self) -> ggraphics::Color {
+        self.color
+    }
+
+    fn set_alpha(&mut self, alpha: f32) {
+        self.color.a = alpha;
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    fn get_alpha(&self) -> f32 {
+        self.color.a
+    }
+
+    #[inline(always)]
+    fn set_transform_offset(&mut self, offset: numeric::Point2f) {
+        self.draw_param.offset = offset.into();
+    }
+
+    #[inline(always)]
+    fn get_transform_offset(&self) -> numeric::Point2f {
+        self.draw_param.offset.into()
+    }
+
+    ///
+    /// 描画する大きさを返す
+    ///
+    #[inline(always)]
+    fn get_texture_size(&self, _ctx: &mut ggez::Context) -> numeric::Vector2f {
+        self.size
+    }
+
+    fn replace_texture(&mut self, texture: Rc<ggraphics::Image>) {
+        self.sprite_batch.set_image((*texture).clone());
+        self.texture = texture;
+        self.dirty = true;
+    }
+
+    fn set_color(&mut self, color: ggraphics::Color) {
+        self.set_drawing_color(color);
+    }
+
+    #[inline(always)]
+    fn get_color(&mut self) -> ggraphics::Color {
+        self.color
+    }
+}
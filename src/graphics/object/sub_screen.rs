@@ -3,6 +3,8 @@ use std::rc::Rc;
 
 use ggez::graphics as ggraphics;
 
+use crate::device::mouse_dispatch::*;
+use crate::error;
 use crate::graphics::object::*;
 
 ///
@@ -25,6 +27,7 @@ pub struct SubScreen {
     draw_param: ggraphics::DrawParam,
     size: numeric::Vector2f,
     back_color: ggraphics::Color,
+    mouse_dispatcher: MouseEventDispatcher,
 }
 
 impl SubScreen {
@@ -34,24 +37,37 @@ impl SubScreen {
         depth: i8,
         back_color: ggraphics::Color,
     ) -> SubScreen {
+        match SubScreen::try_new(ctx, pos, depth, back_color) {
+            Ok(screen) => screen,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    ///
+    /// Canvasの生成に失敗した場合にErrorを返すnew
+    ///
+    pub fn try_new(
+        ctx: &mut ggez::Context,
+        pos: ggraphics::Rect,
+        depth: i8,
+        back_color: ggraphics::Color,
+    ) -> error::Result<SubScreen> {
         let mut dparam = ggraphics::DrawParam::default();
         dparam.dest = numeric::Point2f::new(pos.x, pos.y).into();
 
-        SubScreen {
-            canvas: Rc::new(
-                ggraphics::Canvas::new(
-                    ctx,
-                    pos.w as u16,
-                    pos.h as u16,
-                    ggez::conf::NumSamples::One,
-                )
-                .unwrap(),
-            ),
+        Ok(SubScreen {
+            canvas: Rc::new(ggraphics::Canvas::new(
+                ctx,
+                pos.w as u16,
+                pos.h as u16,
+                ggez::conf::NumSamples::One,
+            )?),
             drwob_essential: DrawableObjectEssential::new(true, depth),
             draw_param: dparam,
             size: numeric::Vector2f::new(pos.w, pos.h),
             back_color: back_color,
-        }
+            mouse_dispatcher: MouseEventDispatcher::new(),
+        })
     }
 
     pub fn relative_point(&self, abs_pos: numeric::Point2f) -> numeric::Point2f {
@@ -74,6 +90,41 @@ impl SubScreen {
     pub fn set_filter(&mut self, mode: ggraphics::FilterMode) {
         Rc::get_mut(&mut self.canvas).unwrap().set_filter(mode);
     }
+
+    ///
+    /// SubScreenが描画される領域を返すメソッド
+    ///
+    pub fn get_area(&self) -> numeric::Rect {
+        numeric::Rect::new(
+            self.draw_param.dest.x,
+            self.draw_param.dest.y,
+            self.size.x,
+            self.size.y,
+        )
+    }
+
+    ///
+    /// SubScreenの内側に配置したオブジェクトを、マウスイベントの配送対象に登録するメソッド
+    /// 配送時の座標はSubScreenからの相対座標となる
+    ///
+    pub fn register_mouse_target(
+        &mut self,
+        target: Rc<RefCell<dyn MouseEventTarget>>,
+    ) -> MouseTargetHandler {
+        self.mouse_dispatcher.register(target)
+    }
+
+    pub fn unregister_mouse_target(&mut self, handler: MouseTargetHandler) {
+        self.mouse_dispatcher.unregister(handler);
+    }
+
+    pub fn get_mouse_dispatcher(&self) -> &MouseEventDispatcher {
+        &self.mouse_dispatcher
+    }
+
+    pub fn get_mouse_dispatcher_mut(&mut self) -> &mut MouseEventDispatcher {
+        &mut self.mouse_dispatcher
+    }
 }
 
 impl DrawableComponent for SubScreen {
@@ -0,0 +1,566 @@
+use ggez::graphics as ggraphics;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+
+use crate::core::{Clock, Updatable};
+use crate::device::focus::Focusable;
+use crate::device::VirtualKey;
+use crate::graphics::object::*;
+
+///
+/// # 入力を受け付ける文字の種類
+///
+/// Any: 制御文字以外の全ての文字
+/// Numeric: 数字
+/// Alphanumeric: ASCIIの英数字
+/// Ascii: 表示可能なASCII文字
+/// Custom: 関数がtrueを返す文字
+///
+#[derive(Clone, Copy)]
+pub enum TextFilter {
+    Any,
+    Numeric,
+    Alphanumeric,
+    Ascii,
+    Custom(fn(char) -> bool),
+}
+
+impl TextFilter {
+    pub fn accepts(&self, c: char) -> bool {
+        if c.is_control() {
+            return false;
+        }
+
+        match self {
+            TextFilter::Any => true,
+            TextFilter::Numeric => c.is_ascii_digit(),
+            TextFilter::Alphanumeric => c.is_ascii_alphanumeric(),
+            TextFilter::Ascii => c.is_ascii_graphic() || c == ' ',
+            TextFilter::Custom(f) => f(c),
+        }
+    }
+}
+
+///
+/// # 入力中の文字列を保持する構造体
+///
+/// 描画に依存しないため、単体で扱うことができる。位置は全て文字単位で扱う
+///
+/// ## フィールド
+/// ### chars
+/// 確定済みの文字列
+///
+/// ### cursor
+/// カーソルの位置
+///
+/// ### anchor
+/// 選択範囲の起点。選択していない場合はNone
+///
+/// ### preedit
+/// IMEで変換中の文字列。カーソルの位置に表示される
+///
+pub struct TextBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+    anchor: Option<usize>,
+    max_length: Option<usize>,
+    filter: TextFilter,
+    preedit: String,
+}
+
+impl TextBuffer {
+    pub fn new(max_length: Option<usize>, filter: TextFilter) -> Self {
+        TextBuffer {
+            chars: Vec::new(),
+            cursor: 0,
+            anchor: None,
+            max_length: max_length,
+            filter: filter,
+            preedit: String::new(),
+        }
+    }
+
+    pub fn get_text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    ///
+    /// 文字列を置き換えるメソッド
+    /// フィルタと最大文字数は適用される
+    ///
+    pub fn set_text(&mut self, text: &str) {
+        self.chars.clear();
+        self.cursor = 0;
+        self.anchor = None;
+        self.insert_str(text);
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_max_length(&mut self, max_length: Option<usize>) {
+        self.max_length = max_length;
+    }
+
+    pub fn set_filter(&mut self, filter: TextFilter) {
+        self.filter = filter;
+    }
+
+    ///
+    /// 選択範囲を(開始位置, 終了位置)で返すメソッド
+    ///
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(anchor) if anchor != self.cursor => {
+                Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection()
+            .map(|(begin, end)| self.chars[begin..end].iter().collect())
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.chars.len();
+    }
+
+    ///
+    /// 選択範囲の文字列を削除するメソッド
+    /// 削除した場合はtrueを返す
+    ///
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((begin, end)) => {
+                self.chars.drain(begin..end);
+                self.cursor = begin;
+                self.anchor = None;
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    ///
+    /// カーソルの位置に文字を挿入するメソッド
+    /// フィルタで弾かれた場合、最大文字数を超える場合はfalseを返す
+    ///
+    pub fn insert_char(&mut self, c: char) -> bool {
+        if !self.filter.accepts(c) {
+            return false;
+        }
+
+        self.delete_selection();
+
+        if let Some(max_length) = self.max_length {
+            if self.chars.len() >= max_length {
+                return false;
+            }
+        }
+
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+        true
+    }
+
+    ///
+    /// カーソルの位置に文字列を挿入するメソッド
+    /// 挿入できた文字数を返す
+    ///
+    pub fn insert_str(&mut self, text: &str) -> usize {
+        text.chars().filter(|c| self.insert_char(*c)).count()
+    }
+
+    ///
+    /// カーソルの前の文字を削除するメソッド
+    ///
+    pub fn backspace(&mut self) {
+        if self.delete_selection() || self.cursor == 0 {
+            return;
+        }
+
+        self.cursor -= 1;
+        self.chars.remove(self.cursor);
+    }
+
+    ///
+    /// カーソルの後ろの文字を削除するメソッド
+    ///
+    pub fn delete(&mut self) {
+        if self.delete_selection() || self.cursor >= self.chars.len() {
+            return;
+        }
+
+        self.chars.remove(self.cursor);
+    }
+
+    fn move_cursor(&mut self, pos: usize, select: bool) {
+        if select {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.cursor);
+            }
+        } else {
+            self.anchor = None;
+        }
+
+        self.cursor = pos.min(self.chars.len());
+    }
+
+    ///
+    /// カーソルを左へ移動するメソッド
+    /// selectがtrueの場合は、選択範囲を広げる
+    ///
+    pub fn move_left(&mut self, select: bool) {
+        match self.selection() {
+            Some((begin, _)) if !select => self.move_cursor(begin, false),
+            _ => self.move_cursor(self.cursor.saturating_sub(1), select),
+        }
+    }
+
+    ///
+    /// カーソルを右へ移動するメソッド
+    /// selectがtrueの場合は、選択範囲を広げる
+    ///
+    pub fn move_right(&mut self, select: bool) {
+        match self.selection() {
+            Some((_, end)) if !select => self.move_cursor(end, false),
+            _ => self.move_cursor(self.cursor + 1, select),
+        }
+    }
+
+    pub fn move_home(&mut self, select: bool) {
+        self.move_cursor(0, select);
+    }
+
+    pub fn move_end(&mut self, select: bool) {
+        self.move_cursor(self.chars.len(), select);
+    }
+
+    ///
+    /// IMEで変換中の文字列を設定するメソッド
+    /// 空文字列を渡すと変換中の文字列を消去する
+    ///
+    pub fn set_preedit(&mut self, preedit: &str) {
+        self.preedit = preedit.to_string();
+    }
+
+    pub fn get_preedit(&self) -> &str {
+        &self.preedit
+    }
+
+    ///
+    /// 変換中の文字列を確定するメソッド
+    ///
+    pub fn commit_preedit(&mut self) {
+        let preedit = std::mem::replace(&mut self.preedit, String::new());
+        self.insert_str(&preedit);
+    }
+
+    ///
+    /// 変換中の文字列をカーソルの位置に差し込んだ、表示用の文字列を返すメソッド
+    ///
+    pub fn display_text(&self) -> String {
+        let mut text: String = self.chars[..self.cursor].iter().collect();
+        text.push_str(&self.preedit);
+        text.extend(self.chars[self.cursor..].iter());
+        text
+    }
+}
+
+///
+/// # 文字列の入力欄
+///
+/// EventHandler::text_input_eventで受け取った文字をtext_input_eventに、
+/// EventHandler::key_down_eventで受け取ったキーをkey_down_eventに渡して利用する
+///
+/// ## フィールド
+/// ### buffer
+/// 入力中の文字列
+///
+/// ### text
+/// 表示用のテキスト
+///
+/// ### focused
+/// フォーカスを持っているか。生成時は持たず、FocusManagerからフォーカスを得るまで入力を受け付けない
+///
+/// ### blink_interval
+/// キャレットが点滅する間隔
+///
+/// ### blink_origin
+/// キャレットの点滅を開始した時刻。入力があるとリセットされる
+///
+pub struct TextInput {
+    buffer: TextBuffer,
+    text: UniText,
+    font_info: FontInformation,
+    focused: bool,
+    caret_visible: bool,
+    blink_interval: Clock,
+    blink_origin: Clock,
+    last_update: Clock,
+}
+
+impl TextInput {
+    pub fn new(
+        pos: numeric::Point2f,
+        drawing_depth: i8,
+        font_info: FontInformation,
+        max_length: Option<usize>,
+        filter: TextFilter,
+    ) -> Self {
+        TextInput {
+            buffer: TextBuffer::new(max_length, filter),
+            text: UniText::new(
+                String::new(),
+                pos,
+                numeric::Vector2f::new(1.0, 1.0),
+                0.0,
+                drawing_depth,
+                font_info,
+            ),
+            font_info: font_info,
+            focused: false,
+            caret_visible: true,
+            blink_interval: 30,
+            blink_origin: 0,
+            last_update: 0,
+        }
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn get_buffer(&self) -> &TextBuffer {
+        &self.buffer
+    }
+
+    pub fn get_text(&self) -> String {
+        self.buffer.get_text()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.buffer.set_text(text);
+        self.on_edit();
+    }
+
+    pub fn set_blink_interval(&mut self, interval: Clock) {
+        self.blink_interval = interval;
+    }
+
+    ///
+    /// IMEで変換中の文字列を設定するメソッド
+    ///
+    pub fn set_preedit(&mut self, preedit: &str) {
+        self.buffer.set_preedit(preedit);
+        self.on_edit();
+    }
+
+    ///
+    /// 入力された文字を受け取るメソッド
+    ///
+    pub fn text_input_event(&mut self, c: char) {
+        if self.focused && self.buffer.insert_char(c) {
+            self.on_edit();
+        }
+    }
+
+    ///
+    /// 押されたキーに応じてカーソル移動や削除を行うメソッド
+    ///
+    pub fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        if !self.focused {
+            return;
+        }
+
+        let select = keymods.contains(KeyMods::SHIFT);
+        match keycode {
+            KeyCode::Back => self.buffer.backspace(),
+            KeyCode::Delete => self.buffer.delete(),
+            KeyCode::Left => self.buffer.move_left(select),
+            KeyCode::Right => self.buffer.move_right(select),
+            KeyCode::Home => self.buffer.move_home(select),
+            KeyCode::End => self.buffer.move_end(select),
+            KeyCode::A if keymods.contains(KeyMods::CTRL) => self.buffer.select_all(),
+            _ => return,
+        }
+
+        self.on_edit();
+    }
+
+    fn on_edit(&mut self) {
+        self.text.replace_text(&self.buffer.display_text());
+        self.blink_origin = self.last_update;
+        self.caret_visible = true;
+    }
+
+    ///
+    /// 表示文字列の先頭からcount文字分の幅を計算するメソッド
+    ///
+    fn prefix_width(&self, ctx: &mut ggez::Context, count: usize) -> f32 {
+        let prefix: String = self.buffer.display_text().chars().take(count).collect();
+        let mut text = ggraphics::Text::new(prefix);
+        text.set_font(
+            self.font_info.font,
+            ggraphics::Scale {
+                x: self.font_info.scale.x,
+                y: self.font_info.scale.y,
+            },
+        );
+        text.width(ctx) as f32
+    }
+
+    fn draw_rect(
+        &self,
+        ctx: &mut ggez::Context,
+        begin: usize,
+        end: usize,
+        height: f32,
+        color: ggraphics::Color,
+    ) -> ggez::GameResult<()> {
+        let pos = self.text.get_position();
+        let x1 = self.prefix_width(ctx, begin);
+        let x2 = self.prefix_width(ctx, end);
+        let y = pos.y + self.font_info.scale.y - height;
+
+        let mesh = ggraphics::Mesh::new_rectangle(
+            ctx,
+            ggraphics::DrawMode::fill(),
+            numeric::Rect::new(pos.x + x1, y, (x2 - x1).max(1.0), height),
+            color,
+        )?;
+        ggraphics::draw(ctx, &mesh, ggraphics::DrawParam::default())
+    }
+}
+
+impl DrawableComponent for TextInput {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if !self.is_visible() {
+            return Ok(());
+        }
+
+        let cursor = self.buffer.get_cursor();
+        let preedit_len = self.buffer.get_preedit().chars().count();
+        let mut selection_color = self.font_info.color;
+        selection_color.a *= 0.3;
+
+        if let Some((begin, end)) = self.buffer.selection() {
+            // 変換中の文字列はカーソルの位置に差し込まれているため、その分ずらす
+            let shift = |i: usize| if i > cursor { i + preedit_len } else { i };
+            self.draw_rect(
+                ctx,
+                shift(begin),
+                shift(end),
+                self.font_info.scale.y,
+                selection_color,
+            )?;
+        }
+
+        self.text.draw(ctx)?;
+
+        if preedit_len > 0 {
+            // 変換中の文字列には下線を引く
+            self.draw_rect(ctx, cursor, cursor + preedit_len, 1.0, self.font_info.color)?;
+        }
+
+        if self.focused && self.caret_visible {
+            let caret = cursor + preedit_len;
+            self.draw_rect(
+                ctx,
+                caret,
+                caret,
+                self.font_info.scale.y,
+                self.font_info.color,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.text.hide();
+    }
+
+    fn appear(&mut self) {
+        self.text.appear();
+    }
+
+    fn is_visible(&self) -> bool {
+        self.text.is_visible()
+    }
+
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.text.set_drawing_depth(depth);
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        self.text.get_drawing_depth()
+    }
+}
+
+impl DrawableObject for TextInput {
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.text.set_position(pos);
+    }
+
+    fn get_position(&self) -> numeric::Point2f {
+        self.text.get_position()
+    }
+
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        self.text.move_diff(offset);
+    }
+}
+
+impl Updatable for TextInput {
+    fn update(&mut self, _ctx: &mut ggez::Context, t: Clock) {
+        self.last_update = t;
+
+        if self.blink_interval > 0 {
+            let elapsed = t.saturating_sub(self.blink_origin);
+            self.caret_visible = (elapsed / self.blink_interval) % 2 == 0;
+        }
+    }
+}
+
+impl Focusable for TextInput {
+    fn focus_area(&self, ctx: &mut ggez::Context) -> numeric::Rect {
+        let pos = self.text.get_position();
+        let width = self.prefix_width(ctx, usize::max_value());
+        numeric::Rect::new(pos.x, pos.y, width.max(1.0), self.font_info.scale.y)
+    }
+
+    fn focus_gained(&mut self, _ctx: &mut ggez::Context) {
+        self.focused = true;
+        self.blink_origin = self.last_update;
+        self.caret_visible = true;
+    }
+
+    fn focus_lost(&mut self, _ctx: &mut ggez::Context) {
+        self.focused = false;
+        self.buffer.commit_preedit();
+        self.text.replace_text(&self.buffer.display_text());
+    }
+
+    fn consumes_key(&self, vkey: VirtualKey) -> bool {
+        // 左右のキーはキャレットの移動に用いる
+        match vkey {
+            VirtualKey::Left | VirtualKey::Right => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::HashSet;
+
+use ggez::graphics as ggraphics;
+
+use crate::core::{Clock, Updatable};
+use crate::error;
+use crate::graphics::drawable::*;
+use crate::graphics::object::tile_batch::TileBatch;
+use crate::numeric;
+
+pub type TileAnimationHandler = usize;
+
+///
+/// # タイルのアニメーション定義
+///
+/// ## フィールド
+/// ### frames
+/// 画像上のタイル位置と、そのフレームを表示する時間の組
+///
+/// ### total
+/// 全フレームの表示時間の合計
+///
+#[derive(Debug, Clone)]
+pub struct TileAnimation {
+    frames: Vec<(numeric::Vector2u, Clock)>,
+    total: Clock,
+}
+
+impl TileAnimation {
+    ///
+    /// 表示時間が0のフレームは取り除かれる
+    ///
+    pub fn new(frames: Vec<(numeric::Vector2u, Clock)>) -> Self {
+        let frames: Vec<(numeric::Vector2u, Clock)> = frames
+            .into_iter()
+            .filter(|(_, duration)| *duration > 0)
+            .collect();
+        let total = frames.iter().map(|(_, duration)| duration).sum();
+
+        TileAnimation {
+            frames: frames,
+            total: total,
+        }
+    }
+
+    pub fn get_frames(&self) -> &[(numeric::Vector2u, Clock)] {
+        &self.frames
+    }
+
+    pub fn total_duration(&self) -> Clock {
+        self.total
+    }
+
+    ///
+    /// 時刻tに表示するフレームの番号を返すメソッド。アニメーションは繰り返す
+    ///
+    /// ```
+    /// use torifune::graphics::object::tile_grid::TileAnimation;
+    /// use torifune::numeric::Vector2u;
+    ///
+    /// let animation = TileAnimation::new(vec![(Vector2u::new(0, 0), 10), (Vector2u::new(1, 0), 5)]);
+    /// assert_eq!(animation.frame_index_at(9), 0);
+    /// assert_eq!(animation.frame_index_at(10), 1);
+    /// assert_eq!(animation.frame_index_at(15), 0);
+    /// ```
+    ///
+    pub fn frame_index_at(&self, t: Clock) -> usize {
+        if self.total == 0 {
+            return 0;
+        }
+
+        let mut rest = t % self.total;
+        for (index, (_, duration)) in self.frames.iter().enumerate() {
+            if rest < *duration {
+                return index;
+            }
+            rest -= duration;
+        }
+
+        0
+    }
+
+    ///
+    /// 時刻tに表示する画像上のタイル位置を返すメソッド
+    ///
+    pub fn frame_at(&self, t: Clock) -> Option<numeric::Vector2u> {
+        self.frames
+            .get(self.frame_index_at(t))
+            .map(|(tile_pos, _)| *tile_pos)
+    }
+}
+
+///
+/// # グリッド上に置くタイル
+///
+/// Static: 画像上のタイル位置を指定した、変化しないタイル
+/// Animated: 登録したアニメーションを指定したタイル
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridTile {
+    Static(numeric::Vector2u),
+    Animated(TileAnimationHandler),
+}
+
+///
+/// # チャンクに分割されたタイルの配置
+///
+/// タイルの変更やアニメーションのフレームの切り替わりを、チャンク単位の変更として記録する
+/// 描画は行わないため、描画はTileGridBatchを用いる
+///
+/// ## フィールド
+/// ### grid_size
+/// グリッドの大きさ。単位はタイル
+///
+/// ### chunk_size
+/// 一つのチャンクに含まれるタイルの数
+///
+/// ### dirty
+/// チャンクごとの、再構築が必要かどうか
+///
+/// ### chunk_animations
+/// チャンクごとの、含まれるアニメーション。フレームが切り替わった時に再構築するチャンクを求めるために使う
+///
+/// ### frame_indices
+/// アニメーションごとの、現在表示しているフレームの番号
+///
+pub struct TileGrid {
+    grid_size: numeric::Vector2u,
+    chunk_size: numeric::Vector2u,
+    tiles: Vec<Option<GridTile>>,
+    dirty: Vec<bool>,
+    chunk_animations: Vec<HashSet<TileAnimationHandler>>,
+    animations: Vec<TileAnimation>,
+    frame_indices: Vec<usize>,
+    now: Clock,
+}
+
+impl TileGrid {
+    ///
+    /// chunk_sizeの各要素が0の場合は1として扱う
+    /// タイルの総数がu32に収まらない場合はエラーを返す
+    ///
+    pub fn new(grid_size: numeric::Vector2u, chunk_size: numeric::Vector2u) -> error::Result<Self> {
+        let tile_count = grid_size.x.checked_mul(grid_size.y).ok_or_else(|| {
+            error::Error::InvalidTileMap(format!(
+                "grid size {}x{} is too large",
+                grid_size.x, grid_size.y
+            ))
+        })?;
+
+        // チャンクの数はタイルの総数を超えないため、溢れることはない
+        let chunk_size = numeric::Vector2u::new(chunk_size.x.max(1), chunk_size.y.max(1));
+        let chunk_count = (Self::chunk_count_of(grid_size.x, chunk_size.x)
+            * Self::chunk_count_of(grid_size.y, chunk_size.y)) as usize;
+
+        Ok(TileGrid {
+            grid_size: grid_size,
+            chunk_size: chunk_size,
+            tiles: vec![None; tile_count as usize],
+            dirty: vec![false; chunk_count],
+            chunk_animations: vec![HashSet::new(); chunk_count],
+            animations: Vec::new(),
+            frame_indices: Vec::new(),
+            now: 0,
+        })
+    }
+
+    fn chunk_count_of(size: u32, chunk: u32) -> u32 {
+        (size / chunk) + if size % chunk == 0 { 0 } else { 1 }
+    }
+
+    pub fn get_grid_size(&self) -> numeric::Vector2u {
+        self.grid_size
+    }
+
+    pub fn get_chunk_size(&self) -> numeric::Vector2u {
+        self.chunk_size
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    fn tile_index(&self, pos: numeric::Vector2u) -> Option<usize> {
+        if pos.x < self.grid_size.x && pos.y < self.grid_size.y {
+            Some((pos.y * self.grid_size.x + pos.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// タイル座標が含まれるチャンクの番号を返すメソッド
+    ///
+    pub fn chunk_of(&self, pos: numeric::Vector2u) -> Option<usize> {
+        self.tile_index(pos)?;
+        let columns = Self::chunk_count_of(self.grid_size.x, self.chunk_size.x);
+        Some(((pos.y / self.chunk_size.y) * columns + (pos.x / self.chunk_size.x)) as usize)
+    }
+
+    ///
+    /// チャンクに含まれるタイル座標の範囲を返すメソッド
+    /// 返す値は(左上のタイル座標, 右下のタイル座標の次)
+    ///
+    pub fn chunk_range(&self, chunk: usize) -> (numeric::Vector2u, numeric::Vector2u) {
+        let columns = Self::chunk_count_of(self.grid_size.x, self.chunk_size.x);
+        let columns = columns.max(1) as usize;
+        let (cx, cy) = (
+            (chunk % columns) as u32,
+            (chunk / columns).min(u32::max_value() as usize) as u32,
+        );
+        // 範囲外のチャンクは空の範囲となる
+        let begin = numeric::Vector2u::new(
+            cx.saturating_mul(self.chunk_size.x),
+            cy.saturating_mul(self.chunk_size.y),
+        );
+        let end = numeric::Vector2u::new(
+            begin
+                .x
+                .saturating_add(self.chunk_size.x)
+                .min(self.grid_size.x),
+            begin
+                .y
+                .saturating_add(self.chunk_size.y)
+                .min(self.grid_size.y),
+        );
+        (begin, end)
+    }
+
+    ///
+    /// アニメーションを登録するメソッド
+    ///
+    pub fn add_animation(&mut self, animation: TileAnimation) -> TileAnimationHandler {
+        self.frame_indices.push(animation.frame_index_at(self.now));
+        self.animations.push(animation);
+        self.animations.len() - 1
+    }
+
+    pub fn get_animation(&self, handler: TileAnimationHandler) -> Option<&TileAnimation> {
+        self.animations.get(handler)
+    }
+
+    ///
+    /// タイルを置くメソッド。Noneの場合はタイルを取り除く
+    /// 変更があった場合のみ、タイルを含むチャンクが再構築の対象となる
+    ///
+    pub fn set_tile(&mut self, pos: numeric::Vector2u, tile: Option<GridTile>) {
+        let index = match self.tile_index(pos) {
+            Some(index) => index,
+            None => return,
+        };
+
+        if self.tiles[index] != tile {
+            self.tiles[index] = tile;
+            let chunk = self.chunk_of(pos).unwrap();
+            if let Some(GridTile::Animated(handler)) = tile {
+                self.chunk_animations[chunk].insert(handler);
+            }
+            self.dirty[chunk] = true;
+        }
+    }
+
+    pub fn clear_tile(&mut self, pos: numeric::Vector2u) {
+        self.set_tile(pos, None);
+    }
+
+    ///
+    /// 全てのタイルを取り除くメソッド
+    ///
+    pub fn clear(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            *tile = None;
+        }
+        for (dirty, animations) in self.dirty.iter_mut().zip(self.chunk_animations.iter_mut()) {
+            *dirty = true;
+            animations.clear();
+        }
+    }
+
+    pub fn get_tile(&self, pos: numeric::Vector2u) -> Option<GridTile> {
+        self.tile_index(pos).and_then(|index| self.tiles[index])
+    }
+
+    ///
+    /// タイルが現在表示する画像上のタイル位置を返すメソッド
+    ///
+    pub fn resolve_tile(&self, tile: GridTile) -> Option<numeric::Vector2u> {
+        match tile {
+            GridTile::Static(tile_pos) => Some(tile_pos),
+            GridTile::Animated(handler) => {
+                let animation = self.animations.get(handler)?;
+                animation
+                    .get_frames()
+                    .get(self.frame_indices[handler])
+                    .map(|(tile_pos, _)| *tile_pos)
+            }
+        }
+    }
+
+    ///
+    /// 時刻tまでアニメーションを進めるメソッド
+    /// フレームが切り替わったアニメーションを含むチャンクが再構築の対象となる
+    ///
+    pub fn advance(&mut self, t: Clock) {
+        self.now = t;
+
+        for (handler, animation) in self.animations.iter().enumerate() {
+            let index = animation.frame_index_at(t);
+            if self.frame_indices[handler] == index {
+                continue;
+            }
+            self.frame_indices[handler] = index;
+
+            for (dirty, animations) in self.dirty.iter_mut().zip(self.chunk_animations.iter()) {
+                if animations.contains(&handler) {
+                    *dirty = true;
+                }
+            }
+        }
+    }
+
+    pub fn is_dirty(&self, chunk: usize) -> bool {
+        self.dirty.get(chunk).cloned().unwrap_or(false)
+    }
+
+    ///
+    /// 全てのチャンクを再構築の対象とするメソッド
+    ///
+    pub fn mark_all_dirty(&mut self) {
+        for dirty in self.dirty.iter_mut() {
+            *dirty = true;
+        }
+    }
+
+    ///
+    /// 再構築が必要なチャンクの番号を返し、記録を消すメソッド
+    ///
+    pub fn take_dirty_chunks(&mut self) -> Vec<usize> {
+        let mut chunks = Vec::new();
+        for (chunk, dirty) in self.dirty.iter_mut().enumerate() {
+            if *dirty {
+                chunks.push(chunk);
+                *dirty = false;
+            }
+        }
+        chunks
+    }
+
+    ///
+    /// チャンクに含まれるタイルを、(タイル座標, 表示する画像上のタイル位置)の組で返すメソッド
+    /// チャンクに含まれるアニメーションの記録もここで更新する
+    ///
+    pub fn chunk_tiles(&mut self, chunk: usize) -> Vec<(numeric::Vector2u, numeric::Vector2u)> {
+        let (begin, end) = self.chunk_range(chunk);
+        let mut tiles = Vec::new();
+        let mut animations = HashSet::new();
+
+        for y in begin.y..end.y {
+            for x in begin.x..end.x {
+                let pos = numeric::Vector2u::new(x, y);
+                let tile = match self.get_tile(pos) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                if let GridTile::Animated(handler) = tile {
+                    animations.insert(handler);
+                }
+                if let Some(tile_pos) = self.resolve_tile(tile) {
+                    tiles.push((pos, tile_pos));
+                }
+            }
+        }
+
+        if let Some(entry) = self.chunk_animations.get_mut(chunk) {
+            *entry = animations;
+        }
+
+        tiles
+    }
+}
+
+///
+/// # TileGridをチャンクごとのTileBatchで描画するオブジェクト
+///
+/// タイルの変更やアニメーションがあった場合、変更のあったチャンクのTileBatchのみを積み直す
+///
+pub struct TileGridBatch {
+    grid: TileGrid,
+    chunks: Vec<TileBatch>,
+    tile_size: numeric::Vector2u,
+    position: numeric::Point2f,
+    drwob_essential: DrawableObjectEssential,
+}
+
+impl TileGridBatch {
+    pub fn new(
+        image: ggraphics::Image,
+        tile_size: numeric::Vector2u,
+        grid_size: numeric::Vector2u,
+        chunk_size: numeric::Vector2u,
+        pos: numeric::Point2f,
+        draw_depth: i8,
+    ) -> error::Result<Self> {
+        let grid = TileGrid::new(grid_size, chunk_size)?;
+        let chunks = (0..grid.chunk_count())
+            .map(|_| TileBatch::new(image.clone(), tile_size, pos, draw_depth))
+            .collect();
+
+        Ok(TileGridBatch {
+            grid: grid,
+            chunks: chunks,
+            tile_size: tile_size,
+            position: pos,
+            drwob_essential: DrawableObjectEssential::new(true, draw_depth),
+        })
+    }
+
+    pub fn get_grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    pub fn get_grid_mut(&mut self) -> &mut TileGrid {
+        &mut self.grid
+    }
+
+    pub fn add_animation(&mut self, animation: TileAnimation) -> TileAnimationHandler {
+        self.grid.add_animation(animation)
+    }
+
+    pub fn set_tile(&mut self, pos: numeric::Vector2u, tile: Option<GridTile>) {
+        self.grid.set_tile(pos, tile);
+    }
+
+    pub fn clear_tile(&mut self, pos: numeric::Vector2u) {
+        self.grid.clear_tile(pos);
+    }
+
+    pub fn get_tile(&self, pos: numeric::Vector2u) -> Option<GridTile> {
+        self.grid.get_tile(pos)
+    }
+
+    ///
+    /// 変更のあったチャンクのTileBatchを積み直すメソッド
+    /// 積み直したチャンクの数を返す
+    ///
+    pub fn rebuild_dirty_chunks(&mut self) -> usize {
+        let chunks = self.grid.take_dirty_chunks();
+
+        for chunk in chunks.iter() {
+            let tiles = self.grid.chunk_tiles(*chunk);
+            let batch = &mut self.chunks[*chunk];
+
+            batch.clear_batch();
+            for (pos, tile_pos) in tiles {
+                batch.add_batch_tile_position(
+                    tile_pos,
+                    numeric::Point2f::new(
+                        pos.x as f32 * self.tile_size.x as f32,
+                        pos.y as f32 * self.tile_size.y as f32,
+                    ),
+                    numeric::Vector2f::new(1.0, 1.0),
+                    ggraphics::WHITE,
+                );
+            }
+        }
+
+        chunks.len()
+    }
+}
+
+impl Updatable for TileGridBatch {
+    fn update(&mut self, _ctx: &mut ggez::Context, t: Clock) {
+        self.grid.advance(t);
+    }
+}
+
+impl DrawableComponent for TileGridBatch {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if self.is_visible() {
+            self.rebuild_dirty_chunks();
+
+            for batch in self.chunks.iter_mut() {
+                batch.draw(ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
+
+impl DrawableObject for TileGridBatch {
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.position = pos;
+        for batch in self.chunks.iter_mut() {
+            batch.set_position(pos);
+        }
+    }
+
+    fn get_position(&self) -> numeric::Point2f {
+        self.position
+    }
+
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        let pos = self.position + offset;
+        self.set_position(pos);
+    }
+}
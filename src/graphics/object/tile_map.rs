@@ -0,0 +1,1288 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::str::FromStr;
+
+use ggez::graphics as ggraphics;
+
+use crate::error;
+use crate::graphics::drawable::*;
+use crate::graphics::object::tile_batch::TileBatch;
+use crate::numeric;
+
+///
+/// GIDの上位3bitは反転フラグとして使われる。反転には対応しないため、読み込み時に取り除く
+///
+const GID_FLIP_FLAGS: u32 = 0xe000_0000;
+
+pub type TileProperties = HashMap<String, String>;
+
+fn invalid(message: &str) -> error::Error {
+    error::Error::InvalidTileMap(message.to_string())
+}
+
+///
+/// 縦横の数の積を求める関数。u32に収まらない場合はエラーを返す
+///
+fn checked_area(name: &str, width: u32, height: u32) -> error::Result<u32> {
+    width.checked_mul(height).ok_or_else(|| {
+        error::Error::InvalidTileMap(format!("{}: {}x{} is too large", name, width, height))
+    })
+}
+
+///
+/// # タイルセット
+///
+/// ## フィールド
+/// ### first_gid
+/// このタイルセットの最初のタイルに割り当てられたGID
+///
+/// ### source
+/// 外部タイルセット(.tsx, .json)を参照している場合、そのパス
+/// 参照先を読み込むまで、画像などの情報は空になる
+///
+/// ### image
+/// タイルセットの画像のパス。マップ(または外部タイルセット)のファイルからの相対パス
+///
+/// ### tile_properties
+/// タイルセット内のIDごとのプロパティ
+///
+/// ### collision
+/// 衝突判定を持つタイルのID。プロパティcollisionがtrue, または衝突形状を持つタイルが含まれる
+///
+#[derive(Debug, Clone, Default)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub name: String,
+    pub source: Option<String>,
+    pub image: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub tile_properties: HashMap<u32, TileProperties>,
+    pub collision: HashSet<u32>,
+}
+
+impl Tileset {
+    ///
+    /// GIDがこのタイルセットに含まれていれば、タイルセット内のIDを返すメソッド
+    ///
+    pub fn local_id(&self, gid: u32) -> Option<u32> {
+        if gid >= self.first_gid && gid - self.first_gid < self.tile_count {
+            Some(gid - self.first_gid)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// タイルセット内のIDから、画像上のタイルの左上の位置を返すメソッド。単位はピクセル
+    ///
+    pub fn tile_image_position(&self, local_id: u32) -> numeric::Vector2u {
+        let columns = self.columns.max(1);
+        let step = |index: u32, tile: u32| {
+            self.margin
+                .saturating_add(index.saturating_mul(tile.saturating_add(self.spacing)))
+        };
+        numeric::Vector2u::new(
+            step(local_id % columns, self.tile_width),
+            step(local_id / columns, self.tile_height),
+        )
+    }
+
+    ///
+    /// 外部タイルセットの内容を取り込むメソッド。first_gidとsourceはそのまま残る
+    ///
+    pub fn merge_external(&mut self, external: Tileset) {
+        let first_gid = self.first_gid;
+        let source = self.source.take();
+        *self = external;
+        self.first_gid = first_gid;
+        self.source = source;
+    }
+
+    ///
+    /// タイルセットの画像の大きさを設定するメソッド
+    /// columns, tile_countが設定されていない場合は、画像の大きさから求める
+    ///
+    pub fn set_image_size(&mut self, width: u32, height: u32) {
+        self.image_width = width;
+        self.image_height = height;
+
+        let fit =
+            |length: u32, margin: u32, tile: u32, spacing: u32| match tile.checked_add(spacing) {
+                Some(0) | None => 0,
+                Some(step) => length.saturating_sub(margin).saturating_add(spacing) / step,
+            };
+
+        if self.columns == 0 {
+            self.columns = fit(width, self.margin, self.tile_width, self.spacing);
+        }
+        if self.tile_count == 0 {
+            let rows = fit(height, self.margin, self.tile_height, self.spacing);
+            self.tile_count = self.columns.saturating_mul(rows);
+        }
+    }
+}
+
+///
+/// # タイルレイヤー
+///
+/// ## フィールド
+/// ### tiles
+/// 左上から行ごとに並んだGID。0は空のタイルを表す
+///
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<u32>,
+    pub visible: bool,
+    pub opacity: f32,
+    pub properties: TileProperties,
+}
+
+impl TileLayer {
+    ///
+    /// タイル座標のGIDを返すメソッド。範囲外, または空のタイルの場合はNoneを返す
+    ///
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        match self
+            .tiles
+            .get((y as usize * self.width as usize) + x as usize)
+        {
+            Some(0) | None => None,
+            Some(gid) => Some(*gid),
+        }
+    }
+}
+
+///
+/// # オブジェクトレイヤー上のオブジェクト
+///
+/// ## フィールド
+/// ### kind
+/// Tiled上のtype(またはclass)
+///
+/// ### position, size
+/// マップの左上を原点とした位置と大きさ。単位はピクセル
+///
+/// ### gid
+/// タイルオブジェクトの場合、そのGID
+///
+#[derive(Debug, Clone)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub kind: String,
+    pub position: numeric::Point2f,
+    pub size: numeric::Vector2f,
+    pub gid: Option<u32>,
+    pub properties: TileProperties,
+}
+
+impl MapObject {
+    pub fn get_area(&self) -> numeric::Rect {
+        numeric::Rect::new(self.position.x, self.position.y, self.size.x, self.size.y)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub visible: bool,
+    pub properties: TileProperties,
+}
+
+impl ObjectLayer {
+    pub fn find_object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+}
+
+///
+/// # Tiledのマップから読み込んだデータ
+///
+/// 対応しているのは直交(orthogonal)マップのみで、タイルデータはCSV, または非圧縮のbase64に対応する
+///
+#[derive(Debug, Clone)]
+pub struct TileMapData {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub tile_layers: Vec<TileLayer>,
+    pub object_layers: Vec<ObjectLayer>,
+    pub properties: TileProperties,
+}
+
+impl TileMapData {
+    ///
+    /// マップ全体の大きさを返すメソッド。単位はピクセル
+    ///
+    pub fn get_pixel_size(&self) -> numeric::Vector2f {
+        numeric::Vector2f::new(
+            self.width as f32 * self.tile_width as f32,
+            self.height as f32 * self.tile_height as f32,
+        )
+    }
+
+    pub fn tileset_index_for_gid(&self, gid: u32) -> Option<usize> {
+        self.tilesets
+            .iter()
+            .position(|tileset| tileset.local_id(gid).is_some())
+    }
+
+    pub fn tileset_for_gid(&self, gid: u32) -> Option<&Tileset> {
+        self.tileset_index_for_gid(gid)
+            .map(|index| &self.tilesets[index])
+    }
+
+    pub fn layer_index(&self, name: &str) -> Option<usize> {
+        self.tile_layers.iter().position(|layer| layer.name == name)
+    }
+
+    pub fn get_object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers.iter().find(|layer| layer.name == name)
+    }
+
+    ///
+    /// マップの左上を原点とした座標から、タイル座標を求めるメソッド
+    /// マップの範囲外の場合はNoneを返す
+    ///
+    pub fn point_to_tile(&self, point: numeric::Point2f) -> Option<numeric::Vector2u> {
+        if point.x < 0.0 || point.y < 0.0 {
+            return None;
+        }
+
+        let x = (point.x / self.tile_width as f32) as u32;
+        let y = (point.y / self.tile_height as f32) as u32;
+        if x < self.width && y < self.height {
+            Some(numeric::Vector2u::new(x, y))
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// タイル座標から、そのタイルの左上の座標を求めるメソッド
+    ///
+    pub fn tile_to_point(&self, tile: numeric::Vector2u) -> numeric::Point2f {
+        numeric::Point2f::new(
+            tile.x as f32 * self.tile_width as f32,
+            tile.y as f32 * self.tile_height as f32,
+        )
+    }
+
+    pub fn get_tile_properties(&self, gid: u32) -> Option<&TileProperties> {
+        let tileset = self.tileset_for_gid(gid)?;
+        tileset.tile_properties.get(&tileset.local_id(gid)?)
+    }
+
+    pub fn is_collision_gid(&self, gid: u32) -> bool {
+        match self.tileset_for_gid(gid) {
+            Some(tileset) => tileset
+                .local_id(gid)
+                .map_or(false, |id| tileset.collision.contains(&id)),
+            None => false,
+        }
+    }
+
+    ///
+    /// いずれかのタイルレイヤーで、タイル座標に衝突判定を持つタイルがあるかを返すメソッド
+    ///
+    pub fn is_collision_tile(&self, tile: numeric::Vector2u) -> bool {
+        self.tile_layers.iter().any(|layer| {
+            layer
+                .get_tile(tile.x, tile.y)
+                .map_or(false, |gid| self.is_collision_gid(gid))
+        })
+    }
+
+    ///
+    /// 矩形(マップの左上を原点とした座標)と重なるタイルの範囲を返すメソッド
+    /// 返す値は(左上のタイル座標, 右下のタイル座標の次)。重ならない場合はNoneを返す
+    /// マップのタイルより大きいタイルは上と右にはみ出すため、その分だけ範囲を広げる
+    ///
+    pub fn visible_tile_range(
+        &self,
+        area: numeric::Rect,
+    ) -> Option<(numeric::Vector2u, numeric::Vector2u)> {
+        let (tile_w, tile_h) = (self.tile_width as f32, self.tile_height as f32);
+        let overflow_x = self
+            .tilesets
+            .iter()
+            .map(|tileset| tileset.tile_width.saturating_sub(self.tile_width))
+            .max()
+            .unwrap_or(0) as f32;
+        let overflow_y = self
+            .tilesets
+            .iter()
+            .map(|tileset| tileset.tile_height.saturating_sub(self.tile_height))
+            .max()
+            .unwrap_or(0) as f32;
+
+        let clamp = |v: f32, max: u32| v.max(0.0).min(max as f32) as u32;
+        let begin = numeric::Vector2u::new(
+            clamp(((area.x - overflow_x) / tile_w).floor(), self.width),
+            clamp((area.y / tile_h).floor(), self.height),
+        );
+        let end = numeric::Vector2u::new(
+            clamp(((area.x + area.w) / tile_w).ceil(), self.width),
+            clamp(
+                ((area.y + area.h + overflow_y) / tile_h).ceil(),
+                self.height,
+            ),
+        );
+
+        if begin.x < end.x && begin.y < end.y {
+            Some((begin, end))
+        } else {
+            None
+        }
+    }
+}
+
+///
+/// タイルデータを展開してGIDの列を返す関数
+/// encodingはNone(またはcsv), base64に対応する。圧縮されたデータには対応しない
+///
+/// ```
+/// use torifune::graphics::object::tile_map::decode_tile_data;
+///
+/// let tiles = decode_tile_data(Some("csv"), None, "1,2,\n0,3", 4).unwrap();
+/// assert_eq!(tiles, vec![1, 2, 0, 3]);
+///
+/// let tiles = decode_tile_data(Some("base64"), None, "AQAAAAIAAAA=", 2).unwrap();
+/// assert_eq!(tiles, vec![1, 2]);
+/// ```
+///
+pub fn decode_tile_data(
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    data: &str,
+    expected: usize,
+) -> error::Result<Vec<u32>> {
+    match compression {
+        None | Some("") => (),
+        Some(compression) => {
+            return Err(error::Error::InvalidTileMap(format!(
+                "unsupported compression: {}",
+                compression
+            )))
+        }
+    }
+
+    let tiles = match encoding {
+        None | Some("") | Some("csv") => data
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u32>()
+                    .map_err(|_| error::Error::InvalidTileMap(format!("invalid gid: {}", s)))
+            })
+            .collect::<error::Result<Vec<u32>>>()?,
+        Some("base64") => {
+            let compact: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+            let bytes = base64::decode(&compact)
+                .map_err(|e| error::Error::InvalidTileMap(e.to_string()))?;
+            if bytes.len() % 4 != 0 {
+                return Err(invalid("base64 tile data is not a sequence of u32"));
+            }
+            bytes
+                .chunks(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+        Some(encoding) => {
+            return Err(error::Error::InvalidTileMap(format!(
+                "unsupported encoding: {}",
+                encoding
+            )))
+        }
+    };
+
+    if tiles.len() != expected {
+        return Err(error::Error::InvalidTileMap(format!(
+            "expected {} tiles, found {}",
+            expected,
+            tiles.len()
+        )));
+    }
+
+    Ok(tiles.into_iter().map(|gid| gid & !GID_FLIP_FLAGS).collect())
+}
+
+fn check_orientation(orientation: Option<&str>, infinite: bool) -> error::Result<()> {
+    match orientation {
+        None | Some("orthogonal") => (),
+        Some(orientation) => {
+            return Err(error::Error::InvalidTileMap(format!(
+                "unsupported orientation: {}",
+                orientation
+            )))
+        }
+    }
+
+    if infinite {
+        return Err(invalid("infinite maps are not supported"));
+    }
+
+    Ok(())
+}
+
+fn xml_attr<T: FromStr>(node: roxmltree::Node, name: &str) -> Option<T> {
+    node.attribute(name).and_then(|value| value.parse().ok())
+}
+
+fn xml_required_attr<T: FromStr>(node: roxmltree::Node, name: &str) -> error::Result<T> {
+    xml_attr(node, name).ok_or_else(|| {
+        error::Error::InvalidTileMap(format!(
+            "<{}>: attribute {} is missing or invalid",
+            node.tag_name().name(),
+            name
+        ))
+    })
+}
+
+fn xml_child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn xml_properties(node: roxmltree::Node) -> TileProperties {
+    let mut properties = HashMap::new();
+
+    if let Some(list) = xml_child(node, "properties") {
+        for property in list.children().filter(|n| n.has_tag_name("property")) {
+            if let Some(name) = property.attribute("name") {
+                let value = property
+                    .attribute("value")
+                    .or_else(|| property.text())
+                    .unwrap_or("");
+                properties.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+
+    properties
+}
+
+fn xml_parse(text: &str) -> error::Result<roxmltree::Document<'_>> {
+    roxmltree::Document::parse(text).map_err(|e| error::Error::InvalidTileMap(e.to_string()))
+}
+
+fn xml_tileset(node: roxmltree::Node, first_gid: u32) -> error::Result<Tileset> {
+    let mut tileset = Tileset {
+        first_gid: first_gid,
+        name: node.attribute("name").unwrap_or("").to_string(),
+        tile_width: xml_required_attr(node, "tilewidth")?,
+        tile_height: xml_required_attr(node, "tileheight")?,
+        tile_count: xml_attr(node, "tilecount").unwrap_or(0),
+        columns: xml_attr(node, "columns").unwrap_or(0),
+        spacing: xml_attr(node, "spacing").unwrap_or(0),
+        margin: xml_attr(node, "margin").unwrap_or(0),
+        ..Default::default()
+    };
+
+    if let Some(image) = xml_child(node, "image") {
+        tileset.image = xml_required_attr(image, "source")?;
+        tileset.image_width = xml_attr(image, "width").unwrap_or(0);
+        tileset.image_height = xml_attr(image, "height").unwrap_or(0);
+    }
+
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let id: u32 = xml_required_attr(tile, "id")?;
+        let properties = xml_properties(tile);
+
+        if properties.get("collision").map_or(false, |v| v == "true")
+            || xml_child(tile, "objectgroup").is_some()
+        {
+            tileset.collision.insert(id);
+        }
+        if !properties.is_empty() {
+            tileset.tile_properties.insert(id, properties);
+        }
+    }
+
+    Ok(tileset)
+}
+
+///
+/// 外部タイルセット(.tsx)を読み込む関数
+///
+pub fn parse_tsx(text: &str, first_gid: u32) -> error::Result<Tileset> {
+    let document = xml_parse(text)?;
+    let root = document.root_element();
+    if !root.has_tag_name("tileset") {
+        return Err(invalid("root element is not <tileset>"));
+    }
+
+    xml_tileset(root, first_gid)
+}
+
+fn xml_object(node: roxmltree::Node) -> error::Result<MapObject> {
+    Ok(MapObject {
+        id: xml_attr(node, "id").unwrap_or(0),
+        name: node.attribute("name").unwrap_or("").to_string(),
+        kind: node
+            .attribute("type")
+            .or_else(|| node.attribute("class"))
+            .unwrap_or("")
+            .to_string(),
+        position: numeric::Point2f::new(
+            xml_required_attr(node, "x")?,
+            xml_required_attr(node, "y")?,
+        ),
+        size: numeric::Vector2f::new(
+            xml_attr(node, "width").unwrap_or(0.0),
+            xml_attr(node, "height").unwrap_or(0.0),
+        ),
+        gid: xml_attr::<u32>(node, "gid").map(|gid| gid & !GID_FLIP_FLAGS),
+        properties: xml_properties(node),
+    })
+}
+
+///
+/// TMX形式のマップを読み込む関数
+/// 外部タイルセットはsourceのみが設定された状態で返される
+///
+pub fn parse_tmx(text: &str) -> error::Result<TileMapData> {
+    let document = xml_parse(text)?;
+    let root = document.root_element();
+    if !root.has_tag_name("map") {
+        return Err(invalid("root element is not <map>"));
+    }
+    check_orientation(
+        root.attribute("orientation"),
+        root.attribute("infinite") == Some("1"),
+    )?;
+
+    let mut map = TileMapData {
+        width: xml_required_attr(root, "width")?,
+        height: xml_required_attr(root, "height")?,
+        tile_width: xml_required_attr(root, "tilewidth")?,
+        tile_height: xml_required_attr(root, "tileheight")?,
+        tilesets: Vec::new(),
+        tile_layers: Vec::new(),
+        object_layers: Vec::new(),
+        properties: xml_properties(root),
+    };
+
+    for node in root.children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "tileset" => {
+                let first_gid = xml_required_attr(node, "firstgid")?;
+                let tileset = match node.attribute("source") {
+                    Some(source) => Tileset {
+                        first_gid: first_gid,
+                        source: Some(source.to_string()),
+                        ..Default::default()
+                    },
+                    None => xml_tileset(node, first_gid)?,
+                };
+                map.tilesets.push(tileset);
+            }
+            "layer" => {
+                let width: u32 = xml_attr(node, "width").unwrap_or(map.width);
+                let height: u32 = xml_attr(node, "height").unwrap_or(map.height);
+                let data = xml_child(node, "data").ok_or_else(|| invalid("<layer> has no data"))?;
+                let name = node.attribute("name").unwrap_or("");
+                let expected = checked_area(name, width, height)? as usize;
+
+                let tiles = if data.attribute("encoding").is_none()
+                    && data.children().any(|n| n.has_tag_name("tile"))
+                {
+                    let tiles: Vec<u32> = data
+                        .children()
+                        .filter(|n| n.has_tag_name("tile"))
+                        .map(|n| xml_attr::<u32>(n, "gid").unwrap_or(0) & !GID_FLIP_FLAGS)
+                        .collect();
+                    if tiles.len() != expected {
+                        return Err(error::Error::InvalidTileMap(format!(
+                            "{}: expected {} tiles, found {}",
+                            name,
+                            expected,
+                            tiles.len()
+                        )));
+                    }
+                    tiles
+                } else {
+                    if xml_child(data, "chunk").is_some() {
+                        return Err(invalid("infinite maps are not supported"));
+                    }
+                    decode_tile_data(
+                        data.attribute("encoding"),
+                        data.attribute("compression"),
+                        data.text().unwrap_or(""),
+                        expected,
+                    )?
+                };
+
+                map.tile_layers.push(TileLayer {
+                    name: name.to_string(),
+                    width: width,
+                    height: height,
+                    tiles: tiles,
+                    visible: node.attribute("visible") != Some("0"),
+                    opacity: xml_attr(node, "opacity").unwrap_or(1.0),
+                    properties: xml_properties(node),
+                });
+            }
+            "objectgroup" => {
+                let objects = node
+                    .children()
+                    .filter(|n| n.has_tag_name("object"))
+                    .map(xml_object)
+                    .collect::<error::Result<Vec<MapObject>>>()?;
+
+                map.object_layers.push(ObjectLayer {
+                    name: node.attribute("name").unwrap_or("").to_string(),
+                    objects: objects,
+                    visible: node.attribute("visible") != Some("0"),
+                    properties: xml_properties(node),
+                });
+            }
+            _ => (),
+        }
+    }
+
+    Ok(map)
+}
+
+fn json_u32(value: &serde_json::Value, key: &str) -> Option<u32> {
+    value.get(key).and_then(|v| v.as_u64()).map(|v| v as u32)
+}
+
+fn json_f32(value: &serde_json::Value, key: &str) -> Option<f32> {
+    value.get(key).and_then(|v| v.as_f64()).map(|v| v as f32)
+}
+
+fn json_str<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|v| v.as_str())
+}
+
+fn json_required_u32(value: &serde_json::Value, key: &str) -> error::Result<u32> {
+    json_u32(value, key)
+        .ok_or_else(|| error::Error::InvalidTileMap(format!("{} is missing or invalid", key)))
+}
+
+fn json_properties(value: &serde_json::Value) -> TileProperties {
+    let mut properties = HashMap::new();
+
+    if let Some(list) = value.get("properties").and_then(|v| v.as_array()) {
+        for property in list {
+            if let (Some(name), Some(value)) = (json_str(property, "name"), property.get("value")) {
+                let value = match value.as_str() {
+                    Some(s) => s.to_string(),
+                    None => value.to_string(),
+                };
+                properties.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    properties
+}
+
+fn json_parse(text: &str) -> error::Result<serde_json::Value> {
+    serde_json::from_str(text).map_err(|e| error::Error::InvalidTileMap(e.to_string()))
+}
+
+fn json_tileset_body(value: &serde_json::Value, first_gid: u32) -> error::Result<Tileset> {
+    let mut tileset = Tileset {
+        first_gid: first_gid,
+        name: json_str(value, "name").unwrap_or("").to_string(),
+        image: json_str(value, "image").unwrap_or("").to_string(),
+        image_width: json_u32(value, "imagewidth").unwrap_or(0),
+        image_height: json_u32(value, "imageheight").unwrap_or(0),
+        tile_width: json_required_u32(value, "tilewidth")?,
+        tile_height: json_required_u32(value, "tileheight")?,
+        tile_count: json_u32(value, "tilecount").unwrap_or(0),
+        columns: json_u32(value, "columns").unwrap_or(0),
+        spacing: json_u32(value, "spacing").unwrap_or(0),
+        margin: json_u32(value, "margin").unwrap_or(0),
+        ..Default::default()
+    };
+
+    if let Some(tiles) = value.get("tiles").and_then(|v| v.as_array()) {
+        for tile in tiles {
+            let id = json_required_u32(tile, "id")?;
+            let properties = json_properties(tile);
+
+            if properties.get("collision").map_or(false, |v| v == "true")
+                || tile.get("objectgroup").is_some()
+            {
+                tileset.collision.insert(id);
+            }
+            if !properties.is_empty() {
+                tileset.tile_properties.insert(id, properties);
+            }
+        }
+    }
+
+    Ok(tileset)
+}
+
+///
+/// JSON形式の外部タイルセットを読み込む関数
+///
+pub fn parse_tiled_json_tileset(text: &str, first_gid: u32) -> error::Result<Tileset> {
+    json_tileset_body(&json_parse(text)?, first_gid)
+}
+
+fn json_object(value: &serde_json::Value) -> MapObject {
+    MapObject {
+        id: json_u32(value, "id").unwrap_or(0),
+        name: json_str(value, "name").unwrap_or("").to_string(),
+        kind: json_str(value, "type")
+            .or_else(|| json_str(value, "class"))
+            .unwrap_or("")
+            .to_string(),
+        position: numeric::Point2f::new(
+            json_f32(value, "x").unwrap_or(0.0),
+            json_f32(value, "y").unwrap_or(0.0),
+        ),
+        size: numeric::Vector2f::new(
+            json_f32(value, "width").unwrap_or(0.0),
+            json_f32(value, "height").unwrap_or(0.0),
+        ),
+        gid: json_u32(value, "gid").map(|gid| gid & !GID_FLIP_FLAGS),
+        properties: json_properties(value),
+    }
+}
+
+///
+/// Tiledが出力するJSON形式のマップを読み込む関数
+/// 外部タイルセットはsourceのみが設定された状態で返される
+///
+pub fn parse_tiled_json(text: &str) -> error::Result<TileMapData> {
+    let root = json_parse(text)?;
+    check_orientation(
+        json_str(&root, "orientation"),
+        root.get("infinite")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    )?;
+
+    let mut map = TileMapData {
+        width: json_required_u32(&root, "width")?,
+        height: json_required_u32(&root, "height")?,
+        tile_width: json_required_u32(&root, "tilewidth")?,
+        tile_height: json_required_u32(&root, "tileheight")?,
+        tilesets: Vec::new(),
+        tile_layers: Vec::new(),
+        object_layers: Vec::new(),
+        properties: json_properties(&root),
+    };
+
+    if let Some(tilesets) = root.get("tilesets").and_then(|v| v.as_array()) {
+        for value in tilesets {
+            let first_gid = json_required_u32(value, "firstgid")?;
+            let tileset = match json_str(value, "source") {
+                Some(source) => Tileset {
+                    first_gid: first_gid,
+                    source: Some(source.to_string()),
+                    ..Default::default()
+                },
+                None => json_tileset_body(value, first_gid)?,
+            };
+            map.tilesets.push(tileset);
+        }
+    }
+
+    let layers = root
+        .get("layers")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| invalid("layers is missing"))?;
+
+    for layer in layers {
+        let name = json_str(layer, "name").unwrap_or("").to_string();
+        let visible = layer
+            .get("visible")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        match json_str(layer, "type") {
+            Some("tilelayer") => {
+                let width = json_u32(layer, "width").unwrap_or(map.width);
+                let height = json_u32(layer, "height").unwrap_or(map.height);
+                let expected = checked_area(&name, width, height)? as usize;
+
+                let tiles = match layer.get("data") {
+                    Some(serde_json::Value::Array(data)) => {
+                        if data.len() != expected {
+                            return Err(error::Error::InvalidTileMap(format!(
+                                "{}: expected {} tiles, found {}",
+                                name,
+                                expected,
+                                data.len()
+                            )));
+                        }
+                        data.iter()
+                            .map(|v| {
+                                v.as_u64()
+                                    .filter(|gid| *gid <= u64::from(u32::max_value()))
+                                    .map(|gid| (gid as u32) & !GID_FLIP_FLAGS)
+                                    .ok_or_else(|| {
+                                        error::Error::InvalidTileMap(format!("invalid gid: {}", v))
+                                    })
+                            })
+                            .collect::<error::Result<Vec<u32>>>()?
+                    }
+                    Some(serde_json::Value::String(data)) => decode_tile_data(
+                        json_str(layer, "encoding"),
+                        json_str(layer, "compression"),
+                        data,
+                        expected,
+                    )?,
+                    _ => {
+                        return Err(error::Error::InvalidTileMap(format!(
+                            "{}: data is missing",
+                            name
+                        )))
+                    }
+                };
+
+                map.tile_layers.push(TileLayer {
+                    name: name,
+                    width: width,
+                    height: height,
+                    tiles: tiles,
+                    visible: visible,
+                    opacity: json_f32(layer, "opacity").unwrap_or(1.0),
+                    properties: json_properties(layer),
+                });
+            }
+            Some("objectgroup") => {
+                let objects = layer
+                    .get("objects")
+                    .and_then(|v| v.as_array())
+                    .map(|objects| objects.iter().map(json_object).collect())
+                    .unwrap_or_else(Vec::new);
+
+                map.object_layers.push(ObjectLayer {
+                    name: name,
+                    objects: objects,
+                    visible: visible,
+                    properties: json_properties(layer),
+                });
+            }
+            _ => (),
+        }
+    }
+
+    Ok(map)
+}
+
+///
+/// baseのファイルからの相対パスrelativeを、ggezのファイルシステム上のパスに変換する関数
+///
+fn resolve_path(base: &str, relative: &str) -> String {
+    if relative.starts_with('/') {
+        return relative.to_string();
+    }
+
+    let mut segments: Vec<&str> = base.split('/').collect();
+    segments.pop();
+    for segment in relative.split('/') {
+        match segment {
+            "." | "" => (),
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+fn read_text(ctx: &mut ggez::Context, path: &str) -> error::Result<String> {
+    if !ggez::filesystem::exists(ctx, path) {
+        return Err(error::Error::ResourceNotFound(path.to_string()));
+    }
+
+    let mut text = String::new();
+    ggez::filesystem::open(ctx, path)?
+        .read_to_string(&mut text)
+        .map_err(|e| error::Error::InvalidTileMap(format!("{}: {}", path, e)))?;
+    Ok(text)
+}
+
+///
+/// 一つのタイルレイヤーを、タイルセットごとのTileBatchで描画する
+///
+struct TileMapLayer {
+    batches: Vec<TileBatch>,
+    visible: bool,
+}
+
+///
+/// # Tiledのマップを描画するオブジェクト
+///
+/// タイルレイヤーごとにTileBatchを持ち、viewportと重なるタイルのみをバッチに積む
+///
+/// ## フィールド
+/// ### data
+/// 読み込んだマップのデータ
+///
+/// ### viewport
+/// 描画する範囲。ワールド座標で指定する。Noneの場合はマップ全体を描画する
+///
+/// ### built_range
+/// 現在バッチに積まれているタイルの範囲。範囲が変わった時のみバッチを積み直す
+///
+pub struct TileMap {
+    data: TileMapData,
+    layers: Vec<TileMapLayer>,
+    position: numeric::Point2f,
+    viewport: Option<numeric::Rect>,
+    built_range: Option<(numeric::Vector2u, numeric::Vector2u)>,
+    dirty: bool,
+    drwob_essential: DrawableObjectEssential,
+}
+
+impl TileMap {
+    ///
+    /// TMX, またはJSON形式のマップを読み込むメソッド
+    /// 形式は拡張子(.tmx, .json)で判断し、外部タイルセットとタイルセットの画像も読み込む
+    ///
+    pub fn load(
+        ctx: &mut ggez::Context,
+        path: &str,
+        pos: numeric::Point2f,
+        draw_depth: i8,
+    ) -> error::Result<TileMap> {
+        let text = read_text(ctx, path)?;
+        let mut data = if path.ends_with(".json") {
+            parse_tiled_json(&text)?
+        } else {
+            parse_tmx(&text)?
+        };
+
+        let mut image_paths = Vec::new();
+        for tileset in data.tilesets.iter_mut() {
+            let image_base = match tileset.source.clone() {
+                Some(source) => {
+                    let source_path = resolve_path(path, &source);
+                    let text = read_text(ctx, &source_path)?;
+                    let external = if source_path.ends_with(".json") {
+                        parse_tiled_json_tileset(&text, tileset.first_gid)?
+                    } else {
+                        parse_tsx(&text, tileset.first_gid)?
+                    };
+                    tileset.merge_external(external);
+                    source_path
+                }
+                None => path.to_string(),
+            };
+
+            if tileset.image.is_empty() {
+                return Err(error::Error::InvalidTileMap(format!(
+                    "tileset {} has no image",
+                    tileset.name
+                )));
+            }
+            image_paths.push(resolve_path(&image_base, &tileset.image));
+        }
+
+        let mut images = Vec::new();
+        for image_path in image_paths {
+            if !ggez::filesystem::exists(ctx, &image_path) {
+                return Err(error::Error::ResourceNotFound(image_path));
+            }
+            images.push(ggraphics::Image::new(ctx, &image_path)?);
+        }
+
+        TileMap::from_data(data, images, pos, draw_depth)
+    }
+
+    ///
+    /// 読み込み済みのデータとタイルセットの画像からTileMapを生成するメソッド
+    /// imagesはdata.tilesetsと同じ順に並んでいなければならず、数が異なる場合はErrorを返す
+    ///
+    pub fn from_data(
+        mut data: TileMapData,
+        images: Vec<ggraphics::Image>,
+        pos: numeric::Point2f,
+        draw_depth: i8,
+    ) -> error::Result<TileMap> {
+        if images.len() != data.tilesets.len() {
+            return Err(error::Error::InvalidTileMap(format!(
+                "expected {} tileset images, found {}",
+                data.tilesets.len(),
+                images.len()
+            )));
+        }
+
+        for (tileset, image) in data.tilesets.iter_mut().zip(images.iter()) {
+            tileset.set_image_size(image.width() as u32, image.height() as u32);
+        }
+
+        let layers = data
+            .tile_layers
+            .iter()
+            .map(|layer| TileMapLayer {
+                batches: data
+                    .tilesets
+                    .iter()
+                    .zip(images.iter())
+                    .map(|(tileset, image)| {
+                        TileBatch::new(
+                            image.clone(),
+                            numeric::Vector2u::new(tileset.tile_width, tileset.tile_height),
+                            pos,
+                            draw_depth,
+                        )
+                    })
+                    .collect(),
+                visible: layer.visible,
+            })
+            .collect();
+
+        Ok(TileMap {
+            data: data,
+            layers: layers,
+            position: pos,
+            viewport: None,
+            built_range: None,
+            dirty: true,
+            drwob_essential: DrawableObjectEssential::new(true, draw_depth),
+        })
+    }
+
+    pub fn get_map_data(&self) -> &TileMapData {
+        &self.data
+    }
+
+    ///
+    /// 描画する範囲を設定するメソッド。ワールド座標で指定する
+    ///
+    pub fn set_viewport(&mut self, viewport: Option<numeric::Rect>) {
+        self.viewport = viewport;
+    }
+
+    pub fn get_viewport(&self) -> Option<numeric::Rect> {
+        self.viewport
+    }
+
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.visible = visible;
+        }
+    }
+
+    pub fn is_layer_visible(&self, index: usize) -> bool {
+        self.layers.get(index).map_or(false, |layer| layer.visible)
+    }
+
+    ///
+    /// タイルの内容を書き換えるメソッド。次の描画でバッチが積み直される
+    ///
+    pub fn set_tile(&mut self, layer: usize, tile: numeric::Vector2u, gid: u32) {
+        if let Some(layer) = self.data.tile_layers.get_mut(layer) {
+            if tile.x < layer.width && tile.y < layer.height {
+                if let Some(cell) = layer
+                    .tiles
+                    .get_mut((tile.y as usize * layer.width as usize) + tile.x as usize)
+                {
+                    *cell = gid;
+                    self.dirty = true;
+                }
+            }
+        }
+    }
+
+    fn world_to_map(&self, point: numeric::Point2f) -> numeric::Point2f {
+        numeric::Point2f::new(point.x - self.position.x, point.y - self.position.y)
+    }
+
+    ///
+    /// ワールド座標から、タイル座標を求めるメソッド
+    ///
+    pub fn world_to_tile(&self, point: numeric::Point2f) -> Option<numeric::Vector2u> {
+        self.data.point_to_tile(self.world_to_map(point))
+    }
+
+    ///
+    /// タイル座標から、そのタイルの左上のワールド座標を求めるメソッド
+    ///
+    pub fn tile_to_world(&self, tile: numeric::Vector2u) -> numeric::Point2f {
+        let point = self.data.tile_to_point(tile);
+        numeric::Point2f::new(point.x + self.position.x, point.y + self.position.y)
+    }
+
+    ///
+    /// ワールド座標にあるタイルのGIDを返すメソッド
+    ///
+    pub fn tile_at(&self, layer: usize, point: numeric::Point2f) -> Option<u32> {
+        let tile = self.world_to_tile(point)?;
+        self.data.tile_layers.get(layer)?.get_tile(tile.x, tile.y)
+    }
+
+    pub fn tile_properties_at(
+        &self,
+        layer: usize,
+        point: numeric::Point2f,
+    ) -> Option<&TileProperties> {
+        self.data.get_tile_properties(self.tile_at(layer, point)?)
+    }
+
+    ///
+    /// ワールド座標に衝突判定を持つタイルがあるかを返すメソッド
+    ///
+    pub fn is_collision_at(&self, point: numeric::Point2f) -> bool {
+        self.world_to_tile(point)
+            .map_or(false, |tile| self.data.is_collision_tile(tile))
+    }
+
+    pub fn get_object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.data.get_object_layer(name)
+    }
+
+    ///
+    /// 描画範囲と重なるタイルのみをバッチに積み直すメソッド
+    /// 範囲とタイルに変化がない場合は何もしない
+    ///
+    pub fn update_visible_tiles(&mut self) {
+        let range = match self.viewport {
+            Some(viewport) => self.data.visible_tile_range(numeric::Rect::new(
+                viewport.x - self.position.x,
+                viewport.y - self.position.y,
+                viewport.w,
+                viewport.h,
+            )),
+            None => Some((
+                numeric::Vector2u::new(0, 0),
+                numeric::Vector2u::new(self.data.width, self.data.height),
+            )),
+        };
+
+        if !self.dirty && range == self.built_range {
+            return;
+        }
+        self.built_range = range;
+        self.dirty = false;
+
+        for (layer, batches) in self.data.tile_layers.iter().zip(self.layers.iter_mut()) {
+            for batch in batches.batches.iter_mut() {
+                batch.clear_batch();
+            }
+
+            let (begin, end) = match range {
+                Some(range) => range,
+                None => continue,
+            };
+            let color = ggraphics::Color::new(1.0, 1.0, 1.0, layer.opacity);
+
+            for y in begin.y..end.y.min(layer.height) {
+                for x in begin.x..end.x.min(layer.width) {
+                    let gid = match layer.get_tile(x, y) {
+                        Some(gid) => gid,
+                        None => continue,
+                    };
+                    let index = match self.data.tileset_index_for_gid(gid) {
+                        Some(index) => index,
+                        None => continue,
+                    };
+                    let tileset = &self.data.tilesets[index];
+                    let local_id = gid - tileset.first_gid;
+
+                    let src = tileset.tile_image_position(local_id);
+                    let ratio = numeric::Vector2f::new(
+                        src.x as f32 / tileset.image_width as f32,
+                        src.y as f32 / tileset.image_height as f32,
+                    );
+                    // Tiledでは大きさの異なるタイルは左下を揃えて配置される
+                    let dest = numeric::Point2f::new(
+                        x as f32 * self.data.tile_width as f32,
+                        (y + 1) as f32 * self.data.tile_height as f32 - tileset.tile_height as f32,
+                    );
+
+                    batches.batches[index].add_batch_ratio_float(
+                        ratio,
+                        dest,
+                        numeric::Vector2f::new(1.0, 1.0),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl DrawableComponent for TileMap {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if self.is_visible() {
+            self.update_visible_tiles();
+
+            for layer in self.layers.iter_mut() {
+                if !layer.visible {
+                    continue;
+                }
+                for batch in layer.batches.iter_mut() {
+                    batch.draw(ctx)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
+
+impl DrawableObject for TileMap {
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.position = pos;
+        for layer in self.layers.iter_mut() {
+            for batch in layer.batches.iter_mut() {
+                batch.set_position(pos);
+            }
+        }
+    }
+
+    fn get_position(&self) -> numeric::Point2f {
+        self.position
+    }
+
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        let pos = self.position + offset;
+        self.set_position(pos);
+    }
+}
@@ -0,0 +1,739 @@
+use ggez::graphics as ggraphics;
+
+use crate::core::{Clock, Updatable};
+use crate::graphics::drawable::*;
+use crate::graphics::object::EffectFnStatus;
+use crate::numeric;
+
+///
+/// # 再現性のある乱数生成器
+///
+/// 同じシードからは常に同じ列を返すため、パーティクルの動きをテストで確認できる
+///
+#[derive(Debug, Clone)]
+pub struct ParticleRng {
+    state: u64,
+}
+
+impl ParticleRng {
+    pub fn new(seed: u64) -> Self {
+        // 0は固定点となるため避ける
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+        ParticleRng {
+            state: if state == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                state
+            },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as u32
+    }
+
+    ///
+    /// [0, 1)の値を返すメソッド
+    ///
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    ///
+    /// [min, max)の値を返すメソッド。min == maxの場合はminを返す
+    ///
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + ((max - min) * self.next_f32())
+    }
+}
+
+///
+/// # 変化の仕方を表すイージング関数
+///
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    EaseInQuad,
+    EaseOutQuad,
+    EaseInOutQuad,
+    EaseInCubic,
+    EaseOutCubic,
+    Custom(fn(f32) -> f32),
+}
+
+impl Easing {
+    ///
+    /// 0.0から1.0の進行度を、イージングを適用した値に変換するメソッド
+    ///
+    /// ```
+    /// use torifune::graphics::particle::Easing;
+    ///
+    /// assert_eq!(Easing::EaseInQuad.apply(0.5), 0.25);
+    /// assert_eq!(Easing::EaseOutQuad.apply(1.0), 1.0);
+    /// ```
+    ///
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t,
+            Easing::EaseOutQuad => t * (2.0 - t),
+            Easing::EaseInOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + ((4.0 - (2.0 * t)) * t)
+                }
+            }
+            Easing::EaseInCubic => t * t * t,
+            Easing::EaseOutCubic => {
+                let u = t - 1.0;
+                (u * u * u) + 1.0
+            }
+            Easing::Custom(f) => f(t),
+        }
+    }
+}
+
+///
+/// # 寿命に応じて値を変化させる設定
+///
+#[derive(Clone, Copy)]
+pub struct Tween<T: Copy> {
+    pub start: T,
+    pub end: T,
+    pub easing: Easing,
+}
+
+impl Tween<f32> {
+    pub fn value_at(&self, ratio: f32) -> f32 {
+        let e = self.easing.apply(ratio);
+        self.start + ((self.end - self.start) * e)
+    }
+}
+
+impl Tween<ggraphics::Color> {
+    pub fn value_at(&self, ratio: f32) -> ggraphics::Color {
+        let e = self.easing.apply(ratio);
+        let lerp = |a: f32, b: f32| a + ((b - a) * e);
+        ggraphics::Color::new(
+            lerp(self.start.r, self.end.r),
+            lerp(self.start.g, self.end.g),
+            lerp(self.start.b, self.end.b),
+            lerp(self.start.a, self.end.a),
+        )
+    }
+}
+
+///
+/// # パーティクルを発生させる範囲
+///
+/// Point: 発生源の一点
+/// Line: 発生源から、指定した差分の位置までの線分上
+/// Rect: 発生源を中心とした、指定した大きさの矩形内
+/// Circle: 発生源を中心とした、指定した半径の円内
+///
+#[derive(Debug, Clone, Copy)]
+pub enum EmitterShape {
+    Point,
+    Line(numeric::Vector2f),
+    Rect(numeric::Vector2f),
+    Circle(f32),
+}
+
+impl EmitterShape {
+    ///
+    /// 発生源からの差分を一つ選ぶメソッド
+    ///
+    pub fn sample(&self, rng: &mut ParticleRng) -> numeric::Vector2f {
+        match self {
+            EmitterShape::Point => numeric::Vector2f::new(0.0, 0.0),
+            EmitterShape::Line(end) => *end * rng.next_f32(),
+            EmitterShape::Rect(size) => numeric::Vector2f::new(
+                rng.range(-size.x / 2.0, size.x / 2.0),
+                rng.range(-size.y / 2.0, size.y / 2.0),
+            ),
+            EmitterShape::Circle(radius) => {
+                // 面積あたりの密度を一様にするため、半径は平方根を取る
+                let r = radius * rng.next_f32().sqrt();
+                let angle = rng.range(0.0, std::f32::consts::PI * 2.0);
+                numeric::Vector2f::new(r * angle.cos(), r * angle.sin())
+            }
+        }
+    }
+}
+
+///
+/// # パーティクルの発生の仕方
+///
+/// Rate: 時間1あたりに発生させる数
+/// Burst: intervalごとにcount個をまとめて発生させる。repeatがSomeの場合は、その回数で止まる
+///
+#[derive(Debug, Clone, Copy)]
+pub enum EmissionMode {
+    Rate(f32),
+    Burst {
+        count: usize,
+        interval: Clock,
+        repeat: Option<u32>,
+    },
+}
+
+///
+/// # 一つのパーティクル
+///
+/// モディファイアから書き換えられるように、フィールドは公開している
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position: numeric::Point2f,
+    pub velocity: numeric::Vector2f,
+    pub age: Clock,
+    pub lifetime: Clock,
+    pub color: ggraphics::Color,
+    pub scale: f32,
+    pub alive: bool,
+}
+
+impl Particle {
+    ///
+    /// 寿命に対する経過時間の割合を返すメソッド
+    ///
+    pub fn life_ratio(&self) -> f32 {
+        if self.lifetime == 0 {
+            1.0
+        } else {
+            self.age as f32 / self.lifetime as f32
+        }
+    }
+}
+
+///
+/// パーティクルに毎時刻適用されるクロージャ
+/// EffectFinishを返した場合、そのパーティクルは消える
+///
+pub type ParticleModifierFn = Box<dyn Fn(&mut Particle, Clock) -> EffectFnStatus>;
+
+///
+/// # パーティクルの設定
+///
+/// ## フィールド
+/// ### lifetime, speed, direction
+/// 発生時に範囲(min, max)から選ばれる寿命, 速さ, 向き(ラジアン)
+///
+/// ### acceleration, gravity
+/// 全てのパーティクルに毎時刻加わる加速度。gravityはy軸方向の加速度
+///
+/// ### color, alpha, scale
+/// 寿命に応じた色, 透明度, 拡大率の変化。alphaはcolorのアルファ値に掛けられる
+///
+/// ### max_particles
+/// 同時に存在できるパーティクルの数
+///
+#[derive(Clone, Copy)]
+pub struct ParticleConfig {
+    pub lifetime: (Clock, Clock),
+    pub speed: (f32, f32),
+    pub direction: (f32, f32),
+    pub acceleration: numeric::Vector2f,
+    pub gravity: f32,
+    pub color: Tween<ggraphics::Color>,
+    pub alpha: Tween<f32>,
+    pub scale: Tween<f32>,
+    pub max_particles: usize,
+}
+
+impl ParticleConfig {
+    pub fn new() -> Self {
+        ParticleConfig {
+            lifetime: (60, 60),
+            speed: (1.0, 1.0),
+            direction: (0.0, std::f32::consts::PI * 2.0),
+            acceleration: numeric::Vector2f::new(0.0, 0.0),
+            gravity: 0.0,
+            color: Tween {
+                start: ggraphics::WHITE,
+                end: ggraphics::WHITE,
+                easing: Easing::Linear,
+            },
+            alpha: Tween {
+                start: 1.0,
+                end: 1.0,
+                easing: Easing::Linear,
+            },
+            scale: Tween {
+                start: 1.0,
+                end: 1.0,
+                easing: Easing::Linear,
+            },
+            max_particles: 1024,
+        }
+    }
+
+    pub fn with_lifetime(mut self, min: Clock, max: Clock) -> Self {
+        self.lifetime = (min, max);
+        self
+    }
+
+    pub fn with_speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = (min, max);
+        self
+    }
+
+    pub fn with_direction(mut self, min: f32, max: f32) -> Self {
+        self.direction = (min, max);
+        self
+    }
+
+    pub fn with_acceleration(mut self, acceleration: numeric::Vector2f) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: f32) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_color(
+        mut self,
+        start: ggraphics::Color,
+        end: ggraphics::Color,
+        easing: Easing,
+    ) -> Self {
+        self.color = Tween {
+            start: start,
+            end: end,
+            easing: easing,
+        };
+        self
+    }
+
+    pub fn with_alpha(mut self, start: f32, end: f32, easing: Easing) -> Self {
+        self.alpha = Tween {
+            start: start,
+            end: end,
+            easing: easing,
+        };
+        self
+    }
+
+    pub fn with_scale(mut self, start: f32, end: f32, easing: Easing) -> Self {
+        self.scale = Tween {
+            start: start,
+            end: end,
+            easing: easing,
+        };
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+}
+
+///
+/// # パーティクルの発生と移動を計算する
+///
+/// 描画は行わないため、描画はParticleEmitterを用いる
+/// 時刻は1ずつ進めて計算するため、advanceを呼ぶ間隔に関わらず、同じシードからは同じ結果となる
+///
+/// ## フィールド
+/// ### origin
+/// 発生源の位置。発生済みのパーティクルは発生源が動いても追従しない
+///
+/// ### emit_carry
+/// Rateで発生させる場合の、端数の持ち越し
+///
+/// ### last
+/// 最後に計算した時刻。最初のadvanceの時刻から計算を始める
+///
+pub struct ParticleSimulator {
+    config: ParticleConfig,
+    shape: EmitterShape,
+    mode: EmissionMode,
+    rng: ParticleRng,
+    particles: Vec<Particle>,
+    modifiers: Vec<ParticleModifierFn>,
+    origin: numeric::Point2f,
+    emitting: bool,
+    emit_carry: f32,
+    burst_count: u32,
+    burst_elapsed: Clock,
+    last: Option<Clock>,
+}
+
+impl ParticleSimulator {
+    pub fn new(
+        config: ParticleConfig,
+        shape: EmitterShape,
+        mode: EmissionMode,
+        origin: numeric::Point2f,
+        seed: u64,
+    ) -> Self {
+        ParticleSimulator {
+            config: config,
+            shape: shape,
+            mode: mode,
+            rng: ParticleRng::new(seed),
+            particles: Vec::new(),
+            modifiers: Vec::new(),
+            origin: origin,
+            emitting: true,
+            emit_carry: 0.0,
+            burst_count: 0,
+            burst_elapsed: 0,
+            last: None,
+        }
+    }
+
+    pub fn get_config(&self) -> &ParticleConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ParticleConfig) {
+        self.config = config;
+    }
+
+    pub fn set_shape(&mut self, shape: EmitterShape) {
+        self.shape = shape;
+    }
+
+    pub fn set_emission_mode(&mut self, mode: EmissionMode) {
+        self.mode = mode;
+        self.emit_carry = 0.0;
+        self.burst_count = 0;
+        self.burst_elapsed = 0;
+    }
+
+    pub fn set_origin(&mut self, origin: numeric::Point2f) {
+        self.origin = origin;
+    }
+
+    pub fn get_origin(&self) -> numeric::Point2f {
+        self.origin
+    }
+
+    ///
+    /// パーティクルに毎時刻適用されるモディファイアを追加するメソッド
+    ///
+    pub fn add_modifier(&mut self, modifier: ParticleModifierFn) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn clear_modifiers(&mut self) {
+        self.modifiers.clear();
+    }
+
+    pub fn start(&mut self) {
+        self.emitting = true;
+    }
+
+    ///
+    /// 発生を止めるメソッド。発生済みのパーティクルは寿命まで残る
+    ///
+    pub fn stop(&mut self) {
+        self.emitting = false;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    ///
+    /// 発生を止めていて、全てのパーティクルが消えたかを返すメソッド
+    ///
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.particles.is_empty()
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    ///
+    /// 発生の仕方に関わらず、count個のパーティクルを即座に発生させるメソッド
+    ///
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+            let particle = self.spawn();
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn(&mut self) -> Particle {
+        let offset = self.shape.sample(&mut self.rng);
+        let speed = self.rng.range(self.config.speed.0, self.config.speed.1);
+        let angle = self
+            .rng
+            .range(self.config.direction.0, self.config.direction.1);
+        let (min, max) = self.config.lifetime;
+        let lifetime = if max > min {
+            min + (self.rng.next_u32() as Clock % (max - min + 1))
+        } else {
+            min
+        };
+
+        let mut particle = Particle {
+            position: self.origin + offset,
+            velocity: numeric::Vector2f::new(speed * angle.cos(), speed * angle.sin()),
+            age: 0,
+            lifetime: lifetime,
+            color: self.config.color.start,
+            scale: self.config.scale.start,
+            alive: true,
+        };
+        self.apply_tweens(&mut particle);
+        particle
+    }
+
+    fn apply_tweens(&self, particle: &mut Particle) {
+        let ratio = particle.life_ratio();
+        let mut color = self.config.color.value_at(ratio);
+        color.a *= self.config.alpha.value_at(ratio);
+        particle.color = color;
+        particle.scale = self.config.scale.value_at(ratio);
+    }
+
+    ///
+    /// 発生の仕方に従って、時刻1の間に発生させる数を返す
+    ///
+    fn emission_count(&mut self) -> usize {
+        if !self.emitting {
+            return 0;
+        }
+
+        match self.mode {
+            EmissionMode::Rate(rate) => {
+                self.emit_carry += rate.max(0.0);
+                let count = self.emit_carry.floor();
+                self.emit_carry -= count;
+                count as usize
+            }
+            EmissionMode::Burst {
+                count,
+                interval,
+                repeat,
+            } => {
+                if repeat.map_or(false, |repeat| self.burst_count >= repeat) {
+                    return 0;
+                }
+
+                let fire = self.burst_elapsed == 0;
+                self.burst_elapsed += 1;
+                if self.burst_elapsed >= interval.max(1) {
+                    self.burst_elapsed = 0;
+                }
+
+                if fire {
+                    self.burst_count += 1;
+                    count
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    ///
+    /// 時刻を1進めるメソッド
+    ///
+    fn step(&mut self, t: Clock) {
+        let acceleration = numeric::Vector2f::new(
+            self.config.acceleration.x,
+            self.config.acceleration.y + self.config.gravity,
+        );
+
+        for particle in self.particles.iter_mut() {
+            particle.age += 1;
+            if particle.age >= particle.lifetime {
+                particle.alive = false;
+                continue;
+            }
+
+            particle.velocity += acceleration;
+            particle.position += particle.velocity;
+        }
+
+        for index in 0..self.particles.len() {
+            if !self.particles[index].alive {
+                continue;
+            }
+            let mut particle = self.particles[index];
+            self.apply_tweens(&mut particle);
+
+            for modifier in self.modifiers.iter() {
+                if modifier(&mut particle, t) == EffectFnStatus::EffectFinish {
+                    particle.alive = false;
+                    break;
+                }
+            }
+            self.particles[index] = particle;
+        }
+        self.particles.retain(|particle| particle.alive);
+
+        let count = self.emission_count();
+        self.burst(count);
+    }
+
+    ///
+    /// 時刻tまで計算を進めるメソッド
+    ///
+    pub fn advance(&mut self, t: Clock) {
+        let last = match self.last {
+            Some(last) => last,
+            None => {
+                // 最初の呼び出しでは、その時刻の発生のみを行う
+                self.last = Some(t);
+                let count = self.emission_count();
+                self.burst(count);
+                return;
+            }
+        };
+
+        for now in (last + 1)..=t {
+            self.step(now);
+        }
+        self.last = Some(t.max(last));
+    }
+}
+
+///
+/// # パーティクルを描画するオブジェクト
+///
+/// 全てのパーティクルを一つのSpriteBatchで描画する。テクスチャはパーティクルの中心に描画される
+///
+pub struct ParticleEmitter {
+    simulator: ParticleSimulator,
+    sprite_batch: ggraphics::spritebatch::SpriteBatch,
+    texture_size: numeric::Vector2f,
+    drwob_essential: DrawableObjectEssential,
+}
+
+impl ParticleEmitter {
+    pub fn new(texture: ggraphics::Image, simulator: ParticleSimulator, drawing_depth: i8) -> Self {
+        ParticleEmitter {
+            texture_size: numeric::Vector2f::new(texture.width() as f32, texture.height() as f32),
+            sprite_batch: ggraphics::spritebatch::SpriteBatch::new(texture),
+            simulator: simulator,
+            drwob_essential: DrawableObjectEssential::new(true, drawing_depth),
+        }
+    }
+
+    pub fn get_simulator(&self) -> &ParticleSimulator {
+        &self.simulator
+    }
+
+    pub fn get_simulator_mut(&mut self) -> &mut ParticleSimulator {
+        &mut self.simulator
+    }
+
+    pub fn burst(&mut self, count: usize) {
+        self.simulator.burst(count);
+    }
+
+    pub fn start(&mut self) {
+        self.simulator.start();
+    }
+
+    pub fn stop(&mut self) {
+        self.simulator.stop();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.simulator.is_finished()
+    }
+}
+
+impl Updatable for ParticleEmitter {
+    fn update(&mut self, _ctx: &mut ggez::Context, t: Clock) {
+        self.simulator.advance(t);
+    }
+}
+
+impl DrawableComponent for ParticleEmitter {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if self.drwob_essential.visible {
+            self.sprite_batch.clear();
+            for particle in self.simulator.get_particles() {
+                let half = self.texture_size * (particle.scale / 2.0);
+                self.sprite_batch.add(ggraphics::DrawParam {
+                    dest: numeric::Point2f::new(
+                        particle.position.x - half.x,
+                        particle.position.y - half.y,
+                    )
+                    .into(),
+                    scale: numeric::Vector2f::new(particle.scale, particle.scale).into(),
+                    color: particle.color,
+                    ..Default::default()
+                });
+            }
+
+            ggraphics::draw(ctx, &self.sprite_batch, ggraphics::DrawParam::default())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    #[inline(always)]
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    #[inline(always)]
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    #[inline(always)]
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    #[inline(always)]
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
+
+impl DrawableObject for ParticleEmitter {
+    ///
+    /// 発生源の位置を変更する。発生済みのパーティクルは動かない
+    ///
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.simulator.set_origin(pos);
+    }
+
+    fn get_position(&self) -> numeric::Point2f {
+        self.simulator.get_origin()
+    }
+
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        let pos = self.simulator.get_origin() + offset;
+        self.simulator.set_origin(pos);
+    }
+}
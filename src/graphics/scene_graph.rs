@@ -0,0 +1,633 @@
+use crate::graphics::drawable::*;
+use crate::graphics::object::TextureObject;
+use crate::numeric;
+
+///
+/// # ノードの変換
+///
+/// 親ノードの変換と合成することで、ワールド座標系での変換となる
+///
+/// ## フィールド
+/// ### position
+/// 親ノードの座標系での位置
+///
+/// ### scale, rotation
+/// 親ノードに対する拡大率と回転角(ラジアン)。子ノードの位置にも作用する
+///
+/// ### alpha
+/// 親ノードのalpha値と乗算される
+///
+/// ### visible
+/// falseの場合、このノードと子孫のノードは描画されない
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: numeric::Point2f,
+    pub scale: numeric::Vector2f,
+    pub rotation: f32,
+    pub alpha: f32,
+    pub visible: bool,
+}
+
+impl Transform {
+    pub fn new(position: numeric::Point2f) -> Self {
+        Transform {
+            position: position,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn identity() -> Self {
+        Transform {
+            position: numeric::Point2f::new(0.0, 0.0),
+            scale: numeric::Vector2f::new(1.0, 1.0),
+            rotation: 0.0,
+            alpha: 1.0,
+            visible: true,
+        }
+    }
+
+    ///
+    /// このTransformの座標系での点を、親の座標系へ変換するメソッド
+    ///
+    pub fn apply_point(&self, point: numeric::Point2f) -> numeric::Point2f {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (point.x * self.scale.x, point.y * self.scale.y);
+        numeric::Point2f::new(
+            self.position.x + (x * cos) - (y * sin),
+            self.position.y + (x * sin) + (y * cos),
+        )
+    }
+
+    ///
+    /// 親の座標系での点を、このTransformの座標系へ変換するメソッド
+    /// 拡大率が0の軸は0として扱う
+    ///
+    pub fn inverse_point(&self, point: numeric::Point2f) -> numeric::Point2f {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (dx, dy) = (point.x - self.position.x, point.y - self.position.y);
+        let (x, y) = ((dx * cos) + (dy * sin), (dy * cos) - (dx * sin));
+        let div = |v: f32, s: f32| if s == 0.0 { 0.0 } else { v / s };
+        numeric::Point2f::new(div(x, self.scale.x), div(y, self.scale.y))
+    }
+
+    ///
+    /// 子のTransformと合成し、この座標系の親から見た子のTransformを返すメソッド
+    ///
+    /// ```
+    /// use torifune::graphics::scene_graph::Transform;
+    /// use torifune::numeric;
+    ///
+    /// let mut parent = Transform::new(numeric::Point2f::new(100.0, 50.0));
+    /// parent.scale = numeric::Vector2f::new(2.0, 2.0);
+    /// let child = Transform::new(numeric::Point2f::new(10.0, 5.0));
+    ///
+    /// let world = parent.compose(&child);
+    /// assert_eq!(world.position, numeric::Point2f::new(120.0, 60.0));
+    /// assert_eq!(world.scale, numeric::Vector2f::new(2.0, 2.0));
+    /// ```
+    ///
+    pub fn compose(&self, child: &Transform) -> Transform {
+        Transform {
+            position: self.apply_point(child.position),
+            scale: numeric::Vector2f::new(
+                self.scale.x * child.scale.x,
+                self.scale.y * child.scale.y,
+            ),
+            rotation: self.rotation + child.rotation,
+            alpha: self.alpha * child.alpha,
+            visible: self.visible && child.visible,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+///
+/// # ノードに取り付けるオブジェクト
+///
+/// Drawable: 位置と可視状態のみを継承する
+/// Texture: 位置, 拡大率, 回転, alpha値と可視状態を継承する
+///
+pub enum NodeObject {
+    Drawable(Box<dyn DrawableObject>),
+    Texture(Box<dyn TextureObject>),
+}
+
+impl NodeObject {
+    fn apply_transform(&mut self, world: &Transform) {
+        match self {
+            NodeObject::Drawable(object) => object.set_position(world.position),
+            NodeObject::Texture(object) => {
+                object.set_position(world.position);
+                object.set_scale(world.scale);
+                object.set_rotation(world.rotation);
+                object.set_alpha(world.alpha);
+            }
+        }
+    }
+
+    fn as_drawable_mut(&mut self) -> &mut dyn DrawableComponent {
+        match self {
+            NodeObject::Drawable(object) => object.as_mut(),
+            NodeObject::Texture(object) => object.as_mut(),
+        }
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        match self {
+            NodeObject::Drawable(object) => object.get_drawing_depth(),
+            NodeObject::Texture(object) => object.get_drawing_depth(),
+        }
+    }
+}
+
+///
+/// # ノードを指すID
+/// ノードが削除されると世代が進むため、削除されたノードのIDは再利用されたノードを指さない
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+struct SceneNode {
+    local: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    object: Option<NodeObject>,
+}
+
+struct NodeSlot {
+    generation: u32,
+    node: Option<SceneNode>,
+}
+
+///
+/// # 親子関係を持つノードの木
+///
+/// 子ノードは親ノードの位置, 拡大率, 回転, alpha値と可視状態を継承する。
+/// 描画時に各ノードのワールド座標系での変換を計算し、取り付けられたオブジェクトへ反映する
+///
+/// ## フィールド
+/// ### nodes
+/// ノードの実体。削除されたノードの枠は世代を進めて再利用される
+///
+/// ### roots
+/// 親を持たないノード
+///
+/// ### origin
+/// 全てのルートノードに適用される変換。set_positionで変更される
+///
+pub struct SceneGraph {
+    nodes: Vec<NodeSlot>,
+    free: Vec<usize>,
+    roots: Vec<NodeId>,
+    origin: Transform,
+    drwob_essential: DrawableObjectEssential,
+}
+
+impl SceneGraph {
+    pub fn new(pos: numeric::Point2f, drawing_depth: i8) -> Self {
+        SceneGraph {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+            origin: Transform::new(pos),
+            drwob_essential: DrawableObjectEssential::new(true, drawing_depth),
+        }
+    }
+
+    fn node(&self, id: NodeId) -> Option<&SceneNode> {
+        match self.nodes.get(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.node.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        match self.nodes.get_mut(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.node.as_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    ///
+    /// ノードを生成するメソッド
+    /// parentが存在しないノードの場合は、ルートノードとして生成する
+    ///
+    pub fn create_node(&mut self, parent: Option<NodeId>, local: Transform) -> NodeId {
+        let parent = parent.filter(|parent| self.contains(*parent));
+        let node = SceneNode {
+            local: local,
+            parent: parent,
+            children: Vec::new(),
+            object: None,
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.nodes[index];
+                slot.node = Some(node);
+                NodeId {
+                    index: index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.nodes.push(NodeSlot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.nodes.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        match parent {
+            Some(parent) => self.node_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+
+        id
+    }
+
+    ///
+    /// DrawableObjectを取り付けたノードを生成するメソッド
+    ///
+    pub fn add_drawable(
+        &mut self,
+        parent: Option<NodeId>,
+        local: Transform,
+        object: Box<dyn DrawableObject>,
+    ) -> NodeId {
+        let id = self.create_node(parent, local);
+        self.attach_object(id, NodeObject::Drawable(object));
+        id
+    }
+
+    ///
+    /// TextureObjectを取り付けたノードを生成するメソッド
+    ///
+    pub fn add_texture(
+        &mut self,
+        parent: Option<NodeId>,
+        local: Transform,
+        object: Box<dyn TextureObject>,
+    ) -> NodeId {
+        let id = self.create_node(parent, local);
+        self.attach_object(id, NodeObject::Texture(object));
+        id
+    }
+
+    ///
+    /// ノードにオブジェクトを取り付けるメソッド
+    /// 既に取り付けられていたオブジェクトを返す
+    ///
+    pub fn attach_object(&mut self, id: NodeId, object: NodeObject) -> Option<NodeObject> {
+        self.node_mut(id)
+            .and_then(|node| std::mem::replace(&mut node.object, Some(object)))
+    }
+
+    pub fn detach_object(&mut self, id: NodeId) -> Option<NodeObject> {
+        self.node_mut(id).and_then(|node| node.object.take())
+    }
+
+    pub fn get_object(&self, id: NodeId) -> Option<&NodeObject> {
+        self.node(id).and_then(|node| node.object.as_ref())
+    }
+
+    pub fn get_object_mut(&mut self, id: NodeId) -> Option<&mut NodeObject> {
+        self.node_mut(id).and_then(|node| node.object.as_mut())
+    }
+
+    ///
+    /// ノードと、その子孫のノードを全て削除するメソッド
+    ///
+    pub fn remove_node(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+
+        self.unlink(id);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if !self.contains(id) {
+                continue;
+            }
+
+            // 世代を進め、このIDを無効にしてから枠を再利用に回す
+            let slot = &mut self.nodes[id.index];
+            slot.generation = slot.generation.wrapping_add(1);
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                self.free.push(id.index);
+            }
+        }
+    }
+
+    ///
+    /// 親ノード, またはルートノードのリストからノードを外す
+    ///
+    fn unlink(&mut self, id: NodeId) {
+        match self.node(id).and_then(|node| node.parent) {
+            Some(parent) => {
+                let parent = self.node_mut(parent).unwrap();
+                parent.children.retain(|child| *child != id);
+            }
+            None => self.roots.retain(|root| *root != id),
+        }
+    }
+
+    ///
+    /// 親ノードを変更するメソッド。Noneを指定した場合はルートノードとなる
+    /// ワールド座標系での位置を保つ場合はkeep_worldをtrueにする
+    /// 自身の子孫を親に指定した場合は何もせずfalseを返す
+    ///
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>, keep_world: bool) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+
+        if let Some(parent) = parent {
+            if !self.contains(parent) || self.is_ancestor(id, parent) {
+                return false;
+            }
+        }
+
+        let world_position = self.local_to_world(id, numeric::Point2f::new(0.0, 0.0));
+
+        self.unlink(id);
+        self.node_mut(id).unwrap().parent = parent;
+        match parent {
+            Some(parent) => self.node_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+
+        if keep_world {
+            let local_position = match parent {
+                Some(parent) => self.world_to_local(parent, world_position),
+                None => self.origin.inverse_point(world_position),
+            };
+            self.node_mut(id).unwrap().local.position = local_position;
+        }
+
+        true
+    }
+
+    ///
+    /// ancestorがnodeの祖先, またはnode自身であればtrueを返す
+    ///
+    pub fn is_ancestor(&self, ancestor: NodeId, node: NodeId) -> bool {
+        let mut current = Some(node);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.node(id).and_then(|node| node.parent);
+        }
+
+        false
+    }
+
+    pub fn get_parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).and_then(|node| node.parent)
+    }
+
+    pub fn get_children(&self, id: NodeId) -> &[NodeId] {
+        match self.node(id) {
+            Some(node) => &node.children,
+            None => &[],
+        }
+    }
+
+    pub fn get_local(&self, id: NodeId) -> Option<&Transform> {
+        self.node(id).map(|node| &node.local)
+    }
+
+    pub fn get_local_mut(&mut self, id: NodeId) -> Option<&mut Transform> {
+        self.node_mut(id).map(|node| &mut node.local)
+    }
+
+    pub fn set_local_position(&mut self, id: NodeId, pos: numeric::Point2f) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.position = pos;
+        }
+    }
+
+    pub fn move_node_diff(&mut self, id: NodeId, offset: numeric::Vector2f) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.position += offset;
+        }
+    }
+
+    pub fn set_local_scale(&mut self, id: NodeId, scale: numeric::Vector2f) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.scale = scale;
+        }
+    }
+
+    pub fn set_local_rotation(&mut self, id: NodeId, rotation: f32) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.rotation = rotation;
+        }
+    }
+
+    pub fn set_local_alpha(&mut self, id: NodeId, alpha: f32) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.alpha = alpha;
+        }
+    }
+
+    pub fn set_node_visible(&mut self, id: NodeId, visible: bool) {
+        if let Some(local) = self.get_local_mut(id) {
+            local.visible = visible;
+        }
+    }
+
+    ///
+    /// ノードのワールド座標系での変換を返すメソッド
+    ///
+    pub fn get_world(&self, id: NodeId) -> Option<Transform> {
+        let mut chain = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node = self.node(id)?;
+            chain.push(node.local);
+            current = node.parent;
+        }
+
+        Some(
+            chain
+                .iter()
+                .rev()
+                .fold(self.origin, |world, local| world.compose(local)),
+        )
+    }
+
+    ///
+    /// ノードの座標系での点を、ワールド座標系へ変換するメソッド
+    ///
+    pub fn local_to_world(&self, id: NodeId, point: numeric::Point2f) -> numeric::Point2f {
+        match self.get_world(id) {
+            Some(world) => world.apply_point(point),
+            None => point,
+        }
+    }
+
+    ///
+    /// ワールド座標系での点を、ノードの座標系へ変換するメソッド
+    ///
+    pub fn world_to_local(&self, id: NodeId, point: numeric::Point2f) -> numeric::Point2f {
+        match self.get_world(id) {
+            Some(world) => world.inverse_point(point),
+            None => point,
+        }
+    }
+
+    ///
+    /// 全てのノードのワールド座標系での変換を計算し、取り付けられたオブジェクトへ反映するメソッド
+    /// 描画の順に、可視状態のノードのIDを返す
+    ///
+    pub fn update_transforms(&mut self) -> Vec<NodeId> {
+        let mut order = Vec::new();
+        let mut stack: Vec<(NodeId, Transform)> = self
+            .sorted_children(&self.roots)
+            .into_iter()
+            .rev()
+            .map(|id| (id, self.origin))
+            .collect();
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let world = parent_world.compose(&self.node(id).unwrap().local);
+            if !world.visible {
+                continue;
+            }
+
+            let node = self.node_mut(id).unwrap();
+            if let Some(object) = node.object.as_mut() {
+                object.apply_transform(&world);
+            }
+            order.push(id);
+
+            let children = self.sorted_children(&self.node(id).unwrap().children);
+            stack.extend(children.into_iter().rev().map(|child| (child, world)));
+        }
+
+        order
+    }
+
+    ///
+    /// 兄弟のノードを描画順に並べる。深度が深いものから先に描画される
+    ///
+    fn sorted_children(&self, ids: &[NodeId]) -> Vec<NodeId> {
+        let mut ids = ids.to_vec();
+        ids.sort_by_key(|id| {
+            std::cmp::Reverse(
+                self.node(*id)
+                    .and_then(|node| node.object.as_ref())
+                    .map_or(0, |object| object.get_drawing_depth()),
+            )
+        });
+        ids
+    }
+}
+
+impl DrawableComponent for SceneGraph {
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        if !self.drwob_essential.visible {
+            return Ok(());
+        }
+
+        for id in self.update_transforms() {
+            if let Some(object) = self.node_mut(id).and_then(|node| node.object.as_mut()) {
+                object.as_drawable_mut().draw(ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn hide(&mut self) {
+        self.drwob_essential.visible = false;
+    }
+
+    #[inline(always)]
+    fn appear(&mut self) {
+        self.drwob_essential.visible = true;
+    }
+
+    #[inline(always)]
+    fn is_visible(&self) -> bool {
+        self.drwob_essential.visible
+    }
+
+    #[inline(always)]
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.drwob_essential.drawing_depth = depth;
+    }
+
+    #[inline(always)]
+    fn get_drawing_depth(&self) -> i8 {
+        self.drwob_essential.drawing_depth
+    }
+}
+
+impl DrawableObject for SceneGraph {
+    #[inline(always)]
+    fn set_position(&mut self, pos: numeric::Point2f) {
+        self.origin.position = pos;
+    }
+
+    #[inline(always)]
+    fn get_position(&self) -> numeric::Point2f {
+        self.origin.position
+    }
+
+    #[inline(always)]
+    fn move_diff(&mut self, offset: numeric::Vector2f) {
+        self.origin.position += offset;
+    }
+}
+
+impl SceneGraph {
+    ///
+    /// 全体に適用される拡大率, 回転, alpha値を設定するメソッド
+    ///
+    pub fn set_origin_transform(&mut self, transform: Transform) {
+        self.origin = transform;
+    }
+
+    pub fn get_origin_transform(&self) -> Transform {
+        self.origin
+    }
+
+    ///
+    /// ワールド座標系での点を含むテクスチャのノードを、手前にあるものから順に返すメソッド
+    ///
+    pub fn pick(&mut self, ctx: &mut ggez::Context, point: numeric::Point2f) -> Vec<NodeId> {
+        let mut hits: Vec<NodeId> = self
+            .update_transforms()
+            .into_iter()
+            .filter(|id| match self.get_object(*id) {
+                Some(NodeObject::Texture(object)) => object.contains(ctx, point),
+                _ => false,
+            })
+            .collect();
+        hits.reverse();
+        hits
+    }
+}
@@ -1,13 +1,17 @@
 #[macro_use]
 pub mod core;
 
+#[macro_use]
+pub mod numeric;
+
+pub mod error;
+
 pub mod graphics;
 
 pub mod device;
 
 pub mod debug;
 
-#[macro_use]
-pub mod numeric;
-
 pub mod sound;
+
+pub mod resource;
@@ -0,0 +1,605 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::Read;
+use std::rc::Rc;
+
+use ggez::graphics as ggraphics;
+
+use crate::error;
+use crate::sound::library::SoundLibrary;
+use crate::sound::SoundData;
+
+///
+/// # ResourceManagerが扱うリソースの種類
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Image,
+    Font,
+    Sound,
+}
+
+impl ResourceKind {
+    fn from_name(name: &str) -> Option<ResourceKind> {
+        match name {
+            "image" => Some(ResourceKind::Image),
+            "font" => Some(ResourceKind::Font),
+            "sound" => Some(ResourceKind::Sound),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceKind::Image => write!(f, "image"),
+            ResourceKind::Font => write!(f, "font"),
+            ResourceKind::Sound => write!(f, "sound"),
+        }
+    }
+}
+
+///
+/// # マニフェストの一行に対応するリソースの情報
+///
+/// ## フィールド
+/// ### group
+/// リソースが属するグループ。Noneの場合はunload_groupで解放されない
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceEntry {
+    pub kind: ResourceKind,
+    pub key: String,
+    pub path: String,
+    pub group: Option<String>,
+}
+
+impl ResourceEntry {
+    pub fn new(kind: ResourceKind, key: &str, path: &str, group: Option<&str>) -> Self {
+        ResourceEntry {
+            kind: kind,
+            key: key.to_string(),
+            path: path.to_string(),
+            group: group.map(|group| group.to_string()),
+        }
+    }
+}
+
+///
+/// マニフェストから、空行と#から始まる行を除いた行を(行番号, 前後の空白を除いた行)で返す関数
+/// 行番号は1から始まる
+///
+pub fn manifest_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+///
+/// マニフェストの一行を、空白で区切られたcolumns個の列に分割する関数
+/// 最後の列には行の残り全体が入るため、パスに空白を含めることができる。列が足りない場合はNoneを返す
+///
+/// ```
+/// use torifune::resource::split_manifest_columns;
+///
+/// assert_eq!(
+///     split_manifest_columns("sound title  /sound/my title.ogg", 3),
+///     Some(vec!["sound", "title", "/sound/my title.ogg"])
+/// );
+/// assert_eq!(split_manifest_columns("sound title", 3), None);
+/// ```
+///
+pub fn split_manifest_columns(line: &str, columns: usize) -> Option<Vec<&str>> {
+    let mut result = Vec::with_capacity(columns);
+    let mut rest = line.trim();
+
+    for _ in 1..columns {
+        let end = rest.find(char::is_whitespace)?;
+        result.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    result.push(rest);
+    Some(result)
+}
+
+///
+/// ggezのファイルシステムからマニフェストを読み込む関数
+///
+pub fn read_manifest(ctx: &mut ggez::Context, path: &str) -> error::Result<String> {
+    if !ggez::filesystem::exists(ctx, path) {
+        return Err(error::Error::ResourceNotFound(path.to_string()));
+    }
+
+    let mut text = String::new();
+    ggez::filesystem::open(ctx, path)?
+        .read_to_string(&mut text)
+        .map_err(|e| error::Error::InvalidManifest(format!("{}: {}", path, e)))?;
+
+    Ok(text)
+}
+
+///
+/// リソースのマニフェストを解析する関数
+///
+/// 一行に一つ、"種類 キー パス"の形式で記述する。種類はimage, font, soundのいずれか。
+/// パスには空白を含めることができる。
+/// "[グループ名]"の行以降のリソースは、そのグループに属する。空行と#から始まる行は無視される
+///
+/// ```
+/// use torifune::resource::*;
+///
+/// let entries = parse_resource_manifest(
+///     "image logo /image/logo.png\n[title]\n# BGM\nsound title /sound/title.ogg",
+/// ).unwrap();
+/// assert_eq!(entries[0], ResourceEntry::new(ResourceKind::Image, "logo", "/image/logo.png", None));
+/// assert_eq!(entries[1].group, Some("title".to_string()));
+/// ```
+///
+pub fn parse_resource_manifest(text: &str) -> error::Result<Vec<ResourceEntry>> {
+    let mut entries = Vec::new();
+    let mut group: Option<String> = None;
+
+    for (number, line) in manifest_lines(text) {
+        let invalid = || error::Error::InvalidManifest(format!("line {}: {}", number, line));
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') || line.len() <= 2 {
+                return Err(invalid());
+            }
+            group = Some(line[1..(line.len() - 1)].trim().to_string());
+            continue;
+        }
+
+        let columns = split_manifest_columns(line, 3).ok_or_else(invalid)?;
+        let kind = ResourceKind::from_name(columns[0]).ok_or_else(invalid)?;
+
+        entries.push(ResourceEntry {
+            kind: kind,
+            key: columns[1].to_string(),
+            path: columns[2].to_string(),
+            group: group.clone(),
+        });
+    }
+
+    Ok(entries)
+}
+
+///
+/// # 読み込みの進捗
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    ///
+    /// 進捗を0.0 ~ 1.0で返すメソッド。読み込むものが無い場合は1.0
+    ///
+    pub fn ratio(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.loaded >= self.total
+    }
+}
+
+///
+/// キャッシュされたリソースと、それを参照しているグループ
+///
+struct CachedResource<T> {
+    resource: T,
+    path: String,
+    groups: HashSet<String>,
+    persistent: bool,
+}
+
+impl<T> CachedResource<T> {
+    fn new(resource: T, path: &str) -> Self {
+        CachedResource {
+            resource: resource,
+            path: path.to_string(),
+            groups: HashSet::new(),
+            persistent: false,
+        }
+    }
+
+    fn attach(&mut self, group: Option<&str>) {
+        match group {
+            Some(group) => {
+                self.groups.insert(group.to_string());
+            }
+            None => self.persistent = true,
+        }
+    }
+
+    ///
+    /// グループの参照を外し、どこからも参照されなくなった場合はtrueを返す
+    ///
+    fn detach(&mut self, group: &str) -> bool {
+        self.groups.remove(group);
+        !self.persistent && self.groups.is_empty()
+    }
+}
+
+///
+/// # 画像, フォント, サウンドデータを文字列のキーで管理する構造体
+///
+/// 読み込んだリソースは共有できるハンドルとしてキャッシュされる。
+/// グループを指定して読み込んだリソースは、全てのグループがunload_groupされた時に解放される
+///
+/// ## フィールド
+/// ### sounds
+/// サウンドデータ。SoundManager::play_by_nameなどにそのまま渡すことができる
+///
+/// ### sound_groups
+/// サウンドデータを参照しているグループ
+///
+/// ### pending
+/// queue_manifestで予約され、まだ読み込まれていないリソース
+///
+/// ### progress
+/// 予約されたリソースの読み込みの進捗
+///
+pub struct ResourceManager {
+    images: HashMap<String, CachedResource<Rc<ggraphics::Image>>>,
+    fonts: HashMap<String, CachedResource<ggraphics::Font>>,
+    sounds: SoundLibrary,
+    sound_groups: HashMap<String, CachedResource<()>>,
+    pending: VecDeque<ResourceEntry>,
+    progress: LoadProgress,
+}
+
+impl ResourceManager {
+    pub fn new() -> Self {
+        ResourceManager {
+            images: HashMap::new(),
+            fonts: HashMap::new(),
+            sounds: SoundLibrary::new(),
+            sound_groups: HashMap::new(),
+            pending: VecDeque::new(),
+            progress: LoadProgress {
+                loaded: 0,
+                total: 0,
+            },
+        }
+    }
+
+    fn check_path(ctx: &mut ggez::Context, kind: ResourceKind, path: &str) -> error::Result<()> {
+        if ggez::filesystem::exists(ctx, path) {
+            Ok(())
+        } else {
+            Err(error::Error::ResourceNotFound(format!("{} {}", kind, path)))
+        }
+    }
+
+    fn not_found(kind: ResourceKind, key: &str) -> error::Error {
+        error::Error::ResourceNotFound(format!("{} '{}' is not loaded", kind, key))
+    }
+
+    ///
+    /// リソースを一つ読み込むメソッド
+    /// 既に同じキーで読み込まれている場合は、グループの参照のみを追加する
+    ///
+    pub fn load(&mut self, ctx: &mut ggez::Context, entry: &ResourceEntry) -> error::Result<()> {
+        let group = entry.group.as_ref().map(|group| group.as_str());
+
+        match entry.kind {
+            ResourceKind::Image => {
+                if !self.images.contains_key(&entry.key) {
+                    Self::check_path(ctx, entry.kind, &entry.path)?;
+                    let image = Rc::new(ggraphics::Image::new(ctx, &entry.path)?);
+                    self.images
+                        .insert(entry.key.clone(), CachedResource::new(image, &entry.path));
+                }
+                self.images.get_mut(&entry.key).unwrap().attach(group);
+            }
+            ResourceKind::Font => {
+                if !self.fonts.contains_key(&entry.key) {
+                    Self::check_path(ctx, entry.kind, &entry.path)?;
+                    let font = ggraphics::Font::new(ctx, &entry.path)?;
+                    self.fonts
+                        .insert(entry.key.clone(), CachedResource::new(font, &entry.path));
+                }
+                self.fonts.get_mut(&entry.key).unwrap().attach(group);
+            }
+            ResourceKind::Sound => {
+                if !self.sounds.contains(&entry.key) {
+                    Self::check_path(ctx, entry.kind, &entry.path)?;
+                    self.sounds.load(ctx, &entry.key, &entry.path)?;
+                }
+                self.sound_groups
+                    .entry(entry.key.clone())
+                    .or_insert_with(|| CachedResource::new((), &entry.path))
+                    .attach(group);
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 画像を読み込み、ハンドルを返すメソッド
+    ///
+    pub fn load_image(
+        &mut self,
+        ctx: &mut ggez::Context,
+        key: &str,
+        path: &str,
+        group: Option<&str>,
+    ) -> error::Result<Rc<ggraphics::Image>> {
+        self.load(
+            ctx,
+            &ResourceEntry::new(ResourceKind::Image, key, path, group),
+        )?;
+        self.get_image(key)
+    }
+
+    ///
+    /// フォントを読み込み、ハンドルを返すメソッド
+    ///
+    pub fn load_font(
+        &mut self,
+        ctx: &mut ggez::Context,
+        key: &str,
+        path: &str,
+        group: Option<&str>,
+    ) -> error::Result<ggraphics::Font> {
+        self.load(
+            ctx,
+            &ResourceEntry::new(ResourceKind::Font, key, path, group),
+        )?;
+        self.get_font(key)
+    }
+
+    ///
+    /// サウンドデータを読み込み、ハンドルを返すメソッド
+    ///
+    pub fn load_sound(
+        &mut self,
+        ctx: &mut ggez::Context,
+        key: &str,
+        path: &str,
+        group: Option<&str>,
+    ) -> error::Result<SoundData> {
+        self.load(
+            ctx,
+            &ResourceEntry::new(ResourceKind::Sound, key, path, group),
+        )?;
+        self.get_sound(key)
+    }
+
+    ///
+    /// パスをキーとして画像を取得するメソッド。読み込まれていない場合は読み込む
+    ///
+    pub fn image_by_path(
+        &mut self,
+        ctx: &mut ggez::Context,
+        path: &str,
+    ) -> error::Result<Rc<ggraphics::Image>> {
+        self.load_image(ctx, path, path, None)
+    }
+
+    pub fn get_image(&self, key: &str) -> error::Result<Rc<ggraphics::Image>> {
+        self.images
+            .get(key)
+            .map(|cached| cached.resource.clone())
+            .ok_or_else(|| Self::not_found(ResourceKind::Image, key))
+    }
+
+    pub fn get_font(&self, key: &str) -> error::Result<ggraphics::Font> {
+        self.fonts
+            .get(key)
+            .map(|cached| cached.resource)
+            .ok_or_else(|| Self::not_found(ResourceKind::Font, key))
+    }
+
+    pub fn get_sound(&self, key: &str) -> error::Result<SoundData> {
+        self.sounds
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Self::not_found(ResourceKind::Sound, key))
+    }
+
+    ///
+    /// 読み込んだサウンドデータを管理するSoundLibraryを返すメソッド
+    ///
+    pub fn get_sound_library(&self) -> &SoundLibrary {
+        &self.sounds
+    }
+
+    pub fn get_sound_library_mut(&mut self) -> &mut SoundLibrary {
+        &mut self.sounds
+    }
+
+    pub fn contains(&self, kind: ResourceKind, key: &str) -> bool {
+        match kind {
+            ResourceKind::Image => self.images.contains_key(key),
+            ResourceKind::Font => self.fonts.contains_key(key),
+            ResourceKind::Sound => self.sounds.contains(key),
+        }
+    }
+
+    pub fn get_path(&self, kind: ResourceKind, key: &str) -> Option<&str> {
+        match kind {
+            ResourceKind::Image => self.images.get(key).map(|cached| cached.path.as_str()),
+            ResourceKind::Font => self.fonts.get(key).map(|cached| cached.path.as_str()),
+            ResourceKind::Sound => self.sounds.get_path(key),
+        }
+    }
+
+    ///
+    /// 画像のハンドルの参照数を返すメソッド。ResourceManager自身が持つ参照も含む
+    ///
+    pub fn get_image_ref_count(&self, key: &str) -> usize {
+        self.images
+            .get(key)
+            .map_or(0, |cached| Rc::strong_count(&cached.resource))
+    }
+
+    ///
+    /// マニフェストに記述されたリソースの読み込みを予約するメソッド
+    /// 予約したリソースはload_pendingで読み込まれる。予約した数を返す
+    ///
+    pub fn queue_manifest(&mut self, ctx: &mut ggez::Context, path: &str) -> error::Result<usize> {
+        let entries = parse_resource_manifest(&read_manifest(ctx, path)?)?;
+        let count = entries.len();
+        for entry in entries {
+            self.queue(entry);
+        }
+
+        Ok(count)
+    }
+
+    ///
+    /// リソースの読み込みを予約するメソッド
+    ///
+    pub fn queue(&mut self, entry: ResourceEntry) {
+        if self.progress.is_complete() {
+            self.progress = LoadProgress {
+                loaded: 0,
+                total: 0,
+            };
+        }
+
+        self.progress.total += 1;
+        self.pending.push_back(entry);
+    }
+
+    ///
+    /// 予約されたリソースを最大でcount個読み込むメソッド
+    /// ローディング画面で毎フレーム呼び出すことを想定している。読み込み後の進捗を返す
+    ///
+    pub fn load_pending(
+        &mut self,
+        ctx: &mut ggez::Context,
+        count: usize,
+    ) -> error::Result<LoadProgress> {
+        for _ in 0..count {
+            let entry = match self.pending.front() {
+                Some(entry) => entry.clone(),
+                None => break,
+            };
+
+            self.load(ctx, &entry)?;
+            self.pending.pop_front();
+            self.progress.loaded += 1;
+        }
+
+        Ok(self.progress)
+    }
+
+    ///
+    /// 予約されたリソースを全て読み込むメソッド
+    ///
+    pub fn load_all_pending(&mut self, ctx: &mut ggez::Context) -> error::Result<LoadProgress> {
+        let count = self.pending.len();
+        self.load_pending(ctx, count)
+    }
+
+    ///
+    /// マニフェストに記述されたリソースを全て読み込むメソッド
+    ///
+    pub fn load_manifest(&mut self, ctx: &mut ggez::Context, path: &str) -> error::Result<usize> {
+        let count = self.queue_manifest(ctx, path)?;
+        self.load_all_pending(ctx)?;
+        Ok(count)
+    }
+
+    pub fn get_progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    ///
+    /// グループの参照を外し、どのグループからも参照されなくなったリソースを解放するメソッド
+    /// グループを指定せずに読み込んだリソースは解放されない。解放した数を返す
+    ///
+    pub fn unload_group(&mut self, group: &str) -> usize {
+        fn detach_all<T>(map: &mut HashMap<String, CachedResource<T>>, group: &str) -> usize {
+            let before = map.len();
+            map.retain(|_, cached| !cached.detach(group));
+            before - map.len()
+        }
+
+        let before = self.pending.len();
+        self.pending
+            .retain(|entry| entry.group.as_ref().map(|g| g.as_str()) != Some(group));
+        self.progress.total -= before - self.pending.len();
+
+        let released_sounds: Vec<String> = self
+            .sound_groups
+            .iter_mut()
+            .filter_map(|(key, cached)| {
+                if cached.detach(group) {
+                    Some(key.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for key in &released_sounds {
+            self.unload(ResourceKind::Sound, key);
+        }
+
+        detach_all(&mut self.images, group)
+            + detach_all(&mut self.fonts, group)
+            + released_sounds.len()
+    }
+
+    ///
+    /// グループに関わらずリソースを解放するメソッド
+    ///
+    pub fn unload(&mut self, kind: ResourceKind, key: &str) {
+        match kind {
+            ResourceKind::Image => {
+                self.images.remove(key);
+            }
+            ResourceKind::Font => {
+                self.fonts.remove(key);
+            }
+            ResourceKind::Sound => {
+                self.sounds.unload(key);
+                self.sound_groups.remove(key);
+            }
+        }
+    }
+
+    ///
+    /// グループに属するリソースのキーを列挙するメソッド
+    ///
+    pub fn group_members(&self, group: &str) -> Vec<(ResourceKind, String)> {
+        let mut members = Vec::new();
+
+        for (key, cached) in &self.images {
+            if cached.groups.contains(group) {
+                members.push((ResourceKind::Image, key.clone()));
+            }
+        }
+        for (key, cached) in &self.fonts {
+            if cached.groups.contains(group) {
+                members.push((ResourceKind::Font, key.clone()));
+            }
+        }
+        for (key, cached) in &self.sound_groups {
+            if cached.groups.contains(group) {
+                members.push((ResourceKind::Sound, key.clone()));
+            }
+        }
+
+        members.sort_by(|a, b| a.1.cmp(&b.1));
+        members
+    }
+}
@@ -1,11 +1,250 @@
-use std::{time::Duration, collections::HashMap};
+pub mod library;
+pub mod looped;
+pub mod spatial;
+pub mod voice;
+
+use std::{fmt, time::Duration, collections::HashMap};
 
 use ggez::audio as gaudio;
 use ggez::audio::SoundSource;
 
+use crate::core::{Clock, Updatable};
+use crate::error;
+use crate::numeric;
+
+use looped::{LoopPoints, LoopedSound};
+use spatial::SoundAttenuation;
+
 pub type SoundData = gaudio::SoundData;
 pub type PlayableSound = gaudio::Source;
-pub type SoundHandler = usize;
+pub type SpatialSound = gaudio::SpatialSource;
+
+///
+/// # 再生したサウンドを指すハンドラ
+/// 停止したサウンドが回収されると世代が進むため、古いハンドラは無効となる
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundHandler {
+    index: usize,
+    generation: u32,
+}
+
+impl fmt::Display for SoundHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}#{}", self.index, self.generation)
+    }
+}
+
+///
+/// # サウンドの再生状態
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+///
+/// # サウンドを流すバス
+/// バスごとに音量とミュートを設定できる
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SoundBusId {
+    Bgm,
+    Se,
+    Voice,
+    System,
+    Custom(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SoundBus {
+    volume: f32,
+    muted: bool,
+}
+
+impl Default for SoundBus {
+    fn default() -> Self {
+	SoundBus {
+	    volume: 1.0,
+	    muted: false,
+	}
+    }
+}
+
+///
+/// # バスごとの音量, ミュートとマスターの音量から、実際の音量を計算する構造体
+/// 設定されていないバスは、音量1.0でミュートされていないものとして扱う
+///
+#[derive(Debug, Clone)]
+pub struct SoundMixer {
+    buses: HashMap<SoundBusId, SoundBus>,
+    master_volume: f32,
+}
+
+impl SoundMixer {
+    pub fn new() -> Self {
+	SoundMixer {
+	    buses: HashMap::new(),
+	    master_volume: 1.0,
+	}
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+	self.master_volume = volume;
+    }
+
+    pub fn get_master_volume(&self) -> f32 {
+	self.master_volume
+    }
+
+    pub fn set_bus_volume(&mut self, bus: SoundBusId, volume: f32) {
+	self.buses.entry(bus).or_default().volume = volume;
+    }
+
+    pub fn get_bus_volume(&self, bus: &SoundBusId) -> f32 {
+	self.buses.get(bus).copied().unwrap_or_default().volume
+    }
+
+    pub fn set_bus_mute(&mut self, bus: SoundBusId, muted: bool) {
+	self.buses.entry(bus).or_default().muted = muted;
+    }
+
+    pub fn is_bus_muted(&self, bus: &SoundBusId) -> bool {
+	self.buses.get(bus).copied().unwrap_or_default().muted
+    }
+
+    ///
+    /// サウンド × バス × マスターの音量を計算する。バスがミュートされている場合は0.0
+    ///
+    pub fn effective_volume(&self, bus: &SoundBusId, volume: f32) -> f32 {
+	let bus = self.buses.get(bus).copied().unwrap_or_default();
+	if bus.muted {
+	    0.0
+	} else {
+	    volume * bus.volume * self.master_volume
+	}
+    }
+}
+
+///
+/// # 同じキーのサウンドが上限数に達した時の振る舞い
+///
+/// StealOldest: 最も古いものを停止して再生する
+/// IgnoreNew: 新しいものを再生しない
+/// Restart: 再生中のものを全て停止して、最初から再生し直す
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoiceLimitPolicy {
+    StealOldest,
+    IgnoreNew,
+    Restart,
+}
+
+///
+/// # サウンドのキーごとの同時再生数の制限
+///
+/// ## フィールド
+/// ### max_instances
+/// 同時に再生できる数。0の場合は、そのキーのサウンドを再生しない
+///
+/// ### min_interval
+/// 前回の再生から、次の再生を受け付けるまでの時間
+///
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceLimit {
+    max_instances: usize,
+    policy: VoiceLimitPolicy,
+    min_interval: Clock,
+}
+
+impl VoiceLimit {
+    pub fn new(max_instances: usize, policy: VoiceLimitPolicy, min_interval: Clock) -> Self {
+	VoiceLimit {
+	    max_instances: max_instances,
+	    policy: policy,
+	    min_interval: min_interval,
+	}
+    }
+
+    ///
+    /// 同じキーのサウンドがactive個再生中の時に、新しいサウンドを再生するため停止する数を返す
+    /// 古いものから順に停止する。新しいサウンドを再生しない場合はNoneを返す
+    ///
+    pub fn voices_to_stop(&self, active: usize) -> Option<usize> {
+	if self.max_instances == 0 {
+	    return None;
+	}
+
+	if active < self.max_instances {
+	    return Some(0);
+	}
+
+	match self.policy {
+	    VoiceLimitPolicy::IgnoreNew => None,
+	    VoiceLimitPolicy::StealOldest => Some(active + 1 - self.max_instances),
+	    VoiceLimitPolicy::Restart => Some(active),
+	}
+    }
+}
+
+///
+/// # 同時再生数の制限を確認するための、再生中のサウンドの情報
+///
+/// ## フィールド
+/// ### same_key
+/// 新しいサウンドと同じキーを持つか
+///
+/// ### capped
+/// 全体の同時再生数に数えるか
+///
+#[derive(Debug, Clone, Copy)]
+pub struct ActiveVoice<H> {
+    pub handler: H,
+    pub priority: i32,
+    pub same_key: bool,
+    pub capped: bool,
+}
+
+///
+/// 優先度priorityの新しいサウンドを再生するために停止するサウンドを返す関数
+/// voicesは古いものから順に並べる。新しいサウンドを再生しない場合はNoneを返し、その場合は何も停止しない
+///
+/// キーごとの制限で停止するものを先に選び、残りが全体の上限capに達していれば、
+/// 新しいサウンドより優先度が低いもののうち、優先度が最も低く最も古いものを加える
+///
+pub fn select_voice_victims<H: Copy + PartialEq>(
+    voices: &[ActiveVoice<H>],
+    limit: Option<&VoiceLimit>,
+    cap: Option<usize>,
+    priority: i32,
+) -> Option<Vec<H>> {
+    let mut victims = Vec::new();
+
+    if let Some(limit) = limit {
+	let same_key: Vec<H> = voices.iter()
+	    .filter(|voice| voice.same_key)
+	    .map(|voice| voice.handler)
+	    .collect();
+	let overflow = limit.voices_to_stop(same_key.len())?;
+	victims.extend(same_key.into_iter().take(overflow));
+    }
+
+    if let Some(cap) = cap {
+	let remaining: Vec<&ActiveVoice<H>> = voices.iter()
+	    .filter(|voice| voice.capped && !victims.contains(&voice.handler))
+	    .collect();
+	if remaining.len() >= cap {
+	    let victim = remaining.into_iter()
+		.filter(|voice| voice.priority < priority)
+		.min_by_key(|voice| voice.priority)?;
+	    victims.push(victim.handler);
+	}
+    }
+
+    Some(victims)
+}
 
 #[derive(Clone)]
 pub struct SoundPlayFlags {
@@ -13,6 +252,12 @@ pub struct SoundPlayFlags {
     pitch: f32,
     repeat: bool,
     volume: f32,
+    bus: SoundBusId,
+    key: Option<String>,
+    priority: i32,
+    position: Option<numeric::Point2f>,
+    attenuation: Option<SoundAttenuation>,
+    loop_points: Option<LoopPoints>,
 }
 
 impl SoundPlayFlags {
@@ -27,8 +272,67 @@ impl SoundPlayFlags {
 	    pitch: pitch,
 	    repeat: repeat,
 	    volume: volume,
+	    bus: SoundBusId::Se,
+	    key: None,
+	    priority: 0,
+	    position: None,
+	    attenuation: None,
+	    loop_points: None,
 	}
     }
+
+    ///
+    /// 再生するバスを指定する。指定しない場合はSoundBusId::Seで再生される
+    ///
+    pub fn with_bus(mut self, bus: SoundBusId) -> SoundPlayFlags {
+	self.bus = bus;
+	self
+    }
+
+    ///
+    /// 同時再生数の制限に用いるキーを指定する
+    ///
+    pub fn with_key(mut self, key: &str) -> SoundPlayFlags {
+	self.key = Some(key.to_string());
+	self
+    }
+
+    ///
+    /// 全体の同時再生数の上限に達した時、優先度の低いものから停止される
+    ///
+    pub fn with_priority(mut self, priority: i32) -> SoundPlayFlags {
+	self.priority = priority;
+	self
+    }
+
+    ///
+    /// 音源の位置を指定する。位置を指定したサウンドは、リスナーとの距離で減衰し、左右にパンされる
+    ///
+    pub fn with_position(mut self, position: numeric::Point2f) -> SoundPlayFlags {
+	self.position = Some(position);
+	self
+    }
+
+    ///
+    /// 減衰の設定を指定する。指定しない場合はSoundManagerの既定の設定が用いられる
+    ///
+    pub fn with_attenuation(mut self, attenuation: SoundAttenuation) -> SoundPlayFlags {
+	self.attenuation = Some(attenuation);
+	self
+    }
+
+    ///
+    /// ループ区間を指定する。イントロを一度再生した後、ループ区間を繰り返す
+    /// ループ区間を指定した場合、repeatと音源の位置は無視される
+    ///
+    pub fn with_loop_points(mut self, loop_points: LoopPoints) -> SoundPlayFlags {
+	self.loop_points = Some(loop_points);
+	self
+    }
+
+    pub fn get_loop_points(&self) -> Option<&LoopPoints> {
+	self.loop_points.as_ref()
+    }
 }
 
 impl Default for SoundPlayFlags {
@@ -38,67 +342,859 @@ impl Default for SoundPlayFlags {
 	    pitch: 1.0,
 	    repeat: false,
 	    volume: 1.0,
+	    bus: SoundBusId::Se,
+	    key: None,
+	    priority: 0,
+	    position: None,
+	    attenuation: None,
+	    loop_points: None,
+	}
+    }
+}
+
+///
+/// # Clockで進行する音量のフェード
+/// BGMのクロスフェードは、現在のBGMのfade_outと新しいBGMのfade_inを同じ時刻に開始して行う
+///
+/// ## フィールド
+/// ### stop_on_end
+/// フェードの終了時にサウンドを停止するか
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundFade {
+    from: f32,
+    to: f32,
+    start: Clock,
+    duration: Clock,
+    stop_on_end: bool,
+}
+
+impl SoundFade {
+    pub fn new(from: f32, to: f32, start: Clock, duration: Clock, stop_on_end: bool) -> Self {
+	SoundFade {
+	    from: from,
+	    to: to,
+	    start: start,
+	    duration: duration,
+	    stop_on_end: stop_on_end,
+	}
+    }
+
+    ///
+    /// fromの音量から0.0までフェードし、終了時に停止するフェード
+    ///
+    pub fn fade_out(from: f32, start: Clock, duration: Clock) -> Self {
+	SoundFade::new(from, 0.0, start, duration, true)
+    }
+
+    ///
+    /// 0.0から1.0までフェードするフェード
+    ///
+    pub fn fade_in(start: Clock, duration: Clock) -> Self {
+	SoundFade::new(0.0, 1.0, start, duration, false)
+    }
+
+    ///
+    /// tの時点での音量を返す。開始前はfrom, 終了後はtoとなる
+    ///
+    pub fn volume_at(&self, t: Clock) -> f32 {
+	if self.duration == 0 {
+	    return self.to;
+	}
+
+	let progress = (t.saturating_sub(self.start) as f32 / self.duration as f32).min(1.0);
+	self.from + ((self.to - self.from) * progress)
+    }
+
+    pub fn is_finished(&self, t: Clock) -> bool {
+	t.saturating_sub(self.start) >= self.duration
+    }
+
+    pub fn stops_on_end(&self) -> bool {
+	self.stop_on_end
+    }
+}
+
+///
+/// 位置を持たないサウンド, 位置を持つサウンドと、ループ区間を持つサウンド
+///
+enum SourceKind {
+    Plain(PlayableSound),
+    Spatial(SpatialSound),
+    Looped(LoopedSound),
+}
+
+impl SourceKind {
+    ///
+    /// ggezのSoundSourceとして参照する。ループ区間を持つサウンドの場合はNone
+    ///
+    fn as_source(&self) -> Option<&dyn SoundSource> {
+	match self {
+	    SourceKind::Plain(source) => Some(source),
+	    SourceKind::Spatial(source) => Some(source),
+	    SourceKind::Looped(_) => None,
+	}
+    }
+
+    fn as_source_mut(&mut self) -> Option<&mut dyn SoundSource> {
+	match self {
+	    SourceKind::Plain(source) => Some(source),
+	    SourceKind::Spatial(source) => Some(source),
+	    SourceKind::Looped(_) => None,
+	}
+    }
+
+    fn play_later(&self) -> ggez::GameResult {
+	match self {
+	    SourceKind::Plain(source) => source.play_later(),
+	    SourceKind::Spatial(source) => source.play_later(),
+	    SourceKind::Looped(source) => {
+		source.play();
+		Ok(())
+	    }
+	}
+    }
+
+    fn pause(&self) {
+	match self {
+	    SourceKind::Plain(source) => source.pause(),
+	    SourceKind::Spatial(source) => source.pause(),
+	    SourceKind::Looped(source) => source.pause(),
+	}
+    }
+
+    fn resume(&self) {
+	match self {
+	    SourceKind::Plain(source) => source.resume(),
+	    SourceKind::Spatial(source) => source.resume(),
+	    SourceKind::Looped(source) => source.play(),
+	}
+    }
+
+    fn stop(&mut self) {
+	match self {
+	    SourceKind::Plain(source) => source.stop(),
+	    SourceKind::Spatial(source) => source.stop(),
+	    SourceKind::Looped(source) => source.stop(),
+	}
+    }
+
+    fn stopped(&self) -> bool {
+	match self {
+	    SourceKind::Plain(source) => source.stopped(),
+	    SourceKind::Spatial(source) => source.stopped(),
+	    SourceKind::Looped(source) => source.stopped(),
+	}
+    }
+
+    fn paused(&self) -> bool {
+	match self {
+	    SourceKind::Plain(source) => source.paused(),
+	    SourceKind::Spatial(source) => source.paused(),
+	    SourceKind::Looped(source) => source.paused(),
+	}
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+	match self {
+	    SourceKind::Plain(source) => source.set_volume(volume),
+	    SourceKind::Spatial(source) => source.set_volume(volume),
+	    SourceKind::Looped(source) => source.set_volume(volume),
+	}
+    }
+
+    ///
+    /// -1.0 ~ 1.0のパンを設定する。位置を持たないサウンドの場合は何もしない
+    ///
+    fn set_pan(&mut self, pan: f32) {
+	if let SourceKind::Spatial(source) = self {
+	    source.set_position(spatial::pan_emitter_position(pan));
 	}
     }
 }
 
+///
+/// 再生中のサウンドと、実際の音量を計算するための情報
+///
+struct PlayingSound {
+    source: SourceKind,
+    bus: SoundBusId,
+    volume: f32,
+    fade_volume: f32,
+    fade: Option<SoundFade>,
+    key: Option<String>,
+    priority: i32,
+    serial: u64,
+    position: Option<numeric::Point2f>,
+    attenuation: SoundAttenuation,
+}
+
+impl PlayingSound {
+    fn state(&self) -> SoundState {
+	if self.source.stopped() {
+	    SoundState::Stopped
+	} else if self.source.paused() {
+	    SoundState::Paused
+	} else {
+	    SoundState::Playing
+	}
+    }
+}
+
+struct SoundSlot<T> {
+    generation: u32,
+    sound: Option<T>,
+}
+
+///
+/// # 世代付きのハンドラでサウンドを管理するためのスロット
+/// 要素を取り除くとスロットの世代が進むため、再利用されたスロットを古いハンドラで参照することはできない
+///
+pub(crate) struct SoundSlots<T> {
+    slots: Vec<SoundSlot<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> SoundSlots<T> {
+    pub fn new() -> Self {
+	SoundSlots {
+	    slots: Vec::new(),
+	    free: Vec::new(),
+	}
+    }
+
+    pub fn insert(&mut self, sound: T) -> SoundHandler {
+	match self.free.pop() {
+	    Some(index) => {
+		let slot = &mut self.slots[index];
+		slot.sound = Some(sound);
+		SoundHandler {
+		    index: index,
+		    generation: slot.generation,
+		}
+	    }
+	    None => {
+		self.slots.push(SoundSlot {
+		    generation: 0,
+		    sound: Some(sound),
+		});
+		SoundHandler {
+		    index: self.slots.len() - 1,
+		    generation: 0,
+		}
+	    }
+	}
+    }
+
+    pub fn get(&self, handler: &SoundHandler) -> Option<&T> {
+	match self.slots.get(handler.index) {
+	    Some(slot) if slot.generation == handler.generation => slot.sound.as_ref(),
+	    _ => None,
+	}
+    }
+
+    pub fn get_mut(&mut self, handler: &SoundHandler) -> Option<&mut T> {
+	match self.slots.get_mut(handler.index) {
+	    Some(slot) if slot.generation == handler.generation => slot.sound.as_mut(),
+	    _ => None,
+	}
+    }
+
+    pub fn remove(&mut self, handler: &SoundHandler) -> Option<T> {
+	let slot = match self.slots.get_mut(handler.index) {
+	    Some(slot) if slot.generation == handler.generation => slot,
+	    _ => return None,
+	};
+
+	let sound = slot.sound.take();
+	if sound.is_some() {
+	    slot.generation = slot.generation.wrapping_add(1);
+	    self.free.push(handler.index);
+	}
+
+	sound
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SoundHandler, &T)> {
+	self.slots.iter().enumerate().filter_map(|(index, slot)| {
+	    slot.sound.as_ref().map(|sound| {
+		(SoundHandler {
+		    index: index,
+		    generation: slot.generation,
+		}, sound)
+	    })
+	})
+    }
+
+    ///
+    /// finishedがtrueを返す要素を取り除き、無効となったハンドラを返す
+    ///
+    pub fn reap<F>(&mut self, finished: F) -> Vec<SoundHandler>
+    where
+	F: Fn(&T) -> bool,
+    {
+	let handlers: Vec<SoundHandler> = self.iter()
+	    .filter(|(_, sound)| finished(sound))
+	    .map(|(handler, _)| handler)
+	    .collect();
+
+	for handler in &handlers {
+	    self.remove(handler);
+	}
+
+	handlers
+    }
+}
+
 pub struct SoundManager {
-    playing_map: HashMap<SoundHandler, PlayableSound>,
-    next_sound_handler: SoundHandler,
+    playing_map: SoundSlots<PlayingSound>,
+    mixer: SoundMixer,
+    current_bgm: Option<(String, SoundHandler)>,
+    now: Clock,
+    voice_limits: HashMap<String, VoiceLimit>,
+    last_triggered: HashMap<String, Clock>,
+    voice_cap: Option<usize>,
+    next_serial: u64,
+    listener: numeric::Point2f,
+    default_attenuation: SoundAttenuation,
 }
 
 impl SoundManager {
     pub fn new() -> Self {
 	SoundManager {
-	    playing_map: HashMap::new(),
-	    next_sound_handler: 0,
+	    playing_map: SoundSlots::new(),
+	    mixer: SoundMixer::new(),
+	    current_bgm: None,
+	    now: 0,
+	    voice_limits: HashMap::new(),
+	    last_triggered: HashMap::new(),
+	    voice_cap: None,
+	    next_serial: 0,
+	    listener: numeric::Point2f::new(0.0, 0.0),
+	    default_attenuation: SoundAttenuation::default(),
 	}
     }
 
+    ///
+    /// 同時再生数の制限により再生されなかった場合はNoneを返す
+    ///
     pub fn play(
 	&mut self,
 	ctx: &mut ggez::Context,
 	sound_data: SoundData,
 	flags: Option<SoundPlayFlags>,
-    ) -> SoundHandler {
-	let mut sound = PlayableSound::from_data(ctx, sound_data).unwrap();
+    ) -> Option<SoundHandler> {
+	match self.try_play(ctx, sound_data, flags) {
+	    Ok(handler) => handler,
+	    Err(e) => panic!("{}", e),
+	}
+    }
+
+    ///
+    /// 再生に失敗した場合にErrorを返すplay
+    ///
+    pub fn try_play(
+	&mut self,
+	ctx: &mut ggez::Context,
+	sound_data: SoundData,
+	flags: Option<SoundPlayFlags>,
+    ) -> error::Result<Option<SoundHandler>> {
+	let flags = flags.unwrap_or_else(|| SoundPlayFlags::new(0, 1.0, false, 1.0));
+
+	if !self.admit_voice(&flags) {
+	    return Ok(None);
+	}
+
+	self.start_sound(ctx, sound_data, flags).map(Some)
+    }
+
+    ///
+    /// キーごとの同時再生数の制限を設定する
+    ///
+    pub fn set_voice_limit(&mut self, key: &str, limit: VoiceLimit) {
+	self.voice_limits.insert(key.to_string(), limit);
+    }
+
+    pub fn remove_voice_limit(&mut self, key: &str) {
+	self.voice_limits.remove(key);
+    }
+
+    ///
+    /// 全体の同時再生数の上限を設定する。Noneの場合は制限しない
+    /// Bgmバスのサウンドと現在のBGMは数えない
+    ///
+    pub fn set_voice_cap(&mut self, cap: Option<usize>) {
+	self.voice_cap = cap;
+    }
+
+    ///
+    /// 停止していないサウンドのハンドラを、古いものから順に返す
+    ///
+    fn active_voices<F>(&self, filter: F) -> Vec<SoundHandler>
+    where
+	F: Fn(&PlayingSound) -> bool,
+    {
+	let mut voices: Vec<(u64, SoundHandler)> = self.playing_map.iter()
+	    .filter(|(_, playing)| playing.state() != SoundState::Stopped && filter(playing))
+	    .map(|(handler, playing)| (playing.serial, handler))
+	    .collect();
+	voices.sort_by_key(|(serial, _)| *serial);
+	voices.into_iter().map(|(_, handler)| handler).collect()
+    }
+
+    ///
+    /// 停止して、すぐにハンドラを無効にする
+    ///
+    fn discard(&mut self, handler: SoundHandler) {
+	self.stop(handler);
+	self.playing_map.remove(&handler);
+	if self.get_current_bgm() == Some(handler) {
+	    self.current_bgm = None;
+	}
+    }
+
+    ///
+    /// 同時再生数の制限を確認し、再生してよいかを返す
+    /// 必要であれば、再生中のサウンドを停止して枠を空ける
+    ///
+    fn admit_voice(&mut self, flags: &SoundPlayFlags) -> bool {
+	let limit = match &flags.key {
+	    Some(key) => self.voice_limits.get(key).copied(),
+	    None => None,
+	};
+
+	if let (Some(key), Some(limit)) = (&flags.key, &limit) {
+	    if let Some(last) = self.last_triggered.get(key) {
+		if self.now.saturating_sub(*last) < limit.min_interval {
+		    return false;
+		}
+	    }
+	}
+
+	// BGMは全体の同時再生数の制限を受けないため、数えない
+	let bgm = self.get_current_bgm();
+	let voices: Vec<ActiveVoice<SoundHandler>> = self.active_voices(|_| true)
+	    .into_iter()
+	    .map(|handler| {
+		let playing = self.playing_map.get(&handler).unwrap();
+		ActiveVoice {
+		    handler: handler,
+		    priority: playing.priority,
+		    same_key: flags.key.is_some() && playing.key == flags.key,
+		    capped: playing.bus != SoundBusId::Bgm && Some(handler) != bgm,
+		}
+	    })
+	    .collect();
+
+	// 再生すると決まってから停止する
+	let victims = match select_voice_victims(&voices, limit.as_ref(), self.voice_cap, flags.priority) {
+	    Some(victims) => victims,
+	    None => return false,
+	};
+	for handler in victims {
+	    self.discard(handler);
+	}
+
+	if let Some(key) = &flags.key {
+	    self.last_triggered.insert(key.clone(), self.now);
+	}
+
+	true
+    }
+
+    ///
+    /// 同時再生数の制限を確認せずに再生する
+    ///
+    fn start_sound(
+	&mut self,
+	ctx: &mut ggez::Context,
+	sound_data: SoundData,
+	flags: SoundPlayFlags,
+    ) -> error::Result<SoundHandler> {
+	let fade_in = Duration::from_millis(flags.fadein_mills);
+	let position = match flags.loop_points {
+	    Some(_) => None,
+	    None => flags.position,
+	};
+
+	let mut sound = match (&flags.loop_points, position) {
+	    (Some(loop_points), _) => SourceKind::Looped(
+		LoopedSound::from_data(ctx, sound_data, loop_points, flags.pitch, fade_in)?
+	    ),
+	    (None, Some(_)) => {
+		let mut sound = SpatialSound::from_data(ctx, sound_data)?;
+		sound.set_ears(spatial::SPATIAL_LEFT_EAR, spatial::SPATIAL_RIGHT_EAR);
+		SourceKind::Spatial(sound)
+	    }
+	    (None, None) => SourceKind::Plain(PlayableSound::from_data(ctx, sound_data)?),
+	};
+
+	if let Some(source) = sound.as_source_mut() {
+	    source.set_fade_in(fade_in);
+	    source.set_pitch(flags.pitch);
+	    source.set_repeat(flags.repeat);
+	}
+	sound.set_volume(0.0);
 
-	if let Some(flags) = flags {
-	    sound.set_fade_in(Duration::from_millis(flags.fadein_mills));
-	    sound.set_pitch(flags.pitch);
-	    sound.set_repeat(flags.repeat);
-	    sound.set_volume(flags.volume);
+	let serial = self.next_serial;
+	self.next_serial += 1;
+
+	let handler = self.playing_map.insert(PlayingSound {
+	    source: sound,
+	    bus: flags.bus,
+	    volume: flags.volume,
+	    fade_volume: 1.0,
+	    fade: None,
+	    key: flags.key,
+	    priority: flags.priority,
+	    serial: serial,
+	    position: position,
+	    attenuation: flags.attenuation.unwrap_or(self.default_attenuation),
+	});
+
+	self.apply_sound_volume(handler);
+	if let Err(e) = self.playing_map.get(&handler).unwrap().source.play_later() {
+	    self.playing_map.remove(&handler);
+	    return Err(e.into());
 	}
-	
-	let handler = self.issue_sound_handler();
-	sound.play_later().unwrap();
-	self.playing_map.insert(handler, sound);
-	handler
+
+	Ok(handler)
+    }
+
+    ///
+    /// 回収済み, または存在しないハンドラの場合はNoneを返す
+    /// ループ区間を持つサウンドはggezのSoundSourceではないため、Noneを返す
+    ///
+    pub fn ref_sound(&self, handler: SoundHandler) -> Option<&dyn SoundSource> {
+	self.playing_map.get(&handler).and_then(|playing| playing.source.as_source())
     }
 
-    fn issue_sound_handler(&mut self) -> SoundHandler {
-	let ret = self.next_sound_handler;
-	self.next_sound_handler += 1;
-	ret
+    ///
+    /// 音量を変更する場合は、バスの音量が反映されるset_sound_volumeを用いる
+    ///
+    pub fn ref_sound_mut(&mut self, handler: SoundHandler) -> Option<&mut dyn SoundSource> {
+	self.playing_map.get_mut(&handler).and_then(|playing| playing.source.as_source_mut())
     }
 
-    pub fn ref_sound(&self, handler: SoundHandler) -> &PlayableSound {
-	self.playing_map.get(&handler).unwrap()
+    ///
+    /// サウンドの再生状態を返す。回収済みのハンドラの場合はNoneを返す
+    ///
+    pub fn get_state(&self, handler: SoundHandler) -> Option<SoundState> {
+	self.playing_map.get(&handler).map(|playing| playing.state())
     }
 
-    pub fn ref_sound_mut(&mut self, handler: SoundHandler) -> &mut PlayableSound {
-	self.playing_map.get_mut(&handler).unwrap()
+    ///
+    /// 回収されていないサウンドのハンドラと再生状態を列挙する
+    ///
+    pub fn playing_sounds(&self) -> impl Iterator<Item = (SoundHandler, SoundState)> + '_ {
+	self.playing_map.iter().map(|(handler, playing)| (handler, playing.state()))
     }
 
+    ///
+    /// 停止したサウンドを回収する。回収したサウンドのハンドラは無効となる
+    /// 一時停止中のサウンドは回収しない
+    ///
+    pub fn reap_finished(&mut self) {
+	let finished = self.playing_map.reap(|playing| playing.state() == SoundState::Stopped);
+
+	if let Some(bgm) = self.get_current_bgm() {
+	    if finished.contains(&bgm) {
+		self.current_bgm = None;
+	    }
+	}
+    }
+
+    ///
+    /// 存在しないSoundHandlerが指定された場合にErrorを返すref_sound
+    ///
+    pub fn try_ref_sound(&self, handler: SoundHandler) -> error::Result<&dyn SoundSource> {
+	self.playing_map.get(&handler)
+	    .and_then(|playing| playing.source.as_source())
+	    .ok_or(error::Error::UnknownSoundHandler(handler))
+    }
+
+    ///
+    /// 存在しないSoundHandlerが指定された場合にErrorを返すref_sound_mut
+    ///
+    pub fn try_ref_sound_mut(&mut self, handler: SoundHandler) -> error::Result<&mut dyn SoundSource> {
+	self.playing_map.get_mut(&handler)
+	    .and_then(|playing| playing.source.as_source_mut())
+	    .ok_or(error::Error::UnknownSoundHandler(handler))
+    }
+
+    ///
+    /// 再生中のサウンドに音量を反映する。busがNoneの場合は全てのサウンドに反映する
+    ///
+    fn apply_volume(&mut self, bus: Option<&SoundBusId>) {
+	let handlers: Vec<SoundHandler> = self.playing_map.iter()
+	    .filter(|(_, playing)| bus.map_or(true, |bus| playing.bus == *bus))
+	    .map(|(handler, _)| handler)
+	    .collect();
+
+	for handler in handlers {
+	    self.apply_sound_volume(handler);
+	}
+    }
+
+    ///
+    /// マスターの音量を変更する。各サウンド, 各バスの音量は保持される
+    ///
     pub fn change_global_volume(&mut self, volume: f32) {
-	for (_, bgm) in self.playing_map.iter_mut() {
-	    bgm.set_volume(volume);
+	self.mixer.set_master_volume(volume);
+	self.apply_volume(None);
+    }
+
+    pub fn get_global_volume(&self) -> f32 {
+	self.mixer.get_master_volume()
+    }
+
+    pub fn set_bus_volume(&mut self, bus: SoundBusId, volume: f32) {
+	self.mixer.set_bus_volume(bus.clone(), volume);
+	self.apply_volume(Some(&bus));
+    }
+
+    pub fn get_bus_volume(&self, bus: &SoundBusId) -> f32 {
+	self.mixer.get_bus_volume(bus)
+    }
+
+    pub fn set_bus_mute(&mut self, bus: SoundBusId, muted: bool) {
+	self.mixer.set_bus_mute(bus.clone(), muted);
+	self.apply_volume(Some(&bus));
+    }
+
+    pub fn is_bus_muted(&self, bus: &SoundBusId) -> bool {
+	self.mixer.is_bus_muted(bus)
+    }
+
+    pub fn get_mixer(&self) -> &SoundMixer {
+	&self.mixer
+    }
+
+    ///
+    /// サウンド個別の音量を変更する
+    ///
+    pub fn set_sound_volume(&mut self, handler: SoundHandler, volume: f32) {
+	if let Some(playing) = self.playing_map.get_mut(&handler) {
+	    playing.volume = volume;
+	}
+	self.apply_sound_volume(handler);
+    }
+
+    ///
+    /// 音量を反映する。位置を持つサウンドの場合は、減衰とパンも反映する
+    ///
+    fn apply_sound_volume(&mut self, handler: SoundHandler) {
+	if let Some(playing) = self.playing_map.get(&handler) {
+	    let (gain, pan) = match playing.position {
+		Some(position) => playing.attenuation.evaluate(self.listener, position),
+		None => (1.0, 0.0),
+	    };
+	    let volume = self.mixer.effective_volume(&playing.bus, playing.volume * playing.fade_volume * gain);
+
+	    let source = &mut self.playing_map.get_mut(&handler).unwrap().source;
+	    source.set_pan(pan);
+	    source.set_volume(volume);
+	}
+    }
+
+    ///
+    /// リスナーの位置を変更する。位置を持つサウンドの減衰とパンが更新される
+    ///
+    pub fn set_listener_position(&mut self, position: numeric::Point2f) {
+	self.listener = position;
+
+	let handlers: Vec<SoundHandler> = self.playing_map.iter()
+	    .filter(|(_, playing)| playing.position.is_some())
+	    .map(|(handler, _)| handler)
+	    .collect();
+
+	for handler in handlers {
+	    self.apply_sound_volume(handler);
+	}
+    }
+
+    pub fn get_listener_position(&self) -> numeric::Point2f {
+	self.listener
+    }
+
+    ///
+    /// SoundPlayFlagsで減衰の設定が指定されなかった場合に用いる設定を変更する
+    /// 既に再生中のサウンドには影響しない
+    ///
+    pub fn set_default_attenuation(&mut self, attenuation: SoundAttenuation) {
+	self.default_attenuation = attenuation;
+    }
+
+    ///
+    /// 再生中のサウンドの位置を変更する
+    /// 位置を指定せずに再生したサウンドの場合は何もしない
+    ///
+    pub fn set_sound_position(&mut self, handler: SoundHandler, position: numeric::Point2f) {
+	if let Some(playing) = self.playing_map.get_mut(&handler) {
+	    if playing.position.is_some() {
+		playing.position = Some(position);
+	    }
+	}
+	self.apply_sound_volume(handler);
+    }
+
+    pub fn get_sound_position(&self, handler: SoundHandler) -> Option<numeric::Point2f> {
+	self.playing_map.get(&handler).and_then(|playing| playing.position)
+    }
+
+    pub fn pause(&mut self, handler: SoundHandler) {
+	if let Some(playing) = self.playing_map.get(&handler) {
+	    playing.source.pause();
+	}
+    }
+
+    pub fn resume(&mut self, handler: SoundHandler) {
+	if let Some(playing) = self.playing_map.get(&handler) {
+	    playing.source.resume();
 	}
     }
 
     pub fn stop(&mut self, handler: SoundHandler) {
-	if let Some(sound) = self.playing_map.get_mut(&handler) {
-	    sound.stop();
+	if let Some(playing) = self.playing_map.get_mut(&handler) {
+	    playing.source.stop();
+	    playing.fade = None;
+	}
+    }
+
+    ///
+    /// durationの時間をかけてフェードアウトしてから停止する
+    /// フェードの進行はupdateで行われる
+    ///
+    pub fn stop_with_fade(&mut self, handler: SoundHandler, duration: Clock) {
+	if duration == 0 {
+	    self.stop(handler);
+	    return;
+	}
+
+	let now = self.now;
+	if let Some(playing) = self.playing_map.get_mut(&handler) {
+	    playing.fade = Some(SoundFade::fade_out(playing.fade_volume, now, duration));
+	}
+    }
+
+    ///
+    /// durationの時間をかけてフェードインする
+    ///
+    pub fn fade_in(&mut self, handler: SoundHandler, duration: Clock) {
+	let now = self.now;
+	if let Some(playing) = self.playing_map.get_mut(&handler) {
+	    playing.fade_volume = 0.0;
+	    playing.fade = Some(SoundFade::fade_in(now, duration));
 	}
+	self.apply_sound_volume(handler);
+    }
+
+    pub fn play_bgm(
+	&mut self,
+	ctx: &mut ggez::Context,
+	name: &str,
+	sound_data: SoundData,
+	flags: Option<SoundPlayFlags>,
+	crossfade: Clock,
+    ) -> SoundHandler {
+	match self.try_play_bgm(ctx, name, sound_data, flags, crossfade) {
+	    Ok(handler) => handler,
+	    Err(e) => panic!("{}", e),
+	}
+    }
+
+    ///
+    /// BGMを再生する
+    /// 同じ名前のBGMが既に再生中の場合は何もせず、そのSoundHandlerを返す。
+    /// 別のBGMが再生中の場合は、crossfadeの時間をかけてクロスフェードする
+    /// BGMは同時再生数の制限を受けない
+    ///
+    pub fn try_play_bgm(
+	&mut self,
+	ctx: &mut ggez::Context,
+	name: &str,
+	sound_data: SoundData,
+	flags: Option<SoundPlayFlags>,
+	crossfade: Clock,
+    ) -> error::Result<SoundHandler> {
+	if let Some(handler) = self.get_current_bgm_if(name) {
+	    return Ok(handler);
+	}
+
+	let flags = flags
+	    .unwrap_or_else(|| SoundPlayFlags::new(0, 1.0, true, 1.0))
+	    .with_bus(SoundBusId::Bgm);
+	let handler = self.start_sound(ctx, sound_data, flags)?;
+
+	self.stop_bgm(crossfade);
+	if crossfade > 0 {
+	    self.fade_in(handler, crossfade);
+	}
+	self.current_bgm = Some((name.to_string(), handler));
+
+	Ok(handler)
+    }
+
+    ///
+    /// 現在のBGMを、durationの時間をかけてフェードアウトしてから停止する
+    ///
+    pub fn stop_bgm(&mut self, duration: Clock) {
+	if let Some((_, handler)) = self.current_bgm.take() {
+	    self.stop_with_fade(handler, duration);
+	}
+    }
+
+    pub fn get_current_bgm(&self) -> Option<SoundHandler> {
+	self.current_bgm.as_ref().map(|(_, handler)| *handler)
+    }
+
+    pub fn get_current_bgm_name(&self) -> Option<&str> {
+	self.current_bgm.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    ///
+    /// nameのBGMが再生中の場合、そのSoundHandlerを返す
+    ///
+    fn get_current_bgm_if(&self, name: &str) -> Option<SoundHandler> {
+	match &self.current_bgm {
+	    Some((current, handler)) if current == name => {
+		match self.playing_map.get(handler) {
+		    Some(playing) if !playing.source.stopped() => Some(*handler),
+		    _ => None,
+		}
+	    }
+	    _ => None,
+	}
+    }
+}
+
+impl Updatable for SoundManager {
+    ///
+    /// フェードを進行させ、停止したサウンドを回収する
+    ///
+    fn update(&mut self, _ctx: &mut ggez::Context, t: Clock) {
+	self.now = t;
+
+	let fading: Vec<SoundHandler> = self.playing_map.iter()
+	    .filter(|(_, playing)| playing.fade.is_some())
+	    .map(|(handler, _)| handler)
+	    .collect();
+
+	for handler in fading {
+	    let playing = self.playing_map.get_mut(&handler).unwrap();
+	    let fade = playing.fade.unwrap();
+	    playing.fade_volume = fade.volume_at(t);
+
+	    if fade.is_finished(t) {
+		playing.fade = None;
+		if fade.stops_on_end() {
+		    playing.source.stop();
+		}
+	    }
+
+	    self.apply_sound_volume(handler);
+	}
+
+	self.reap_finished();
     }
 }
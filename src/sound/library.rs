@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use crate::error;
+use crate::resource::{manifest_lines, read_manifest, split_manifest_columns};
+use crate::sound::looped::LoopPoints;
+use crate::sound::*;
+
+///
+/// マニフェストを解析する関数
+///
+/// 一行に一つ、"キー パス"の形式で記述する。パスには空白を含めることができる。
+/// 空行と#から始まる行は無視される
+///
+/// ```
+/// use torifune::sound::library::parse_manifest;
+///
+/// let entries = parse_manifest("# SE\ncursor /sound/cursor.wav\n\nbgm1 /sound/title.ogg").unwrap();
+/// assert_eq!(entries[0], ("cursor".to_string(), "/sound/cursor.wav".to_string()));
+/// assert_eq!(entries.len(), 2);
+/// ```
+///
+pub fn parse_manifest(text: &str) -> error::Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+
+    for (number, line) in manifest_lines(text) {
+        match split_manifest_columns(line, 2) {
+            Some(columns) => entries.push((columns[0].to_string(), columns[1].to_string())),
+            None => {
+                return Err(error::Error::InvalidManifest(format!(
+                    "line {}: {}",
+                    number, line
+                )))
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+struct SoundLibraryEntry {
+    path: String,
+    data: SoundData,
+    ref_count: usize,
+    loop_points: Option<LoopPoints>,
+}
+
+///
+/// # 文字列のキーでSoundDataを管理する構造体
+///
+/// acquireで参照カウントが増え、releaseで減る。参照カウントが0のものはunload_unusedで解放される
+///
+pub struct SoundLibrary {
+    entries: HashMap<String, SoundLibraryEntry>,
+}
+
+impl SoundLibrary {
+    pub fn new() -> Self {
+        SoundLibrary {
+            entries: HashMap::new(),
+        }
+    }
+
+    ///
+    /// ggezのファイルシステムからSoundDataを読み込み、keyで登録するメソッド
+    /// 既に同じキーで読み込まれている場合は何もしない
+    ///
+    pub fn load(&mut self, ctx: &mut ggez::Context, key: &str, path: &str) -> error::Result<()> {
+        if self.entries.contains_key(key) {
+            return Ok(());
+        }
+
+        if !ggez::filesystem::exists(ctx, path) {
+            return Err(error::Error::ResourceNotFound(path.to_string()));
+        }
+
+        let data = SoundData::new(ctx, path)?;
+        self.entries.insert(
+            key.to_string(),
+            SoundLibraryEntry {
+                path: path.to_string(),
+                data: data,
+                ref_count: 0,
+                loop_points: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    ///
+    /// マニフェストに記述されたサウンドを全て読み込むメソッド
+    /// 読み込んだ数を返す
+    ///
+    pub fn load_manifest(&mut self, ctx: &mut ggez::Context, path: &str) -> error::Result<usize> {
+        let entries = parse_manifest(&read_manifest(ctx, path)?)?;
+        for (key, path) in &entries {
+            self.load(ctx, key, path)?;
+        }
+
+        Ok(entries.len())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SoundData> {
+        self.entries.get(key).map(|entry| &entry.data)
+    }
+
+    pub fn get_path(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|entry| entry.path.as_str())
+    }
+
+    ///
+    /// サウンドのループ区間を設定するメソッド
+    /// play_by_name, play_bgm_by_nameでflagsにループ区間が指定されていない場合に用いられる
+    ///
+    pub fn set_loop_points(
+        &mut self,
+        key: &str,
+        loop_points: Option<LoopPoints>,
+    ) -> error::Result<()> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.loop_points = loop_points;
+                Ok(())
+            }
+            None => Err(error::Error::ResourceNotFound(key.to_string())),
+        }
+    }
+
+    pub fn get_loop_points(&self, key: &str) -> Option<&LoopPoints> {
+        self.entries
+            .get(key)
+            .and_then(|entry| entry.loop_points.as_ref())
+    }
+
+    ///
+    /// flagsにループ区間が指定されていない場合、登録されたループ区間を設定する
+    ///
+    fn apply_loop_points(&self, key: &str, flags: SoundPlayFlags) -> SoundPlayFlags {
+        match (flags.get_loop_points(), self.get_loop_points(key)) {
+            (None, Some(loop_points)) => flags.with_loop_points(*loop_points),
+            _ => flags,
+        }
+    }
+
+    ///
+    /// 参照カウントを増やし、SoundDataを返すメソッド
+    ///
+    pub fn acquire(&mut self, key: &str) -> error::Result<SoundData> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.ref_count += 1;
+                Ok(entry.data.clone())
+            }
+            None => Err(error::Error::ResourceNotFound(key.to_string())),
+        }
+    }
+
+    ///
+    /// 参照カウントを減らすメソッド
+    ///
+    pub fn release(&mut self, key: &str) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.ref_count = entry.ref_count.saturating_sub(1);
+        }
+    }
+
+    pub fn get_ref_count(&self, key: &str) -> usize {
+        self.entries.get(key).map_or(0, |entry| entry.ref_count)
+    }
+
+    ///
+    /// 参照カウントに関わらず解放するメソッド
+    ///
+    pub fn unload(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    ///
+    /// 参照カウントが0のサウンドを全て解放するメソッド
+    /// 解放した数を返す
+    ///
+    pub fn unload_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| entry.ref_count > 0);
+        before - self.entries.len()
+    }
+}
+
+impl SoundManager {
+    ///
+    /// SoundLibraryに登録されたサウンドをキーで指定して再生するメソッド
+    /// flagsでキーが指定されていない場合、同時再生数の制限にはSoundLibraryのキーが用いられる
+    ///
+    pub fn play_by_name(
+        &mut self,
+        ctx: &mut ggez::Context,
+        library: &SoundLibrary,
+        key: &str,
+        flags: Option<SoundPlayFlags>,
+    ) -> error::Result<Option<SoundHandler>> {
+        let flags = flags.unwrap_or_else(|| SoundPlayFlags::new(0, 1.0, false, 1.0));
+        let flags = match flags.key {
+            Some(_) => flags,
+            None => flags.with_key(key),
+        };
+
+        let flags = library.apply_loop_points(key, flags);
+
+        match library.get(key) {
+            Some(data) => self.try_play(ctx, data.clone(), Some(flags)),
+            None => Err(error::Error::ResourceNotFound(key.to_string())),
+        }
+    }
+
+    ///
+    /// SoundLibraryに登録されたBGMをキーで指定して再生するメソッド
+    /// キーがBGMの名前として扱われる。ループ区間が登録されている場合は、イントロの後にループ区間を繰り返す
+    ///
+    pub fn play_bgm_by_name(
+        &mut self,
+        ctx: &mut ggez::Context,
+        library: &SoundLibrary,
+        key: &str,
+        flags: Option<SoundPlayFlags>,
+        crossfade: Clock,
+    ) -> error::Result<SoundHandler> {
+        let flags = flags.unwrap_or_else(|| SoundPlayFlags::new(0, 1.0, true, 1.0));
+        let flags = library.apply_loop_points(key, flags);
+
+        match library.get(key) {
+            Some(data) => self.try_play_bgm(ctx, key, data.clone(), Some(flags), crossfade),
+            None => Err(error::Error::ResourceNotFound(key.to_string())),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::error;
+use crate::sound::SoundData;
+
+///
+/// # ループ区間の位置
+///
+/// Samples: 先頭からのサンプルフレーム数
+/// Millis: 先頭からのミリ秒
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoopPosition {
+    Samples(u64),
+    Millis(u64),
+}
+
+impl LoopPosition {
+    ///
+    /// サンプリングレートからサンプルフレームの位置に変換するメソッド
+    ///
+    pub fn to_frames(&self, sample_rate: u32) -> u64 {
+        match self {
+            LoopPosition::Samples(frames) => *frames,
+            LoopPosition::Millis(millis) => (*millis * sample_rate as u64) / 1000,
+        }
+    }
+}
+
+///
+/// # イントロとループ区間の指定
+///
+/// 先頭からstartまでをイントロとして一度だけ再生し、その後startからendまでを繰り返す
+///
+/// ## フィールド
+/// ### start
+/// ループ区間の開始位置
+///
+/// ### end
+/// ループ区間の終了位置。Noneの場合はデータの末尾
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoopPoints {
+    pub start: LoopPosition,
+    pub end: Option<LoopPosition>,
+}
+
+impl LoopPoints {
+    pub fn new(start: LoopPosition, end: Option<LoopPosition>) -> Self {
+        LoopPoints {
+            start: start,
+            end: end,
+        }
+    }
+
+    ///
+    /// ループ区間をサンプルフレームの範囲で返すメソッド
+    /// 範囲はtotal_framesに収まるように切り詰められる
+    ///
+    pub fn frame_range(&self, sample_rate: u32, total_frames: u64) -> (u64, u64) {
+        let end = self
+            .end
+            .map_or(total_frames, |end| end.to_frames(sample_rate))
+            .min(total_frames);
+        let start = self.start.to_frames(sample_rate).min(end);
+
+        (start, end)
+    }
+
+    ///
+    /// インターリーブされたサンプル列を、イントロとループ区間に分割するメソッド
+    /// ループ区間が空の場合はデータ全体をループ区間とする。LoopedSourceと同じ区間となる
+    ///
+    /// ```
+    /// use torifune::sound::looped::*;
+    ///
+    /// let samples: Vec<i16> = (0..10).collect();
+    /// let points = LoopPoints::new(LoopPosition::Samples(2), Some(LoopPosition::Samples(4)));
+    /// let (intro, body) = points.split_samples(&samples, 2, 44100);
+    /// assert_eq!(intro, vec![0, 1, 2, 3]);
+    /// assert_eq!(body, vec![4, 5, 6, 7]);
+    /// ```
+    ///
+    pub fn split_samples<S: Copy>(
+        &self,
+        samples: &[S],
+        channels: u16,
+        sample_rate: u32,
+    ) -> (Vec<S>, Vec<S>) {
+        let channels = channels.max(1) as usize;
+        let total_frames = (samples.len() / channels) as u64;
+        let (start, end) = self.frame_range(sample_rate, total_frames);
+        let (start, end) = (start as usize * channels, end as usize * channels);
+
+        if start == end {
+            (
+                Vec::new(),
+                samples[..(total_frames as usize * channels)].to_vec(),
+            )
+        } else {
+            (samples[..start].to_vec(), samples[start..end].to_vec())
+        }
+    }
+}
+
+///
+/// # イントロを一度再生した後、ループ区間を繰り返すSource
+///
+/// データは一度だけデコードする。イントロとループ区間の一周目はデコードしながら再生し、
+/// その間にループ区間のサンプルを保持しておき、二周目からはメモリ上のループ区間を繰り返す。
+/// ループ区間の終端でデコードし直さないため、ループの継ぎ目で途切れない
+///
+/// ## フィールド
+/// ### samples
+/// デコード中のサンプル列。ループ区間の終端に達すると破棄される
+///
+/// ### loop_start, loop_end
+/// インターリーブされたサンプル単位でのループ区間。loop_endがNoneの場合はデータの末尾
+///
+/// ### position
+/// samplesから次に読み出すサンプルの位置
+///
+/// ### intro
+/// ループ区間が空だった場合にデータ全体を繰り返すため、ループ区間に入るまで保持するイントロのサンプル
+///
+/// ### body
+/// ループ区間のサンプル
+///
+/// ### replay
+/// メモリ上のループ区間を繰り返している場合、次に返すbodyの位置
+///
+pub struct LoopedSource<I> {
+    samples: Option<I>,
+    channels: u16,
+    sample_rate: u32,
+    loop_start: usize,
+    loop_end: Option<usize>,
+    position: usize,
+    intro: Vec<i16>,
+    body: Vec<i16>,
+    replay: Option<usize>,
+}
+
+impl<I> LoopedSource<I>
+where
+    I: Iterator<Item = i16>,
+{
+    ///
+    /// samplesをデコードしながら再生を始めるLoopedSourceを生成する
+    /// ループ区間が空の場合はデータ全体をループ区間とする
+    ///
+    pub fn new(samples: I, channels: u16, sample_rate: u32, loop_points: &LoopPoints) -> Self {
+        let width = channels.max(1) as usize;
+        let start = loop_points.start.to_frames(sample_rate) as usize * width;
+        let end = loop_points
+            .end
+            .map(|end| end.to_frames(sample_rate) as usize * width);
+
+        let (loop_start, loop_end) = match end {
+            Some(end) if end <= start => (0, None),
+            _ => (start, end),
+        };
+
+        LoopedSource {
+            samples: Some(samples),
+            channels: channels,
+            sample_rate: sample_rate,
+            loop_start: loop_start,
+            loop_end: loop_end,
+            position: 0,
+            intro: Vec::new(),
+            body: Vec::new(),
+            replay: None,
+        }
+    }
+
+    fn decode_next(&mut self) -> Option<i16> {
+        if self.loop_end.map_or(false, |end| self.position >= end) {
+            return None;
+        }
+
+        let sample = self.samples.as_mut()?.next()?;
+        if self.position < self.loop_start {
+            self.intro.push(sample);
+        } else {
+            if !self.intro.is_empty() {
+                self.intro = Vec::new();
+            }
+            self.body.push(sample);
+        }
+        self.position += 1;
+
+        Some(sample)
+    }
+
+    ///
+    /// デコードを終え、メモリ上のループ区間の繰り返しへ移る
+    /// ループ区間が空の場合は、データ全体をループ区間とする
+    ///
+    fn start_replay(&mut self) -> bool {
+        self.samples = None;
+        if self.body.is_empty() {
+            self.body = std::mem::replace(&mut self.intro, Vec::new());
+        }
+
+        if self.body.is_empty() {
+            false
+        } else {
+            self.replay = Some(0);
+            true
+        }
+    }
+}
+
+impl LoopedSource<rodio::Decoder<Cursor<SoundData>>> {
+    ///
+    /// SoundDataをデコードしながら再生するLoopedSourceを生成する
+    ///
+    pub fn from_data(sound_data: SoundData, loop_points: &LoopPoints) -> error::Result<Self> {
+        let decoder = decode(sound_data)?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+
+        Ok(LoopedSource::new(
+            decoder,
+            channels,
+            sample_rate,
+            loop_points,
+        ))
+    }
+}
+
+impl<I> Iterator for LoopedSource<I>
+where
+    I: Iterator<Item = i16>,
+{
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.replay.is_none() {
+            if let Some(sample) = self.decode_next() {
+                return Some(sample);
+            }
+            if !self.start_replay() {
+                return None;
+            }
+        }
+
+        let index = self.replay?;
+        let sample = self.body[index];
+        self.replay = Some((index + 1) % self.body.len());
+        Some(sample)
+    }
+}
+
+impl<I> Source for LoopedSource<I>
+where
+    I: Iterator<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+fn decode(sound_data: SoundData) -> error::Result<rodio::Decoder<Cursor<SoundData>>> {
+    rodio::Decoder::new(Cursor::new(sound_data)).map_err(|e| {
+        error::Error::Ggez(ggez::GameError::AudioError(format!(
+            "failed to decode sound data: {:?}",
+            e
+        )))
+    })
+}
+
+///
+/// # イントロを一度再生した後、ループ区間を繰り返すサウンド
+///
+/// LoopedSourceをSinkに積んで再生する
+///
+pub struct LoopedSound {
+    sink: rodio::Sink,
+    volume: f32,
+}
+
+impl LoopedSound {
+    pub fn from_data(
+        ctx: &mut ggez::Context,
+        sound_data: SoundData,
+        loop_points: &LoopPoints,
+        pitch: f32,
+        fade_in: Duration,
+    ) -> error::Result<Self> {
+        let source = LoopedSource::from_data(sound_data, loop_points)?;
+
+        let sink = rodio::Sink::new(ctx.audio_context.device());
+        sink.pause();
+        sink.append(source.speed(pitch).fade_in(fade_in));
+
+        Ok(LoopedSound {
+            sink: sink,
+            volume: 1.0,
+        })
+    }
+
+    pub fn play(&self) {
+        self.sink.play();
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+    }
+
+    pub fn stop(&mut self) {
+        self.sink.stop();
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.sink.empty()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.sink.set_volume(volume);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::numeric;
+
+///
+/// SpatialSoundの左右の耳の位置。音源を耳の間に置くことでパンを表現する
+///
+pub const SPATIAL_LEFT_EAR: [f32; 3] = [-0.5, 0.0, 0.0];
+pub const SPATIAL_RIGHT_EAR: [f32; 3] = [0.5, 0.0, 0.0];
+
+///
+/// パン(-1.0が左端, 1.0が右端)を表現する音源の位置を返す関数
+/// rodioのSpatialは音源に近い方の耳の音量を小さくするため、左右を反転させて配置する
+///
+pub fn pan_emitter_position(pan: f32) -> [f32; 3] {
+    [-pan * SPATIAL_RIGHT_EAR[0], 0.0, 0.0]
+}
+
+///
+/// # 距離による減衰のカーブ
+///
+/// None: 減衰しない
+/// Linear: min_distanceからmax_distanceまで直線的に減衰する
+/// InverseDistance: min_distance / (min_distance + rolloff * (distance - min_distance)) で減衰する
+/// Exponential: (distance / min_distance)の-rolloff乗で減衰する
+/// Custom: min_distanceからmax_distanceを0.0 ~ 1.0に正規化した値を受け取り、音量の倍率を返す関数
+///
+#[derive(Debug, Clone, Copy)]
+pub enum AttenuationCurve {
+    None,
+    Linear,
+    InverseDistance(f32),
+    Exponential(f32),
+    Custom(fn(f32) -> f32),
+}
+
+///
+/// # 位置を持つサウンドの減衰とパンの設定
+///
+/// ## フィールド
+/// ### min_distance
+/// この距離までは減衰しない
+///
+/// ### max_distance
+/// この距離より遠い場合は無音となる
+///
+/// ### pan_width
+/// 水平方向にこの距離だけ離れると、パンが左右の端となる
+///
+#[derive(Debug, Clone, Copy)]
+pub struct SoundAttenuation {
+    pub curve: AttenuationCurve,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub pan_width: f32,
+}
+
+impl SoundAttenuation {
+    pub fn new(
+        curve: AttenuationCurve,
+        min_distance: f32,
+        max_distance: f32,
+        pan_width: f32,
+    ) -> Self {
+        SoundAttenuation {
+            curve: curve,
+            min_distance: min_distance,
+            max_distance: max_distance,
+            pan_width: pan_width,
+        }
+    }
+
+    ///
+    /// 距離に応じた音量の倍率を返すメソッド
+    ///
+    /// ```
+    /// use torifune::sound::spatial::*;
+    ///
+    /// let attenuation = SoundAttenuation::new(AttenuationCurve::Linear, 100.0, 300.0, 400.0);
+    /// assert_eq!(attenuation.gain(50.0), 1.0);
+    /// assert_eq!(attenuation.gain(200.0), 0.5);
+    /// assert_eq!(attenuation.gain(400.0), 0.0);
+    /// ```
+    ///
+    pub fn gain(&self, distance: f32) -> f32 {
+        if distance <= self.min_distance {
+            return 1.0;
+        }
+
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+
+        let min_distance = self.min_distance.max(std::f32::EPSILON);
+        let range = (self.max_distance - self.min_distance).max(std::f32::EPSILON);
+        let normalized = (distance - self.min_distance) / range;
+
+        let gain = match self.curve {
+            AttenuationCurve::None => 1.0,
+            AttenuationCurve::Linear => 1.0 - normalized,
+            AttenuationCurve::InverseDistance(rolloff) => {
+                min_distance / (min_distance + (rolloff * (distance - self.min_distance)))
+            }
+            AttenuationCurve::Exponential(rolloff) => (distance / min_distance).powf(-rolloff),
+            AttenuationCurve::Custom(f) => f(normalized),
+        };
+
+        gain.max(0.0).min(1.0)
+    }
+
+    ///
+    /// 水平方向の差からパンを計算するメソッド
+    /// -1.0が左端, 1.0が右端となる
+    ///
+    pub fn pan(&self, offset_x: f32) -> f32 {
+        if self.pan_width <= 0.0 {
+            return 0.0;
+        }
+
+        (offset_x / self.pan_width).max(-1.0).min(1.0)
+    }
+
+    ///
+    /// リスナーの位置から見た音源の(音量の倍率, パン)を返すメソッド
+    ///
+    pub fn evaluate(&self, listener: numeric::Point2f, emitter: numeric::Point2f) -> (f32, f32) {
+        let offset = emitter - listener;
+        (self.gain(offset.norm()), self.pan(offset.x))
+    }
+}
+
+impl Default for SoundAttenuation {
+    fn default() -> Self {
+        SoundAttenuation::new(AttenuationCurve::Linear, 64.0, 1024.0, 512.0)
+    }
+}
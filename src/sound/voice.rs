@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use crate::core::Clock;
+use crate::error;
+use crate::sound::*;
+
+///
+/// # 会話のボイスを再生するチャンネル
+///
+/// 新しいボイスを再生すると、前のボイスは停止される。
+/// updateを毎フレーム呼び出すことで、ボイスが終了した時刻を記録する
+///
+/// ## フィールド
+/// ### current
+/// 再生中のボイス
+///
+/// ### finished_at
+/// ボイスが終了した時刻。再生中の場合はNone
+///
+/// ### muted_characters
+/// ボイスを再生しないキャラクターの名前
+///
+pub struct VoiceChannel {
+    current: Option<SoundHandler>,
+    finished_at: Option<Clock>,
+    muted_characters: HashSet<String>,
+    now: Clock,
+}
+
+impl VoiceChannel {
+    pub fn new() -> Self {
+        VoiceChannel {
+            current: None,
+            finished_at: Some(0),
+            muted_characters: HashSet::new(),
+            now: 0,
+        }
+    }
+
+    ///
+    /// ボイスを再生するメソッド
+    /// characterがミュートされている場合は再生せず、Noneを返す
+    ///
+    pub fn play(
+        &mut self,
+        ctx: &mut ggez::Context,
+        manager: &mut SoundManager,
+        sound_data: SoundData,
+        character: Option<&str>,
+        flags: Option<SoundPlayFlags>,
+    ) -> error::Result<Option<SoundHandler>> {
+        self.stop(manager);
+
+        if let Some(character) = character {
+            if self.is_character_muted(character) {
+                return Ok(None);
+            }
+        }
+
+        let flags = flags
+            .unwrap_or_else(|| SoundPlayFlags::new(0, 1.0, false, 1.0))
+            .with_bus(SoundBusId::Voice);
+        let handler = manager.try_play(ctx, sound_data, Some(flags))?;
+
+        if handler.is_some() {
+            self.current = handler;
+            self.finished_at = None;
+        }
+
+        Ok(handler)
+    }
+
+    ///
+    /// play以外の方法で再生したサウンドを、このチャンネルのボイスとして扱うメソッド
+    /// 前のボイスは停止される
+    ///
+    pub fn track(&mut self, manager: &mut SoundManager, handler: SoundHandler) {
+        self.stop(manager);
+        self.current = Some(handler);
+        self.finished_at = None;
+    }
+
+    ///
+    /// 再生中のボイスを停止するメソッド
+    ///
+    pub fn stop(&mut self, manager: &mut SoundManager) {
+        if let Some(handler) = self.current.take() {
+            manager.stop(handler);
+        }
+        self.finished_at = Some(self.now);
+    }
+
+    ///
+    /// ボイスの終了を検出するメソッド
+    ///
+    pub fn update(&mut self, manager: &SoundManager, t: Clock) {
+        let state = self.current.and_then(|handler| manager.get_state(handler));
+        self.advance(t, state);
+    }
+
+    ///
+    /// SoundManagerを参照せずに時刻を進めるメソッド
+    /// stateには再生中のボイスの状態を渡す。回収済みの場合はNone
+    ///
+    pub fn advance(&mut self, t: Clock, state: Option<SoundState>) {
+        self.now = t;
+
+        if self.current.is_some() {
+            match state {
+                Some(SoundState::Playing) | Some(SoundState::Paused) => (),
+                _ => {
+                    self.current = None;
+                    self.finished_at = Some(t);
+                }
+            }
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn get_current(&self) -> Option<SoundHandler> {
+        self.current
+    }
+
+    ///
+    /// ボイスが終了した時刻を返すメソッド。再生中の場合はNoneを返す
+    ///
+    pub fn finished_at(&self) -> Option<Clock> {
+        self.finished_at
+    }
+
+    ///
+    /// オート送りで次のメッセージへ進む時刻を返すメソッド
+    /// テキストの表示が完了した時刻と、ボイスが終了した時刻の遅い方にwaitを加えた時刻となる。
+    /// ボイスが再生中の場合はNoneを返す
+    ///
+    pub fn auto_advance_time(&self, text_revealed_at: Clock, wait: Clock) -> Option<Clock> {
+        self.finished_at
+            .map(|finished_at| text_revealed_at.max(finished_at) + wait)
+    }
+
+    ///
+    /// オート送りで次のメッセージへ進んでよいかを返すメソッド
+    ///
+    pub fn is_ready_to_advance(&self, text_revealed_at: Option<Clock>, wait: Clock) -> bool {
+        match text_revealed_at.and_then(|revealed| self.auto_advance_time(revealed, wait)) {
+            Some(time) => self.now >= time,
+            None => false,
+        }
+    }
+
+    ///
+    /// キャラクターごとにボイスのミュートを設定するメソッド
+    ///
+    pub fn set_character_mute(&mut self, character: &str, muted: bool) {
+        if muted {
+            self.muted_characters.insert(character.to_string());
+        } else {
+            self.muted_characters.remove(character);
+        }
+    }
+
+    pub fn is_character_muted(&self, character: &str) -> bool {
+        self.muted_characters.contains(character)
+    }
+}
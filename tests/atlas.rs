@@ -0,0 +1,206 @@
+extern crate torifune;
+
+use torifune::graphics::atlas::*;
+use torifune::graphics::object::TextureRegion;
+use torifune::numeric;
+
+#[test]
+fn pack_rects_pages_and_padding() {
+    let packed = pack_rects(&[(40, 40), (40, 40), (40, 40)], (64, 64), 2).unwrap();
+    assert_eq!(
+        packed.iter().map(|rect| rect.page).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+
+    let packed = pack_rects(&[(16, 16), (16, 16), (16, 8)], (64, 64), 2).unwrap();
+    assert_eq!((packed[1].x, packed[1].y), (18, 0));
+    assert_eq!((packed[2].x, packed[2].y), (36, 0));
+
+    assert!(pack_rects(&[(128, 8)], (64, 64), 0).is_err());
+}
+
+#[test]
+fn parse_texture_packer_json() {
+    let hash = r#"{
+        "frames": {
+            "button.png": { "frame": { "x": 2, "y": 4, "w": 32, "h": 16 } }
+        },
+        "meta": { "image": "ui.png", "size": { "w": 128, "h": 128 } }
+    }"#;
+    let (image, frames) = parse_atlas_json(hash).unwrap();
+    assert_eq!(image, "ui.png");
+    assert_eq!(frames[0].name, "button.png");
+    assert_eq!((frames[0].rect.x, frames[0].rect.w), (2.0, 32.0));
+    assert_eq!(frames[0].offset, numeric::Vector2f::new(0.0, 0.0));
+    assert_eq!(frames[0].source_size, numeric::Vector2f::new(32.0, 16.0));
+
+    let array = r#"{
+        "frames": [ { "filename": "icon", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } } ],
+        "meta": { "image": "icons.png" }
+    }"#;
+    let (_, frames) = parse_atlas_json(array).unwrap();
+    assert_eq!(frames[0].name, "icon");
+
+    assert!(parse_atlas_json(r#"{ "frames": {} }"#).is_err());
+}
+
+#[test]
+fn parse_trimmed_and_rotated_frames() {
+    let trimmed = r#"{
+        "frames": {
+            "chara": {
+                "frame": { "x": 10, "y": 0, "w": 20, "h": 30 },
+                "rotated": false,
+                "trimmed": true,
+                "spriteSourceSize": { "x": 6, "y": 2, "w": 20, "h": 30 },
+                "sourceSize": { "w": 32, "h": 32 }
+            }
+        },
+        "meta": { "image": "chara.png" }
+    }"#;
+    let (_, frames) = parse_atlas_json(trimmed).unwrap();
+    assert_eq!(frames[0].rect, numeric::Rect::new(10.0, 0.0, 20.0, 30.0));
+    assert_eq!(frames[0].offset, numeric::Vector2f::new(6.0, 2.0));
+    assert_eq!(frames[0].source_size, numeric::Vector2f::new(32.0, 32.0));
+
+    let rotated = r#"{
+        "frames": {
+            "chara": { "frame": { "x": 0, "y": 0, "w": 30, "h": 20 }, "rotated": true }
+        },
+        "meta": { "image": "chara.png" }
+    }"#;
+    match parse_atlas_json(rotated) {
+        Err(torifune::error::Error::InvalidAtlas(message)) => assert!(message.contains("rotated")),
+        _ => panic!("rotated frames must be rejected"),
+    }
+
+    let no_source = r#"{
+        "frames": { "chara": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "trimmed": true } },
+        "meta": { "image": "chara.png" }
+    }"#;
+    assert!(parse_atlas_json(no_source).is_err());
+}
+
+#[test]
+fn crop_is_composed_inside_region() {
+    let region = TextureRegion {
+        crop: numeric::Rect::new(0.25, 0.5, 0.5, 0.25),
+        size: numeric::Vector2f::new(64.0, 32.0),
+        offset: numeric::Vector2f::new(0.0, 0.0),
+        source_size: numeric::Vector2f::new(64.0, 32.0),
+    };
+    let none = numeric::Vector2f::new(0.0, 0.0);
+
+    // 画像全体の切り抜きは、領域そのものになる
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.0, 0.0, 1.0, 1.0)),
+        (region.crop, none)
+    );
+
+    // 領域の左半分のみ
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.0, 0.0, 0.5, 1.0)),
+        (numeric::Rect::new(0.25, 0.5, 0.25, 0.25), none)
+    );
+
+    // 領域の右下四分の一
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.5, 0.5, 0.5, 0.5)),
+        (numeric::Rect::new(0.5, 0.625, 0.25, 0.125), none)
+    );
+}
+
+#[test]
+fn crop_of_trimmed_region_follows_source_image() {
+    // 32x32の元の画像から余白を取り除いた16x16の部分が、(8, 4)の位置にある
+    let region = TextureRegion {
+        crop: numeric::Rect::new(0.0, 0.0, 0.25, 0.25),
+        size: numeric::Vector2f::new(16.0, 16.0),
+        offset: numeric::Vector2f::new(8.0, 4.0),
+        source_size: numeric::Vector2f::new(32.0, 32.0),
+    };
+
+    // 切り抜かない場合は、余白の分ずらして詰められた部分全体を描画する
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.0, 0.0, 1.0, 1.0)),
+        (region.crop, numeric::Vector2f::new(8.0, 4.0))
+    );
+
+    // 元の画像の左半分(x: 0 ~ 16)は、詰められた部分の左半分(x: 8 ~ 16)と重なる
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.0, 0.0, 0.5, 1.0)),
+        (
+            numeric::Rect::new(0.0, 0.0, 0.125, 0.25),
+            numeric::Vector2f::new(8.0, 4.0)
+        )
+    );
+
+    // 元の画像の右半分(x: 16 ~ 32)は、詰められた部分の右半分(x: 16 ~ 24)と重なる
+    assert_eq!(
+        region.resolve_crop(numeric::Rect::new(0.5, 0.0, 0.5, 1.0)),
+        (
+            numeric::Rect::new(0.125, 0.0, 0.125, 0.25),
+            numeric::Vector2f::new(0.0, 4.0)
+        )
+    );
+
+    // 余白のみを切り抜いた場合は何も描画しない
+    let (crop, _) = region.resolve_crop(numeric::Rect::new(0.0, 0.0, 0.125, 1.0));
+    assert_eq!((crop.w, crop.h), (0.0, 0.25));
+}
+
+#[test]
+fn atlas_builder_rejects_oversized_input() {
+    let mut builder = TextureAtlasBuilder::new((64, 64), 0);
+    assert!(builder
+        .add_rgba("huge", u32::max_value(), 2, Vec::new())
+        .is_err());
+    assert!(builder.add_rgba("short", 2, 2, vec![0; 15]).is_err());
+    assert!(builder.add_rgba("ok", 2, 2, vec![0; 16]).is_ok());
+    assert_eq!(builder.layout().unwrap().len(), 1);
+
+    let builder = TextureAtlasBuilder::new((70000, 64), 0);
+    match builder.layout() {
+        Err(torifune::error::Error::InvalidAtlas(_)) => (),
+        _ => panic!("pages larger than u16 must be rejected"),
+    }
+
+    assert!(pack_rects(&[(8, 8)], (u32::max_value(), 8), u32::max_value()).is_ok());
+}
+
+#[test]
+#[ignore]
+fn trimmed_texture_reports_source_size() {
+    use std::rc::Rc;
+    use torifune::graphics::object::TextureObject;
+
+    // ウィンドウが必要なため、`cargo test -- --ignored`で実行する
+    let (mut ctx, _events_loop) = ggez::ContextBuilder::new("torifune_atlas_test", "torifune")
+        .build()
+        .unwrap();
+    let page =
+        Rc::new(ggez::graphics::Image::from_rgba8(&mut ctx, 64, 64, &[0; 64 * 64 * 4]).unwrap());
+
+    let region = AtlasRegion::trimmed(
+        page,
+        numeric::Rect::new(0.0, 0.0, 16.0, 16.0),
+        numeric::Vector2f::new(8.0, 4.0),
+        numeric::Vector2f::new(32.0, 32.0),
+    );
+    let texture = region.to_texture(
+        numeric::Point2f::new(0.0, 0.0),
+        numeric::Vector2f::new(2.0, 2.0),
+        0.0,
+        0,
+    );
+
+    // 配置や当たり判定には、余白を含む元の画像の大きさが使われる
+    assert_eq!(
+        texture.get_texture_size(&mut ctx),
+        numeric::Vector2f::new(32.0, 32.0)
+    );
+    assert_eq!(
+        texture.get_drawing_size(&mut ctx),
+        numeric::Vector2f::new(64.0, 64.0)
+    );
+}
@@ -0,0 +1,79 @@
+extern crate torifune;
+
+use torifune::graphics::camera::*;
+use torifune::numeric;
+
+fn approx(a: numeric::Point2f, b: numeric::Point2f) -> bool {
+    (a.x - b.x).abs() < 0.001 && (a.y - b.y).abs() < 0.001
+}
+
+#[test]
+fn camera_coordinate_conversion() {
+    let mut camera = Camera::new(numeric::Vector2f::new(640.0, 480.0));
+    camera.set_position(numeric::Point2f::new(1000.0, 1000.0));
+    camera.set_zoom(2.0);
+
+    assert!(approx(
+        camera.world_to_screen(numeric::Point2f::new(1010.0, 990.0)),
+        numeric::Point2f::new(340.0, 220.0)
+    ));
+
+    camera.set_rotation(0.5);
+    let point = numeric::Point2f::new(123.0, 456.0);
+    assert!(approx(
+        camera.screen_to_world(camera.world_to_screen(point)),
+        point
+    ));
+}
+
+#[test]
+fn camera_follow_with_deadzone_and_bounds() {
+    let mut camera = Camera::new(numeric::Vector2f::new(200.0, 100.0));
+    camera.set_position(numeric::Point2f::new(500.0, 500.0));
+    camera.set_deadzone(numeric::Vector2f::new(40.0, 40.0));
+
+    camera.follow(Some(numeric::Point2f::new(510.0, 500.0)));
+    camera.advance(1);
+    assert_eq!(camera.get_position(), numeric::Point2f::new(500.0, 500.0));
+
+    camera.follow(Some(numeric::Point2f::new(600.0, 500.0)));
+    camera.advance(2);
+    assert_eq!(camera.get_position(), numeric::Point2f::new(580.0, 500.0));
+
+    camera.set_bounds(Some(numeric::Rect::new(0.0, 0.0, 640.0, 80.0)));
+    assert_eq!(camera.get_position(), numeric::Point2f::new(540.0, 40.0));
+}
+
+#[test]
+fn camera_shake_is_deterministic() {
+    let mut a = Camera::new(numeric::Vector2f::new(100.0, 100.0));
+    let mut b = Camera::new(numeric::Vector2f::new(100.0, 100.0));
+    a.shake(8.0, 30, 0.2);
+    b.shake(8.0, 30, 0.2);
+
+    a.advance(7);
+    b.advance(7);
+    assert_eq!(a.visible_area(), b.visible_area());
+    assert!(a.is_shaking());
+
+    a.advance(30);
+    assert!(!a.is_shaking());
+    assert_eq!(a.visible_area(), numeric::Rect::new(0.0, 0.0, 100.0, 100.0));
+}
+
+#[test]
+fn camera_matrix_matches_world_to_screen() {
+    let mut camera = Camera::new(numeric::Vector2f::new(640.0, 480.0));
+    camera.set_position(numeric::Point2f::new(300.0, 200.0));
+    camera.set_zoom(1.5);
+    camera.set_rotation(0.7);
+
+    let point = numeric::Point2f::new(350.0, 120.0);
+    let transformed = camera
+        .to_matrix()
+        .transform_point(&numeric::Point3f::new(point.x, point.y, 0.0));
+    assert!(approx(
+        numeric::Point2f::new(transformed.x, transformed.y),
+        camera.world_to_screen(point)
+    ));
+}
@@ -0,0 +1,576 @@
+extern crate torifune;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ggez::input::mouse::MouseButton;
+use torifune::device::action::*;
+use torifune::device::command::*;
+use torifune::device::focus::*;
+use torifune::device::*;
+use torifune::error::{Error, ErrorPolicy, HandlerErrors, MAX_HANDLER_ERRORS};
+use torifune::graphics::drawable::*;
+use torifune::numeric;
+
+fn recording_handler(log: &Rc<RefCell<Vec<String>>>, msg: &'static str) -> InputEventHandler {
+    let log = log.clone();
+    Box::new(move |_input: &dyn InputSource, t| {
+        log.borrow_mut().push(format!("{}@{}", msg, t));
+        Ok(())
+    })
+}
+
+#[test]
+fn keyboard_first_pressed_and_typed() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut key = KeyboardListener::new_masked(
+        vec![KeyInputDevice::GenericKeyboard],
+        vec![VirtualKey::Action1],
+    );
+
+    key.register_event_handler(
+        VirtualKey::Action1,
+        KeyboardEvent::FirstPressed,
+        recording_handler(&log, "first"),
+    );
+    key.register_event_handler(
+        VirtualKey::Action1,
+        KeyboardEvent::KeepPressed,
+        recording_handler(&log, "keep"),
+    );
+    key.register_event_handler(
+        VirtualKey::Action1,
+        KeyboardEvent::Typed,
+        recording_handler(&log, "typed"),
+    );
+
+    key.update_with_input(&input, 0).unwrap();
+    input.press_key(VirtualKey::Action1);
+    key.update_with_input(&input, 1).unwrap();
+    key.update_with_input(&input, 2).unwrap();
+    input.release_key(VirtualKey::Action1);
+    key.update_with_input(&input, 3).unwrap();
+    key.update_with_input(&input, 4).unwrap();
+
+    assert_eq!(*log.borrow(), vec!["first@1", "keep@2", "typed@3"]);
+}
+
+#[test]
+fn keyboard_ignores_unlistened_keys() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut key = KeyboardListener::new_masked(
+        vec![KeyInputDevice::GenericKeyboard],
+        vec![VirtualKey::Action1],
+    );
+
+    key.register_event_handler(
+        VirtualKey::Action2,
+        KeyboardEvent::FirstPressed,
+        recording_handler(&log, "first"),
+    );
+
+    input.press_key(VirtualKey::Action2);
+    key.update_with_input(&input, 0).unwrap();
+
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn keyboard_last_events_skip_keep_released() {
+    let mut input = ScriptedInputSource::new();
+    let mut key = KeyboardListener::new_masked(
+        vec![KeyInputDevice::GenericKeyboard],
+        vec![VirtualKey::Action1, VirtualKey::Action2],
+    );
+
+    key.update_with_input(&input, 0).unwrap();
+    assert!(key.last_events().is_empty());
+
+    input.press_key(VirtualKey::Action2);
+    key.update_with_input(&input, 1).unwrap();
+    assert_eq!(
+        key.last_events(),
+        &[(VirtualKey::Action2, KeyboardEvent::FirstPressed)]
+    );
+
+    input.release_key(VirtualKey::Action2);
+    key.update_with_input(&input, 2).unwrap();
+    assert_eq!(
+        key.last_events(),
+        &[(VirtualKey::Action2, KeyboardEvent::Typed)]
+    );
+}
+
+#[test]
+fn focus_spatial_neighbour_prefers_aligned_widget() {
+    let from = numeric::Rect::new(100.0, 100.0, 50.0, 50.0);
+    let candidates = vec![
+        (1, numeric::Rect::new(200.0, 220.0, 50.0, 50.0)),
+        (2, numeric::Rect::new(260.0, 100.0, 50.0, 50.0)),
+        (3, numeric::Rect::new(0.0, 100.0, 50.0, 50.0)),
+    ];
+
+    assert_eq!(
+        find_spatial_neighbour(from, &candidates, FocusDirection::Right),
+        Some(2)
+    );
+    assert_eq!(
+        find_spatial_neighbour(from, &candidates, FocusDirection::Left),
+        Some(3)
+    );
+    assert_eq!(
+        find_spatial_neighbour(from, &candidates, FocusDirection::Down),
+        Some(1)
+    );
+    assert_eq!(
+        find_spatial_neighbour(from, &candidates, FocusDirection::Up),
+        None
+    );
+}
+
+struct FocusDummy {
+    visible: bool,
+    enabled: bool,
+}
+
+impl DrawableComponent for FocusDummy {
+    fn draw(&mut self, _ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    fn appear(&mut self) {
+        self.visible = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_drawing_depth(&mut self, _depth: i8) {}
+
+    fn get_drawing_depth(&self) -> i8 {
+        0
+    }
+}
+
+impl Focusable for FocusDummy {
+    fn focus_area(&self, _ctx: &mut ggez::Context) -> numeric::Rect {
+        numeric::Rect::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    fn is_focusable(&self) -> bool {
+        self.visible && self.enabled
+    }
+}
+
+#[test]
+fn focus_first_skips_hidden_and_disabled() {
+    let mut focus = FocusManager::new();
+    focus.register(Rc::new(RefCell::new(FocusDummy {
+        visible: false,
+        enabled: true,
+    })));
+    focus.register(Rc::new(RefCell::new(FocusDummy {
+        visible: true,
+        enabled: false,
+    })));
+    let third = focus.register(Rc::new(RefCell::new(FocusDummy {
+        visible: true,
+        enabled: true,
+    })));
+
+    assert_eq!(focus.first_focusable(), Some(third));
+    assert_eq!(
+        focus.navigation_direction(VirtualKey::Left),
+        Some(FocusDirection::Left)
+    );
+    assert_eq!(focus.navigation_direction(VirtualKey::Action1), None);
+}
+
+#[test]
+fn mouse_pressed_dragged_clicked() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut mouse = MouseListener::new();
+
+    mouse.register_event_handler(
+        MouseButton::Left,
+        MouseButtonEvent::Pressed,
+        recording_handler(&log, "pressed"),
+    );
+    mouse.register_event_handler(
+        MouseButton::Left,
+        MouseButtonEvent::Dragged,
+        recording_handler(&log, "dragged"),
+    );
+    mouse.register_event_handler(
+        MouseButton::Left,
+        MouseButtonEvent::Clicked,
+        recording_handler(&log, "clicked"),
+    );
+
+    input.set_mouse_position(numeric::Point2f::new(10.0, 20.0));
+    input.press_button(MouseButton::Left);
+    mouse.update_with_input(&input, 0).unwrap();
+    mouse.update_with_input(&input, 1).unwrap();
+    input.set_mouse_position(numeric::Point2f::new(30.0, 40.0));
+    input.release_button(MouseButton::Left);
+    mouse.update_with_input(&input, 2).unwrap();
+
+    assert_eq!(*log.borrow(), vec!["pressed@0", "dragged@1", "clicked@2"]);
+    assert_eq!(
+        mouse.get_last_clicked(MouseButton::Left),
+        numeric::Point2f::new(30.0, 40.0)
+    );
+}
+
+#[test]
+fn mouse_double_click_within_interval() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut mouse = MouseListener::new();
+    mouse.set_double_click_interval(10);
+
+    mouse.register_event_handler(
+        MouseButton::Left,
+        MouseButtonEvent::DoubleClicked,
+        recording_handler(&log, "double"),
+    );
+
+    for t in &[0, 5, 30, 50] {
+        input.press_button(MouseButton::Left);
+        mouse.update_with_input(&input, *t).unwrap();
+        input.release_button(MouseButton::Left);
+        mouse.update_with_input(&input, *t + 1).unwrap();
+    }
+
+    assert_eq!(*log.borrow(), vec!["double@6"]);
+}
+
+#[test]
+fn mouse_drag_start_and_end_with_threshold() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut mouse = MouseListener::new();
+    mouse.set_drag_threshold(5.0);
+
+    for (event, msg) in &[
+        (MouseButtonEvent::DragStart, "start"),
+        (MouseButtonEvent::DragEnd, "end"),
+        (MouseButtonEvent::Clicked, "clicked"),
+    ] {
+        mouse.register_event_handler(MouseButton::Left, *event, recording_handler(&log, msg));
+    }
+
+    input.press_button(MouseButton::Left);
+    mouse.update_with_input(&input, 0).unwrap();
+    input.set_mouse_position(numeric::Point2f::new(3.0, 0.0));
+    mouse.update_with_input(&input, 1).unwrap();
+    assert_eq!(
+        mouse.get_drag_origin(MouseButton::Left),
+        Some(numeric::Point2f::new(0.0, 0.0))
+    );
+    input.set_mouse_position(numeric::Point2f::new(6.0, 0.0));
+    mouse.update_with_input(&input, 2).unwrap();
+    assert!(mouse.is_dragging(MouseButton::Left));
+    input.release_button(MouseButton::Left);
+    mouse.update_with_input(&input, 3).unwrap();
+
+    assert_eq!(*log.borrow(), vec!["start@2", "end@3"]);
+    assert_eq!(mouse.get_drag_origin(MouseButton::Left), None);
+    assert_eq!(
+        mouse.get_last_drag_origin(MouseButton::Left),
+        Some(numeric::Point2f::new(0.0, 0.0))
+    );
+}
+
+#[test]
+fn mouse_click_with_non_monotonic_clock() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut mouse = MouseListener::new();
+    mouse.register_event_handler(
+        MouseButton::Left,
+        MouseButtonEvent::DoubleClicked,
+        recording_handler(&log, "double"),
+    );
+
+    // 時刻が巻き戻ってもパニックせず、巻き戻った後のクリックはダブルクリックとしない
+    for t in &[10, 2, 4] {
+        input.press_button(MouseButton::Left);
+        mouse.update_with_input(&input, *t).unwrap();
+        input.release_button(MouseButton::Left);
+        mouse.update_with_input(&input, *t).unwrap();
+    }
+
+    assert_eq!(*log.borrow(), vec!["double@4"]);
+}
+
+#[test]
+fn mouse_wheel_motion_and_extra_button() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut input = ScriptedInputSource::new();
+    let mut mouse = MouseListener::new();
+
+    let wheel_log = log.clone();
+    mouse.register_wheel_handler(Box::new(move |_input: &dyn InputSource, delta, t| {
+        wheel_log
+            .borrow_mut()
+            .push(format!("wheel {} {}@{}", delta.x, delta.y, t));
+        Ok(())
+    }));
+    let motion_log = log.clone();
+    mouse.register_motion_handler(Box::new(move |_input: &dyn InputSource, delta, t| {
+        motion_log
+            .borrow_mut()
+            .push(format!("motion {} {}@{}", delta.x, delta.y, t));
+        Ok(())
+    }));
+    mouse.register_event_handler(
+        MouseButton::Other(4),
+        MouseButtonEvent::Pressed,
+        recording_handler(&log, "other"),
+    );
+
+    mouse.update_with_input(&input, 0).unwrap();
+    mouse.notify_wheel(0.0, 1.0);
+    mouse.notify_wheel(0.0, 2.0);
+    input.set_mouse_position(numeric::Point2f::new(2.0, 1.0));
+    input.press_button(MouseButton::Other(4));
+    mouse.update_with_input(&input, 1).unwrap();
+    mouse.update_with_input(&input, 2).unwrap();
+
+    assert_eq!(
+        *log.borrow(),
+        vec!["motion 2 1@1", "wheel 0 3@1", "other@1"]
+    );
+}
+
+#[test]
+fn command_sequence_within_window() {
+    let mut detector = CommandDetector::new();
+    let hadouken = detector.register_command(vec![
+        CommandStep::key(VirtualKey::Down, 0),
+        CommandStep::new(vec![VirtualKey::Down, VirtualKey::Right], 10),
+        CommandStep::new(vec![VirtualKey::Right, VirtualKey::Action1], 10),
+    ]);
+
+    assert!(detector
+        .feed(VirtualKey::Down, KeyboardEvent::FirstPressed, 0)
+        .is_empty());
+    detector.feed(VirtualKey::Right, KeyboardEvent::FirstPressed, 4);
+    detector.feed(VirtualKey::Down, KeyboardEvent::Typed, 8);
+    assert_eq!(
+        detector.feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 12),
+        vec![hadouken]
+    );
+
+    // 一度成立した入力では再び成立しない
+    detector.feed(VirtualKey::Action1, KeyboardEvent::Typed, 14);
+    assert!(detector
+        .feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 15)
+        .is_empty());
+}
+
+#[test]
+fn command_sequence_too_slow_or_interrupted() {
+    let mut detector = CommandDetector::new();
+    detector.register_command(vec![
+        CommandStep::key(VirtualKey::Down, 0),
+        CommandStep::key(VirtualKey::Action1, 10),
+    ]);
+
+    detector.feed(VirtualKey::Down, KeyboardEvent::FirstPressed, 0);
+    assert!(detector
+        .feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 11)
+        .is_empty());
+
+    detector.reset();
+    detector.feed(VirtualKey::Down, KeyboardEvent::FirstPressed, 20);
+    detector.feed(VirtualKey::Down, KeyboardEvent::Typed, 21);
+    detector.feed(VirtualKey::Up, KeyboardEvent::FirstPressed, 22);
+    assert!(detector
+        .feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 23)
+        .is_empty());
+}
+
+#[test]
+fn command_sequence_with_non_monotonic_clock() {
+    let mut detector = CommandDetector::new();
+    let command = detector.register_command(vec![
+        CommandStep::key(VirtualKey::Down, 0),
+        CommandStep::key(VirtualKey::Action1, 10),
+    ]);
+
+    // 時刻が巻き戻ってもパニックしない
+    detector.feed(VirtualKey::Down, KeyboardEvent::FirstPressed, 100);
+    detector.feed(VirtualKey::Down, KeyboardEvent::Typed, 101);
+    assert_eq!(
+        detector.feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 3),
+        vec![command]
+    );
+}
+
+#[test]
+fn command_chord_fires_handler() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut detector = CommandDetector::new();
+    let chord = detector.register_command(vec![CommandStep::new(
+        vec![VirtualKey::Mod1, VirtualKey::Action2],
+        0,
+    )]);
+    let cloned = log.clone();
+    detector.register_event_handler(
+        chord,
+        Box::new(move |t| {
+            cloned.borrow_mut().push(t);
+            Ok(())
+        }),
+    );
+
+    detector.feed(VirtualKey::Action2, KeyboardEvent::FirstPressed, 3);
+    assert_eq!(
+        detector.feed(VirtualKey::Mod1, KeyboardEvent::FirstPressed, 5),
+        vec![chord]
+    );
+    assert_eq!(*log.borrow(), vec![5]);
+}
+
+#[test]
+fn action_map_top_context_blocks_lower_contexts() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut actions = ActionMap::new();
+
+    let mut field = InputContext::new("field", ContextMode::Blocking);
+    field.bind("confirm", VirtualKey::Action1);
+    field.bind("menu", VirtualKey::Action2);
+    let cloned = log.clone();
+    field.register_action_handler(
+        "confirm",
+        KeyboardEvent::FirstPressed,
+        Box::new(move |t| {
+            cloned.borrow_mut().push(format!("field@{}", t));
+            Ok(())
+        }),
+    );
+    actions.push_context(field);
+
+    let mut menu = InputContext::new("menu", ContextMode::Blocking);
+    menu.bind("confirm", VirtualKey::Action1);
+    menu.bind("confirm", VirtualKey::Action3);
+    let cloned = log.clone();
+    menu.register_action_handler(
+        "confirm",
+        KeyboardEvent::FirstPressed,
+        Box::new(move |t| {
+            cloned.borrow_mut().push(format!("menu@{}", t));
+            Ok(())
+        }),
+    );
+    actions.push_context(menu);
+
+    actions.feed(VirtualKey::Action3, KeyboardEvent::FirstPressed, 1);
+    assert!(actions.is_action_pressed("confirm"));
+    assert!(actions
+        .feed(VirtualKey::Action2, KeyboardEvent::FirstPressed, 2)
+        .is_empty());
+    assert!(!actions.is_action_pressed("menu"));
+
+    actions.pop_context();
+    actions.feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 3);
+    assert!(actions.is_action_pressed("menu"));
+
+    assert_eq!(*log.borrow(), vec!["menu@1", "field@3"]);
+}
+
+#[test]
+fn action_map_pass_through_context() {
+    let mut actions = ActionMap::new();
+
+    let mut field = InputContext::new("field", ContextMode::Blocking);
+    field.bind("move_up", VirtualKey::Up);
+    actions.push_context(field);
+
+    let mut dialogue = InputContext::new("dialogue", ContextMode::PassThrough);
+    dialogue.bind("next", VirtualKey::Action1);
+    actions.push_context(dialogue);
+
+    assert_eq!(
+        actions.feed(VirtualKey::Up, KeyboardEvent::FirstPressed, 0),
+        vec!["move_up".to_string()]
+    );
+    assert_eq!(
+        actions.feed(VirtualKey::Action1, KeyboardEvent::FirstPressed, 0),
+        vec!["next".to_string()]
+    );
+
+    actions
+        .get_context_mut("dialogue")
+        .unwrap()
+        .set_mode(ContextMode::Blocking);
+    assert!(actions
+        .feed(VirtualKey::Up, KeyboardEvent::Typed, 1)
+        .is_empty());
+}
+
+#[test]
+fn handler_errors_follow_error_policy() {
+    let mut input = ScriptedInputSource::new();
+    let mut key = KeyboardListener::new_masked(
+        vec![KeyInputDevice::GenericKeyboard],
+        vec![VirtualKey::Action1],
+    );
+    key.register_event_handler(
+        VirtualKey::Action1,
+        KeyboardEvent::FirstPressed,
+        Box::new(|_input: &dyn InputSource, t| Err(format!("failed@{}", t))),
+    );
+
+    // 既定ではエラーは出力されるのみで、蓄積されない
+    input.press_key(VirtualKey::Action1);
+    assert!(key.update_with_input(&input, 0).is_ok());
+    assert!(key.take_error().is_none());
+    input.release_key(VirtualKey::Action1);
+    key.update_with_input(&input, 0).unwrap();
+
+    key.set_error_policy(ErrorPolicy::Propagate);
+    input.press_key(VirtualKey::Action1);
+    match key.update_with_input(&input, 1) {
+        Err(Error::EventHandler(messages)) => assert_eq!(messages, vec!["failed@1"]),
+        _ => panic!("handler error was not propagated"),
+    }
+
+    key.set_error_policy(ErrorPolicy::Log);
+    input.release_key(VirtualKey::Action1);
+    key.update_with_input(&input, 2).unwrap();
+    input.press_key(VirtualKey::Action1);
+    assert!(key.update_with_input(&input, 3).is_ok());
+    assert!(key.take_error().is_none());
+}
+
+#[test]
+fn propagated_handler_errors_keep_latest() {
+    let mut errors = HandlerErrors::new(ErrorPolicy::Propagate);
+    for i in 0..(MAX_HANDLER_ERRORS + 3) {
+        errors.handle(Err(format!("failed@{}", i)));
+    }
+
+    match errors.take() {
+        Some(Error::EventHandler(messages)) => {
+            assert_eq!(messages.len(), MAX_HANDLER_ERRORS);
+            assert_eq!(messages[0], "failed@3");
+            assert_eq!(
+                messages.last().unwrap(),
+                &format!("failed@{}", MAX_HANDLER_ERRORS + 2)
+            );
+        }
+        _ => panic!("handler errors were not kept"),
+    }
+    assert!(errors.take().is_none());
+}
@@ -0,0 +1,82 @@
+extern crate torifune;
+
+use torifune::graphics::draw_layer::*;
+use torifune::graphics::drawable::*;
+
+struct Dummy {
+    depth: i8,
+    visible: bool,
+}
+
+impl Dummy {
+    fn boxed(depth: i8) -> Box<dyn DrawableComponent> {
+        Box::new(Dummy {
+            depth: depth,
+            visible: true,
+        })
+    }
+}
+
+impl DrawableComponent for Dummy {
+    fn draw(&mut self, _ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    fn appear(&mut self) {
+        self.visible = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.depth = depth;
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        self.depth
+    }
+}
+
+#[test]
+fn draw_layer_keeps_stable_depth_order() {
+    let mut list = DrawLayerList::new(0);
+    let a = list.add(Dummy::boxed(0));
+    let b = list.add(Dummy::boxed(10));
+    let c = list.add(Dummy::boxed(0));
+    let d = list.add(Dummy::boxed(-5));
+    assert_eq!(list.handlers(), vec![b, a, c, d]);
+
+    list.set_object_depth(d, 10);
+    assert_eq!(list.handlers(), vec![b, d, a, c]);
+
+    list.get_mut(b).unwrap().set_drawing_depth(-1);
+    list.refresh_order();
+    assert_eq!(list.handlers(), vec![d, a, c, b]);
+
+    assert!(list.remove(a).is_some());
+    assert!(list.remove(a).is_none());
+    assert_eq!(list.handlers(), vec![d, c, b]);
+}
+
+#[test]
+fn draw_layer_tags() {
+    let mut list = DrawLayerList::new(0);
+    let window = list.add_with_tags(Dummy::boxed(0), &["ui", "window"]);
+    let field = list.add(Dummy::boxed(5));
+    let button = list.add_with_tags(Dummy::boxed(-1), &["ui"]);
+
+    assert_eq!(list.handlers_with_tag("ui"), vec![window, button]);
+
+    list.set_visible_with_tag("ui", false);
+    assert!(!list.get(button).unwrap().is_visible());
+    assert!(list.get(field).unwrap().is_visible());
+
+    assert_eq!(list.remove_with_tag("ui"), 2);
+    assert_eq!(list.handlers(), vec![field]);
+}
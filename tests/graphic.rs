@@ -3,7 +3,6 @@ use ggez::input::mouse::MouseButton;
 use ggez::*;
 use std::env;
 use std::path;
-use torifune::core::Clock;
 use torifune::core::Updatable;
 use torifune::device;
 use torifune::graphics::object as tobj;
@@ -20,20 +19,16 @@ struct State {
     image: tobj::SimpleObject,
 }
 
-fn sample_mouse_closure(
-    msg: &'static str,
-) -> Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>> {
-    Box::new(move |ctx: &Context, _t| {
-        let p = device::MouseListener::get_position(ctx);
+fn sample_mouse_closure(msg: &'static str) -> device::InputEventHandler {
+    Box::new(move |input: &dyn device::InputSource, _t| {
+        let p = input.mouse_position();
         println!("{}: {}, {}", msg, p.x, p.y);
         Ok(())
     })
 }
 
-fn sample_keyboard_closure(
-    msg: &'static str,
-) -> Box<dyn Fn(&ggez::Context, Clock) -> Result<(), String>> {
-    Box::new(move |_ctx: &Context, _t| {
+fn sample_keyboard_closure(msg: &'static str) -> device::InputEventHandler {
+    Box::new(move |_input: &dyn device::InputSource, _t| {
         println!("key event ====> {}", msg);
         Ok(())
     })
@@ -105,7 +100,7 @@ impl State {
         self.mouse.register_event_handler(
             MouseButton::Left,
             device::MouseButtonEvent::Dragged,
-            Box::new(|_ctx: &Context, _t| {
+            Box::new(|_input: &dyn device::InputSource, _t| {
                 println!("Dragging!!");
                 Ok(())
             }),
@@ -120,7 +115,7 @@ impl State {
         self.key.register_event_handler(
             device::VirtualKey::Action2,
             device::KeyboardEvent::FirstPressed,
-            Box::new(move |_ctx: &Context, _t| Ok(())),
+            Box::new(move |_input: &dyn device::InputSource, _t| Ok(())),
         );
     }
 }
@@ -0,0 +1,185 @@
+extern crate torifune;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ggez::input::mouse::MouseButton;
+use torifune::device::mouse_dispatch::*;
+use torifune::graphics::drawable::*;
+use torifune::numeric;
+
+struct Dummy {
+    area: numeric::Rect,
+    depth: i8,
+    visible: bool,
+    children: Option<MouseEventDispatcher>,
+}
+
+impl Dummy {
+    fn new(area: numeric::Rect, depth: i8) -> Rc<RefCell<Dummy>> {
+        Rc::new(RefCell::new(Dummy {
+            area: area,
+            depth: depth,
+            visible: true,
+            children: None,
+        }))
+    }
+
+    fn container(
+        area: numeric::Rect,
+        depth: i8,
+        children: MouseEventDispatcher,
+    ) -> Rc<RefCell<Dummy>> {
+        Rc::new(RefCell::new(Dummy {
+            area: area,
+            depth: depth,
+            visible: true,
+            children: Some(children),
+        }))
+    }
+}
+
+impl DrawableComponent for Dummy {
+    fn draw(&mut self, _ctx: &mut ggez::Context) -> ggez::GameResult<()> {
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    fn appear(&mut self) {
+        self.visible = true;
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_drawing_depth(&mut self, depth: i8) {
+        self.depth = depth;
+    }
+
+    fn get_drawing_depth(&self) -> i8 {
+        self.depth
+    }
+}
+
+impl MouseEventTarget for Dummy {
+    fn hit_test(&self, _ctx: &mut ggez::Context, point: numeric::Point2f) -> bool {
+        self.area.contains(point)
+    }
+
+    fn hit_area(&self) -> Option<numeric::Rect> {
+        Some(self.area)
+    }
+
+    fn child_point(&self, point: numeric::Point2f) -> numeric::Point2f {
+        numeric::Point2f::new(point.x - self.area.x, point.y - self.area.y)
+    }
+
+    fn child_dispatcher(&mut self) -> Option<&mut MouseEventDispatcher> {
+        self.children.as_mut()
+    }
+}
+
+#[test]
+fn hit_test_order_follows_depth_then_registration() {
+    let mut dispatcher = MouseEventDispatcher::new();
+    let back = dispatcher.register(Dummy::new(numeric::Rect::new(0.0, 0.0, 100.0, 100.0), 10));
+    let front = dispatcher.register(Dummy::new(numeric::Rect::new(0.0, 0.0, 50.0, 50.0), 0));
+    let later = dispatcher.register(Dummy::new(numeric::Rect::new(0.0, 0.0, 50.0, 50.0), 10));
+
+    let point = numeric::Point2f::new(10.0, 10.0);
+    assert_eq!(dispatcher.hit_handlers(point), vec![front, later, back]);
+    assert_eq!(
+        dispatcher.hit_handlers(numeric::Point2f::new(80.0, 80.0)),
+        vec![back]
+    );
+
+    let hidden = Dummy::new(numeric::Rect::new(0.0, 0.0, 50.0, 50.0), -1);
+    hidden.borrow_mut().hide();
+    dispatcher.register(hidden);
+    assert_eq!(dispatcher.hit_handlers(point), vec![front, later, back]);
+}
+
+#[test]
+fn hover_enter_and_leave_transitions() {
+    let mut dispatcher = MouseEventDispatcher::new();
+    let a = dispatcher.register(Dummy::new(numeric::Rect::new(0.0, 0.0, 10.0, 10.0), 0));
+    let b = dispatcher.register(Dummy::new(numeric::Rect::new(20.0, 0.0, 10.0, 10.0), 0));
+
+    assert_eq!(dispatcher.transition_hover(Some(a)), Some((None, Some(a))));
+    assert_eq!(dispatcher.get_hovered(), Some(a));
+    assert_eq!(dispatcher.transition_hover(Some(a)), None);
+    assert_eq!(
+        dispatcher.transition_hover(Some(b)),
+        Some((Some(a), Some(b)))
+    );
+    assert_eq!(dispatcher.transition_hover(None), Some((Some(b), None)));
+
+    dispatcher.transition_hover(Some(a));
+    dispatcher.unregister(a);
+    assert_eq!(dispatcher.get_hovered(), None);
+}
+
+#[test]
+fn clicked_requires_release_over_pressed_target() {
+    let mut dispatcher = MouseEventDispatcher::new();
+    let a = dispatcher.register(Dummy::new(numeric::Rect::new(0.0, 0.0, 10.0, 10.0), 0));
+    let b = dispatcher.register(Dummy::new(numeric::Rect::new(20.0, 0.0, 10.0, 10.0), 0));
+
+    // 押した対象の上で離した場合のみClickedの配送先となる
+    dispatcher.record_press(MouseButton::Left, Some(a));
+    let hits = dispatcher.hit_handlers(numeric::Point2f::new(5.0, 5.0));
+    assert_eq!(
+        dispatcher.take_click_target(MouseButton::Left, &hits),
+        Some(a)
+    );
+    assert_eq!(dispatcher.get_press_target(MouseButton::Left), None);
+
+    // 別の対象の上で離した場合はどちらにも配送しない
+    dispatcher.record_press(MouseButton::Left, Some(a));
+    let hits = dispatcher.hit_handlers(numeric::Point2f::new(25.0, 5.0));
+    assert_eq!(hits, vec![b]);
+    assert_eq!(dispatcher.take_click_target(MouseButton::Left, &hits), None);
+
+    // ボタンごとに独立して記録される
+    dispatcher.record_press(MouseButton::Right, Some(b));
+    let hits = dispatcher.hit_handlers(numeric::Point2f::new(25.0, 5.0));
+    assert_eq!(dispatcher.take_click_target(MouseButton::Left, &hits), None);
+    assert_eq!(
+        dispatcher.take_click_target(MouseButton::Right, &hits),
+        Some(b)
+    );
+
+    // 押した対象が登録解除された場合は配送しない
+    dispatcher.record_press(MouseButton::Left, Some(a));
+    dispatcher.unregister(a);
+    assert_eq!(dispatcher.take_click_target(MouseButton::Left, &[a]), None);
+}
+
+#[test]
+fn nested_dispatcher_receives_relative_point() {
+    let mut children = MouseEventDispatcher::new();
+    let button = children.register(Dummy::new(numeric::Rect::new(10.0, 10.0, 20.0, 20.0), 0));
+
+    let mut dispatcher = MouseEventDispatcher::new();
+    let screen = dispatcher.register(Dummy::container(
+        numeric::Rect::new(100.0, 100.0, 200.0, 200.0),
+        0,
+        children,
+    ));
+
+    assert_eq!(
+        dispatcher.hit_path(numeric::Point2f::new(115.0, 115.0)),
+        vec![screen, button]
+    );
+    assert_eq!(
+        dispatcher.hit_path(numeric::Point2f::new(150.0, 150.0)),
+        vec![screen]
+    );
+    assert!(dispatcher
+        .hit_path(numeric::Point2f::new(15.0, 15.0))
+        .is_empty());
+}
@@ -0,0 +1,85 @@
+extern crate torifune;
+
+use ggez::graphics as ggraphics;
+use torifune::graphics::object::nine_slice::*;
+use torifune::numeric::{Rect, Vector2f};
+
+const TEXTURE: (f32, f32) = (24.0, 24.0);
+
+fn slice(
+    target: Vector2f,
+    edge_mode: NineSliceMode,
+    center_mode: NineSliceMode,
+) -> Vec<NineSlicePiece> {
+    compute_nine_slice(
+        Rect::new(0.0, 0.0, TEXTURE.0, TEXTURE.1),
+        Vector2f::new(TEXTURE.0, TEXTURE.1),
+        NineSliceInsets::uniform(8.0),
+        target,
+        edge_mode,
+        center_mode,
+    )
+}
+
+#[test]
+fn stretch_keeps_corners() {
+    let pieces = slice(
+        Vector2f::new(64.0, 40.0),
+        NineSliceMode::Stretch,
+        NineSliceMode::Stretch,
+    );
+
+    assert_eq!(pieces.len(), 9);
+    assert_eq!(pieces[0].dest, Rect::new(0.0, 0.0, 8.0, 8.0));
+    assert_eq!(pieces[8].dest, Rect::new(56.0, 32.0, 8.0, 8.0));
+    assert_eq!(
+        pieces[8].src,
+        Rect::new(16.0 / 24.0, 16.0 / 24.0, 8.0 / 24.0, 8.0 / 24.0)
+    );
+    assert_eq!(pieces[1].dest, Rect::new(8.0, 0.0, 48.0, 8.0));
+
+    let param = pieces[1].to_draw_param(Vector2f::new(TEXTURE.0, TEXTURE.1), ggraphics::WHITE);
+    let scale: Vector2f = param.scale.into();
+    assert_eq!(scale, Vector2f::new(6.0, 1.0));
+}
+
+#[test]
+fn tile_repeats_and_crops_last_piece() {
+    let pieces = slice(
+        Vector2f::new(36.0, 24.0),
+        NineSliceMode::Tile,
+        NineSliceMode::Stretch,
+    );
+
+    // 上辺は8, 8, 4の3片に分かれ、最後の片は切り取られる
+    let top: Vec<&NineSlicePiece> = pieces
+        .iter()
+        .filter(|piece| piece.dest.y == 0.0 && piece.dest.x >= 8.0 && piece.dest.x < 28.0)
+        .collect();
+    assert_eq!(top.len(), 3);
+    assert_eq!(top[2].dest, Rect::new(24.0, 0.0, 4.0, 8.0));
+    assert_eq!(top[2].src.w, 4.0 / 24.0);
+
+    // 中央は引き伸ばしのため1片
+    assert_eq!(
+        pieces
+            .iter()
+            .filter(|piece| piece.dest.x == 8.0 && piece.dest.y == 8.0)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn small_target_shrinks_corners() {
+    let pieces = slice(
+        Vector2f::new(8.0, 40.0),
+        NineSliceMode::Stretch,
+        NineSliceMode::Stretch,
+    );
+
+    // 幅が角の合計より小さいため、中央の列は描画されない
+    assert_eq!(pieces.len(), 6);
+    assert_eq!(pieces[0].dest, Rect::new(0.0, 0.0, 4.0, 8.0));
+    assert_eq!(pieces[1].dest, Rect::new(4.0, 0.0, 4.0, 8.0));
+}
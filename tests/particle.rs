@@ -0,0 +1,121 @@
+extern crate torifune;
+
+use ggez::graphics as ggraphics;
+use torifune::graphics::object::EffectFnStatus;
+use torifune::graphics::particle::*;
+use torifune::numeric::{Point2f, Vector2f};
+
+fn simulator(mode: EmissionMode, seed: u64) -> ParticleSimulator {
+    ParticleSimulator::new(
+        ParticleConfig::new()
+            .with_lifetime(10, 10)
+            .with_speed(1.0, 2.0),
+        EmitterShape::Circle(8.0),
+        mode,
+        Point2f::new(100.0, 100.0),
+        seed,
+    )
+}
+
+#[test]
+fn same_seed_same_result() {
+    let mut a = simulator(EmissionMode::Rate(0.5), 7);
+    let mut b = simulator(EmissionMode::Rate(0.5), 7);
+
+    // advanceを呼ぶ間隔が違っても結果は変わらない
+    a.advance(0);
+    a.advance(6);
+    b.advance(0);
+    for t in 1..=6 {
+        b.advance(t);
+    }
+
+    assert_eq!(a.len(), 3);
+    assert_eq!(a.len(), b.len());
+    for (pa, pb) in a.get_particles().iter().zip(b.get_particles().iter()) {
+        assert_eq!(pa.position, pb.position);
+        assert_eq!(pa.velocity, pb.velocity);
+    }
+}
+
+#[test]
+fn rng_range_and_shapes() {
+    let mut rng = ParticleRng::new(1);
+    for _ in 0..100 {
+        let v = rng.range(2.0, 3.0);
+        assert!(v >= 2.0 && v < 3.0);
+
+        let offset = EmitterShape::Circle(4.0).sample(&mut rng);
+        assert!(offset.norm() <= 4.0);
+
+        let offset = EmitterShape::Rect(Vector2f::new(10.0, 2.0)).sample(&mut rng);
+        assert!(offset.x.abs() <= 5.0 && offset.y.abs() <= 1.0);
+    }
+    assert_eq!(
+        EmitterShape::Point.sample(&mut rng),
+        Vector2f::new(0.0, 0.0)
+    );
+}
+
+#[test]
+fn burst_and_lifetime() {
+    let mut sim = simulator(
+        EmissionMode::Burst {
+            count: 5,
+            interval: 4,
+            repeat: Some(2),
+        },
+        3,
+    );
+
+    sim.advance(0);
+    assert_eq!(sim.len(), 5);
+    sim.advance(4);
+    assert_eq!(sim.len(), 10);
+    sim.advance(9);
+    assert_eq!(sim.len(), 10);
+
+    // 最初のバーストは寿命10で消える
+    sim.advance(10);
+    assert_eq!(sim.len(), 5);
+    sim.stop();
+    sim.advance(20);
+    assert!(sim.is_finished());
+}
+
+#[test]
+fn tweens_gravity_and_modifiers() {
+    let config = ParticleConfig::new()
+        .with_lifetime(4, 4)
+        .with_speed(0.0, 0.0)
+        .with_gravity(1.0)
+        .with_color(ggraphics::WHITE, ggraphics::BLACK, Easing::Linear)
+        .with_alpha(1.0, 0.0, Easing::Linear)
+        .with_scale(1.0, 3.0, Easing::EaseInQuad);
+    let mut sim = ParticleSimulator::new(
+        config,
+        EmitterShape::Point,
+        EmissionMode::Rate(0.0),
+        Point2f::new(0.0, 0.0),
+        0,
+    );
+
+    sim.advance(0);
+    sim.burst(1);
+    sim.advance(2);
+    let particle = sim.get_particles()[0];
+    assert_eq!(particle.position, Point2f::new(0.0, 3.0));
+    assert_eq!(particle.color.r, 0.5);
+    assert_eq!(particle.color.a, 0.5);
+    assert_eq!(particle.scale, 1.5);
+
+    sim.add_modifier(Box::new(|particle: &mut Particle, _t| {
+        if particle.position.y > 4.0 {
+            EffectFnStatus::EffectFinish
+        } else {
+            EffectFnStatus::EffectContinue
+        }
+    }));
+    sim.advance(3);
+    assert!(sim.is_empty());
+}
@@ -0,0 +1,70 @@
+extern crate torifune;
+
+use torifune::error::Error;
+use torifune::resource::*;
+
+#[test]
+fn resource_manifest_groups() {
+    let entries = parse_resource_manifest(
+        "font main /font/main.ttf\n\n[title]\nimage logo /image/logo.png\n[field]\nsound step /sound/step.wav\n",
+    )
+    .unwrap();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].group, None);
+    assert_eq!(
+        entries[1],
+        ResourceEntry::new(
+            ResourceKind::Image,
+            "logo",
+            "/image/logo.png",
+            Some("title")
+        )
+    );
+    assert_eq!(entries[2].group, Some("field".to_string()));
+}
+
+#[test]
+fn resource_manifest_errors() {
+    match parse_resource_manifest("image logo /image/logo.png\nmovie op /movie/op.mp4") {
+        Err(Error::InvalidManifest(message)) => assert!(message.starts_with("line 2")),
+        _ => panic!("unknown resource kind must be rejected"),
+    }
+
+    assert!(parse_resource_manifest("[]").is_err());
+    assert!(parse_resource_manifest("image logo").is_err());
+}
+
+#[test]
+fn load_progress_ratio() {
+    let progress = LoadProgress {
+        loaded: 1,
+        total: 4,
+    };
+    assert_eq!(progress.ratio(), 0.25);
+    assert!(!progress.is_complete());
+    assert!(LoadProgress {
+        loaded: 0,
+        total: 0
+    }
+    .is_complete());
+}
+
+#[test]
+fn resource_manifest_paths_with_spaces() {
+    let entries = parse_resource_manifest(
+        "sound title\t/sound/my title.ogg  \nimage bg  /image/back ground.png",
+    )
+    .unwrap();
+
+    assert_eq!(entries[0].key, "title");
+    assert_eq!(entries[0].path, "/sound/my title.ogg");
+    assert_eq!(entries[1].path, "/image/back ground.png");
+
+    assert_eq!(split_manifest_columns("a b", 2), Some(vec!["a", "b"]));
+    assert_eq!(split_manifest_columns("a", 2), None);
+    assert_eq!(
+        manifest_lines("# comment\n\n  key path  \n").collect::<Vec<_>>(),
+        vec![(3, "key path")]
+    );
+}
@@ -0,0 +1,84 @@
+extern crate torifune;
+
+use torifune::graphics::scene_graph::*;
+use torifune::numeric;
+
+fn approx(a: numeric::Point2f, b: numeric::Point2f) -> bool {
+    (a.x - b.x).abs() < 0.001 && (a.y - b.y).abs() < 0.001
+}
+
+#[test]
+fn world_transform_inherits_parent() {
+    let mut graph = SceneGraph::new(numeric::Point2f::new(10.0, 0.0), 0);
+    let window = graph.create_node(None, Transform::new(numeric::Point2f::new(100.0, 100.0)));
+    let mut label_local = Transform::new(numeric::Point2f::new(20.0, 0.0));
+    label_local.alpha = 0.5;
+    let label = graph.create_node(Some(window), label_local);
+
+    graph.set_local_rotation(window, std::f32::consts::FRAC_PI_2);
+    graph.set_local_alpha(window, 0.5);
+
+    let world = graph.get_world(label).unwrap();
+    assert!(approx(world.position, numeric::Point2f::new(110.0, 120.0)));
+    assert_eq!(world.alpha, 0.25);
+
+    let point = numeric::Point2f::new(3.0, 4.0);
+    assert!(approx(
+        graph.world_to_local(label, graph.local_to_world(label, point)),
+        point
+    ));
+
+    graph.set_node_visible(window, false);
+    assert!(!graph.get_world(label).unwrap().visible);
+}
+
+#[test]
+fn reparent_and_remove() {
+    let mut graph = SceneGraph::new(numeric::Point2f::new(0.0, 0.0), 0);
+    let a = graph.create_node(None, Transform::new(numeric::Point2f::new(50.0, 0.0)));
+    let b = graph.create_node(Some(a), Transform::new(numeric::Point2f::new(10.0, 10.0)));
+    let c = graph.create_node(None, Transform::new(numeric::Point2f::new(0.0, 30.0)));
+
+    // 自身の子孫を親にはできない
+    assert!(!graph.set_parent(a, Some(b), false));
+
+    assert!(graph.set_parent(b, Some(c), true));
+    assert_eq!(graph.get_parent(b), Some(c));
+    assert!(approx(
+        graph.get_world(b).unwrap().position,
+        numeric::Point2f::new(60.0, 10.0)
+    ));
+    assert!(graph.get_children(a).is_empty());
+
+    graph.remove_node(c);
+    assert!(!graph.contains(b));
+    assert!(!graph.contains(c));
+    assert!(graph.contains(a));
+}
+
+#[test]
+fn stale_node_id_does_not_reach_reused_slot() {
+    let mut graph = SceneGraph::new(numeric::Point2f::new(0.0, 0.0), 0);
+    let old = graph.create_node(None, Transform::new(numeric::Point2f::new(10.0, 0.0)));
+    graph.remove_node(old);
+
+    // 削除された枠が再利用される
+    let new = graph.create_node(None, Transform::new(numeric::Point2f::new(0.0, 20.0)));
+    let other = graph.create_node(None, Transform::new(numeric::Point2f::new(5.0, 5.0)));
+    assert_ne!(old, new);
+    assert!(!graph.contains(old));
+    assert!(graph.contains(new));
+    assert!(graph.get_world(old).is_none());
+
+    // 古いIDを通して新しいノードを操作できない
+    graph.set_local_position(old, numeric::Point2f::new(99.0, 99.0));
+    assert!(!graph.set_parent(old, Some(other), false));
+    assert!(!graph.set_parent(other, Some(old), false));
+    graph.remove_node(old);
+    assert!(graph.contains(new));
+    assert!(approx(
+        graph.get_world(new).unwrap().position,
+        numeric::Point2f::new(0.0, 20.0)
+    ));
+    assert_eq!(graph.get_parent(new), None);
+}
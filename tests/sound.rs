@@ -0,0 +1,479 @@
+extern crate torifune;
+
+use torifune::numeric;
+use torifune::sound::looped::*;
+use torifune::sound::spatial::*;
+use torifune::sound::*;
+
+#[test]
+fn attenuation_curves() {
+    let inverse =
+        SoundAttenuation::new(AttenuationCurve::InverseDistance(1.0), 100.0, 1000.0, 200.0);
+    assert_eq!(inverse.gain(100.0), 1.0);
+    assert_eq!(inverse.gain(200.0), 0.5);
+    assert_eq!(inverse.gain(1000.0), 0.0);
+
+    let none = SoundAttenuation::new(AttenuationCurve::None, 100.0, 1000.0, 200.0);
+    assert_eq!(none.gain(999.0), 1.0);
+
+    let custom = SoundAttenuation::new(
+        AttenuationCurve::Custom(|x| 1.0 - (x * x)),
+        0.0,
+        100.0,
+        200.0,
+    );
+    assert_eq!(custom.gain(50.0), 0.75);
+}
+
+#[test]
+fn attenuation_pan() {
+    let attenuation = SoundAttenuation::new(AttenuationCurve::Linear, 0.0, 400.0, 200.0);
+    let listener = numeric::Point2f::new(100.0, 100.0);
+
+    let (gain, pan) = attenuation.evaluate(listener, numeric::Point2f::new(200.0, 100.0));
+    assert_eq!(gain, 0.75);
+    assert_eq!(pan, 0.5);
+
+    let (_, pan) = attenuation.evaluate(listener, numeric::Point2f::new(-500.0, 100.0));
+    assert_eq!(pan, -1.0);
+}
+
+fn spatial_gains(pan: f32) -> (f32, f32) {
+    let samples: Vec<f32> = rodio::source::Spatial::new(
+        rodio::buffer::SamplesBuffer::new(1, 44100, vec![1.0_f32]),
+        pan_emitter_position(pan),
+        SPATIAL_LEFT_EAR,
+        SPATIAL_RIGHT_EAR,
+    )
+    .collect();
+    (samples[0], samples[1])
+}
+
+#[test]
+fn spatial_pan_gains() {
+    // パンの向きの耳の方が大きく聞こえる
+    let (left, right) = spatial_gains(-1.0);
+    assert!(left > right, "pan -1.0: left {}, right {}", left, right);
+
+    let (left, right) = spatial_gains(1.0);
+    assert!(right > left, "pan 1.0: left {}, right {}", left, right);
+
+    let (left, right) = spatial_gains(0.0);
+    assert_eq!(left, right);
+}
+
+#[test]
+fn loop_points_split() {
+    let samples: Vec<i16> = (0..8000).map(|x| x as i16).collect();
+    let points = LoopPoints::new(LoopPosition::Millis(250), None);
+    let (intro, body) = points.split_samples(&samples, 1, 4000);
+    assert_eq!(intro.len(), 1000);
+    assert_eq!(body.len(), 7000);
+    assert_eq!(body[0], 1000);
+
+    let points = LoopPoints::new(LoopPosition::Samples(9000), None);
+    let (intro, body) = points.split_samples(&samples, 1, 4000);
+    assert!(intro.is_empty());
+    assert_eq!(body.len(), 8000);
+}
+
+fn looped(data: Vec<i16>, channels: u16, points: LoopPoints, take: usize) -> Vec<i16> {
+    LoopedSource::new(data.into_iter(), channels, 1000, &points)
+        .take(take)
+        .collect()
+}
+
+#[test]
+fn looped_source_streams_intro_then_loop() {
+    let data: Vec<i16> = (0..10).collect();
+
+    let points = LoopPoints::new(LoopPosition::Samples(4), Some(LoopPosition::Samples(8)));
+    assert_eq!(
+        looped(data.clone(), 1, points, 16),
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 4, 5, 6, 7, 4, 5, 6, 7]
+    );
+
+    let points = LoopPoints::new(LoopPosition::Millis(7), None);
+    assert_eq!(
+        looped(data.clone(), 1, points, 16),
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 7, 8, 9, 7, 8, 9]
+    );
+
+    // ステレオではフレーム単位で位置を指定する
+    let points = LoopPoints::new(LoopPosition::Samples(3), None);
+    assert_eq!(
+        looped(data.clone(), 2, points, 14),
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 6, 7, 8, 9]
+    );
+}
+
+#[test]
+fn looped_source_decodes_only_once() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let decoded = Rc::new(Cell::new(0));
+    let counter = decoded.clone();
+    let data = (0..10_i16).inspect(move |_| counter.set(counter.get() + 1));
+
+    // ループ区間の二周目以降はメモリから再生し、データを読み直さない
+    let points = LoopPoints::new(LoopPosition::Samples(4), Some(LoopPosition::Samples(8)));
+    let samples: Vec<i16> = LoopedSource::new(data, 1, 1000, &points).take(40).collect();
+    assert_eq!(&samples[36..], &[4, 5, 6, 7]);
+    assert_eq!(decoded.get(), 8);
+}
+
+#[test]
+fn looped_source_falls_back_to_whole_data() {
+    let data: Vec<i16> = (0..4).collect();
+
+    // 開始位置がデータの末尾より後ろ
+    let points = LoopPoints::new(LoopPosition::Samples(20), None);
+    assert_eq!(
+        looped(data.clone(), 1, points, 10),
+        vec![0, 1, 2, 3, 0, 1, 2, 3, 0, 1]
+    );
+
+    // 空のループ区間
+    let points = LoopPoints::new(LoopPosition::Samples(2), Some(LoopPosition::Samples(2)));
+    assert_eq!(looped(data.clone(), 1, points, 6), vec![0, 1, 2, 3, 0, 1]);
+
+    // 空のデータは再生を終える
+    let points = LoopPoints::new(LoopPosition::Samples(0), None);
+    assert!(looped(Vec::new(), 1, points, 4).is_empty());
+}
+
+#[test]
+fn mixer_bus_volume_and_mute() {
+    let mut mixer = SoundMixer::new();
+    assert_eq!(mixer.effective_volume(&SoundBusId::Se, 0.5), 0.5);
+
+    mixer.set_master_volume(0.5);
+    mixer.set_bus_volume(SoundBusId::Bgm, 0.5);
+    assert_eq!(mixer.effective_volume(&SoundBusId::Bgm, 1.0), 0.25);
+    assert_eq!(mixer.effective_volume(&SoundBusId::Se, 1.0), 0.5);
+
+    // ミュートしてもバスの音量は保持される
+    mixer.set_bus_mute(SoundBusId::Bgm, true);
+    assert_eq!(mixer.effective_volume(&SoundBusId::Bgm, 1.0), 0.0);
+    assert_eq!(mixer.get_bus_volume(&SoundBusId::Bgm), 0.5);
+    mixer.set_bus_mute(SoundBusId::Bgm, false);
+    assert_eq!(mixer.effective_volume(&SoundBusId::Bgm, 1.0), 0.25);
+
+    let custom = SoundBusId::Custom("ambient".to_string());
+    mixer.set_bus_volume(custom.clone(), 0.0);
+    assert_eq!(mixer.effective_volume(&custom, 1.0), 0.0);
+    assert!(!mixer.is_bus_muted(&custom));
+}
+
+#[test]
+fn fade_envelope() {
+    let fade = SoundFade::new(1.0, 0.5, 10, 20, false);
+    assert_eq!(fade.volume_at(0), 1.0);
+    assert_eq!(fade.volume_at(20), 0.75);
+    assert_eq!(fade.volume_at(100), 0.5);
+    assert!(!fade.is_finished(29));
+    assert!(fade.is_finished(30));
+
+    let instant = SoundFade::fade_out(1.0, 10, 0);
+    assert_eq!(instant.volume_at(10), 0.0);
+    assert!(instant.is_finished(10));
+    assert!(instant.stops_on_end());
+}
+
+#[test]
+fn crossfade_envelopes_meet_halfway() {
+    // 途中まで下がっていたBGMからクロスフェードする
+    let outgoing = SoundFade::fade_out(0.8, 100, 40);
+    let incoming = SoundFade::fade_in(100, 40);
+
+    assert_eq!(outgoing.volume_at(100), 0.8);
+    assert_eq!(incoming.volume_at(100), 0.0);
+    assert_eq!(outgoing.volume_at(120), 0.4);
+    assert_eq!(incoming.volume_at(120), 0.5);
+    assert_eq!(outgoing.volume_at(140), 0.0);
+    assert_eq!(incoming.volume_at(140), 1.0);
+
+    assert!(outgoing.stops_on_end());
+    assert!(!incoming.stops_on_end());
+}
+
+///
+/// SoundManagerで実際に再生するテストのためのContextを生成する
+/// ウィンドウとオーディオデバイスが必要なため、これを用いるテストは`cargo test -- --ignored`で実行する
+///
+fn with_context<F: FnOnce(&mut ggez::Context)>(f: F) {
+    let (mut ctx, _events_loop) = ggez::ContextBuilder::new("torifune_sound_test", "torifune")
+        .build()
+        .unwrap();
+    f(&mut ctx);
+}
+
+///
+/// 無音のモノラル16bitのWAVを生成する
+///
+fn silent_wav(samples: u32) -> SoundData {
+    let rate: u32 = 44100;
+    let data_len = samples * 2;
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16_u32.to_le_bytes());
+    bytes.extend_from_slice(&1_u16.to_le_bytes());
+    bytes.extend_from_slice(&1_u16.to_le_bytes());
+    bytes.extend_from_slice(&rate.to_le_bytes());
+    bytes.extend_from_slice(&(rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2_u16.to_le_bytes());
+    bytes.extend_from_slice(&16_u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    bytes.resize(bytes.len() + data_len as usize, 0);
+
+    SoundData::from_bytes(&bytes)
+}
+
+#[test]
+#[ignore]
+fn sound_manager_rejects_stale_handlers() {
+    with_context(|ctx| {
+        let mut manager = SoundManager::new();
+        let first = manager.play(ctx, silent_wav(44100), None).unwrap();
+        let second = manager.play(ctx, silent_wav(44100), None).unwrap();
+
+        manager.stop(first);
+        assert_eq!(manager.get_state(first), Some(SoundState::Stopped));
+
+        // 停止したサウンドのみ回収され、そのハンドラは無効となる
+        manager.reap_finished();
+        assert_eq!(manager.get_state(first), None);
+        assert!(manager.try_ref_sound(first).is_err());
+        assert_ne!(manager.get_state(second), None);
+        assert_eq!(
+            manager
+                .playing_sounds()
+                .map(|(handler, _)| handler)
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+
+        // 回収された枠は再利用されるが、古いハンドラでは参照できない
+        let third = manager.play(ctx, silent_wav(44100), None).unwrap();
+        assert_ne!(third, first);
+        assert_eq!(manager.get_state(first), None);
+        assert!(manager.ref_sound_mut(first).is_none());
+        assert_ne!(manager.get_state(third), None);
+
+        // 古いハンドラの操作は、新しいサウンドに影響しない
+        manager.stop(first);
+        assert_ne!(manager.get_state(third), Some(SoundState::Stopped));
+    });
+}
+
+#[test]
+fn library_manifest_parsing() {
+    use torifune::sound::library::*;
+
+    let manifest = "# SE\n\n   \ncursor /sound/cursor.wav\n  # indented comment\nbgm1\t/sound/my title.ogg  \n";
+    let entries = parse_manifest(manifest).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            ("cursor".to_string(), "/sound/cursor.wav".to_string()),
+            ("bgm1".to_string(), "/sound/my title.ogg".to_string()),
+        ]
+    );
+
+    match parse_manifest("cursor /sound/cursor.wav\nbroken\n") {
+        Err(torifune::error::Error::InvalidManifest(message)) => {
+            assert_eq!(message, "line 2: broken")
+        }
+        _ => panic!("missing path was accepted"),
+    }
+}
+
+#[test]
+fn library_unknown_names() {
+    use torifune::sound::library::*;
+
+    let mut library = SoundLibrary::new();
+    assert!(!library.contains("missing"));
+    assert!(library.get("missing").is_none());
+    assert_eq!(library.get_ref_count("missing"), 0);
+    library.release("missing");
+
+    match library.acquire("missing") {
+        Err(torifune::error::Error::ResourceNotFound(key)) => assert_eq!(key, "missing"),
+        _ => panic!("unknown name was acquired"),
+    }
+    assert!(library.set_loop_points("missing", None).is_err());
+    assert_eq!(library.unload_unused(), 0);
+}
+
+#[test]
+fn voice_limit_policies() {
+    let steal = VoiceLimit::new(2, VoiceLimitPolicy::StealOldest, 0);
+    assert_eq!(steal.voices_to_stop(1), Some(0));
+    assert_eq!(steal.voices_to_stop(2), Some(1));
+    assert_eq!(steal.voices_to_stop(4), Some(3));
+
+    let ignore = VoiceLimit::new(2, VoiceLimitPolicy::IgnoreNew, 0);
+    assert_eq!(ignore.voices_to_stop(1), Some(0));
+    assert_eq!(ignore.voices_to_stop(2), None);
+
+    let restart = VoiceLimit::new(2, VoiceLimitPolicy::Restart, 0);
+    assert_eq!(restart.voices_to_stop(2), Some(2));
+
+    // 上限が0の場合は、どのポリシーでも再生しない
+    for policy in &[
+        VoiceLimitPolicy::StealOldest,
+        VoiceLimitPolicy::IgnoreNew,
+        VoiceLimitPolicy::Restart,
+    ] {
+        let never = VoiceLimit::new(0, *policy, 0);
+        assert_eq!(never.voices_to_stop(0), None);
+        assert_eq!(never.voices_to_stop(3), None);
+    }
+}
+
+fn voice(handler: usize, priority: i32, same_key: bool, capped: bool) -> ActiveVoice<usize> {
+    ActiveVoice {
+        handler: handler,
+        priority: priority,
+        same_key: same_key,
+        capped: capped,
+    }
+}
+
+#[test]
+fn voice_victims_are_chosen_before_stopping() {
+    let steal = VoiceLimit::new(2, VoiceLimitPolicy::StealOldest, 0);
+    let voices = [
+        voice(0, 5, true, true),
+        voice(1, 5, true, true),
+        voice(2, 5, false, true),
+        voice(3, 5, false, true),
+    ];
+
+    // キーごとの制限は満たせても、全体の上限で再生できない場合は何も停止しない
+    assert_eq!(
+        select_voice_victims(&voices, Some(&steal), Some(3), 0),
+        None
+    );
+
+    // キーごとの制限で停止するものは、全体の数から除いて数える
+    assert_eq!(
+        select_voice_victims(&voices, Some(&steal), Some(4), 0),
+        Some(vec![0])
+    );
+    assert_eq!(
+        select_voice_victims(&voices, Some(&steal), Some(3), 10),
+        Some(vec![0, 1])
+    );
+
+    // 全体の数に数えないもの(BGM)は停止しない
+    let voices = [voice(0, 0, false, false), voice(1, 5, false, true)];
+    assert_eq!(
+        select_voice_victims(&voices, None, Some(1), 10),
+        Some(vec![1])
+    );
+    assert_eq!(select_voice_victims(&voices, None, Some(1), 5), None);
+    assert_eq!(select_voice_victims(&voices, None, None, 0), Some(vec![]));
+}
+
+#[test]
+#[ignore]
+fn voice_cap_rejection_keeps_same_key_voices() {
+    with_context(|ctx| {
+        let mut manager = SoundManager::new();
+        manager.set_voice_limit("step", VoiceLimit::new(2, VoiceLimitPolicy::StealOldest, 0));
+
+        let flags = |key: Option<&str>, priority: i32| {
+            let flags = SoundPlayFlags::new(0, 1.0, true, 1.0).with_priority(priority);
+            match key {
+                Some(key) => Some(flags.with_key(key)),
+                None => Some(flags),
+            }
+        };
+
+        let handlers: Vec<SoundHandler> = vec![
+            manager.play(ctx, silent_wav(44100), flags(Some("step"), 5)),
+            manager.play(ctx, silent_wav(44100), flags(Some("step"), 5)),
+            manager.play(ctx, silent_wav(44100), flags(None, 5)),
+            manager.play(ctx, silent_wav(44100), flags(None, 5)),
+        ]
+        .into_iter()
+        .map(|handler| handler.unwrap())
+        .collect();
+        manager.set_voice_cap(Some(3));
+
+        // キーごとの制限は満たせても全体の上限で拒否される場合、同じキーのサウンドは停止しない
+        assert_eq!(
+            manager.play(ctx, silent_wav(44100), flags(Some("step"), 0)),
+            None
+        );
+        for handler in handlers {
+            assert_eq!(manager.get_state(handler), Some(SoundState::Playing));
+        }
+    });
+}
+
+#[test]
+fn voice_auto_advance_without_voice() {
+    use torifune::sound::voice::*;
+
+    let mut channel = VoiceChannel::new();
+    assert_eq!(channel.auto_advance_time(10, 5), Some(15));
+    assert!(!channel.is_ready_to_advance(None, 0));
+
+    channel.advance(14, None);
+    assert!(!channel.is_ready_to_advance(Some(10), 5));
+    channel.advance(15, None);
+    assert!(channel.is_ready_to_advance(Some(10), 5));
+}
+
+#[test]
+#[ignore]
+fn voice_auto_advance_waits_for_voice() {
+    use torifune::sound::voice::*;
+
+    with_context(|ctx| {
+        let mut manager = SoundManager::new();
+        let handler = manager.play(ctx, silent_wav(44100), None).unwrap();
+        let mut channel = VoiceChannel::new();
+
+        channel.advance(2, None);
+        channel.track(&mut manager, handler);
+        assert_eq!(channel.get_current(), Some(handler));
+
+        // ボイスの再生中は、テキストの表示が完了していても進まない
+        channel.advance(30, Some(SoundState::Playing));
+        assert_eq!(channel.auto_advance_time(10, 5), None);
+        assert!(!channel.is_ready_to_advance(Some(10), 5));
+        channel.advance(35, Some(SoundState::Paused));
+        assert!(channel.is_playing());
+
+        // ボイスの終了時刻とテキストの表示完了時刻の遅い方が基準となる
+        channel.advance(40, Some(SoundState::Stopped));
+        assert!(!channel.is_playing());
+        assert_eq!(channel.finished_at(), Some(40));
+        assert_eq!(channel.auto_advance_time(10, 5), Some(45));
+        assert_eq!(channel.auto_advance_time(50, 5), Some(55));
+        assert!(!channel.is_ready_to_advance(Some(10), 5));
+        channel.advance(45, None);
+        assert!(channel.is_ready_to_advance(Some(10), 5));
+
+        // 回収済みのハンドラは終了したものとして扱う
+        channel.track(&mut manager, handler);
+        manager.stop(handler);
+        manager.reap_finished();
+        channel.update(&manager, 60);
+        assert_eq!(channel.finished_at(), Some(60));
+
+        channel.track(&mut manager, handler);
+        channel.advance(70, Some(SoundState::Playing));
+        channel.stop(&mut manager);
+        assert_eq!(channel.finished_at(), Some(70));
+    });
+}
@@ -0,0 +1,49 @@
+extern crate torifune;
+
+use torifune::graphics::object::text_input::*;
+
+#[test]
+fn text_buffer_filter_and_max_length() {
+    let mut buffer = TextBuffer::new(Some(4), TextFilter::Numeric);
+
+    assert_eq!(buffer.insert_str("1a2b345"), 4);
+    assert_eq!(buffer.get_text(), "1234");
+    assert!(!buffer.insert_char('6'));
+    assert!(!buffer.insert_char('\u{8}'));
+}
+
+#[test]
+fn text_buffer_cursor_selection_and_delete() {
+    let mut buffer = TextBuffer::new(None, TextFilter::Any);
+    buffer.set_text("abcdef");
+
+    buffer.move_left(false);
+    buffer.move_left(true);
+    buffer.move_left(true);
+    assert_eq!(buffer.selection(), Some((3, 5)));
+    assert_eq!(buffer.selected_text(), Some("de".to_string()));
+
+    buffer.insert_char('X');
+    assert_eq!(buffer.get_text(), "abcXf");
+    assert_eq!(buffer.get_cursor(), 4);
+
+    buffer.backspace();
+    buffer.move_home(false);
+    buffer.delete();
+    assert_eq!(buffer.get_text(), "bcf");
+}
+
+#[test]
+fn text_buffer_preedit_is_shown_at_cursor() {
+    let mut buffer = TextBuffer::new(Some(5), TextFilter::Any);
+    buffer.set_text("名前");
+    buffer.move_left(false);
+    buffer.set_preedit("にゅうりょく");
+
+    assert_eq!(buffer.display_text(), "名にゅうりょく前");
+    assert_eq!(buffer.get_text(), "名前");
+
+    buffer.commit_preedit();
+    assert_eq!(buffer.get_text(), "名にゅう前");
+    assert_eq!(buffer.get_preedit(), "");
+}
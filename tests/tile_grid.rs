@@ -0,0 +1,96 @@
+extern crate torifune;
+
+use torifune::graphics::object::tile_grid::*;
+use torifune::numeric::Vector2u;
+
+#[test]
+fn tile_animation_frames() {
+    let animation = TileAnimation::new(vec![
+        (Vector2u::new(0, 0), 4),
+        (Vector2u::new(1, 0), 0),
+        (Vector2u::new(2, 0), 2),
+    ]);
+
+    assert_eq!(animation.get_frames().len(), 2);
+    assert_eq!(animation.total_duration(), 6);
+    assert_eq!(animation.frame_at(3), Some(Vector2u::new(0, 0)));
+    assert_eq!(animation.frame_at(5), Some(Vector2u::new(2, 0)));
+    assert_eq!(animation.frame_at(11), Some(Vector2u::new(2, 0)));
+
+    assert_eq!(TileAnimation::new(Vec::new()).frame_at(10), None);
+}
+
+#[test]
+fn set_tile_marks_only_its_chunk() {
+    let mut grid = TileGrid::new(Vector2u::new(10, 5), Vector2u::new(4, 4)).unwrap();
+    assert_eq!(grid.chunk_count(), 6);
+    assert_eq!(grid.chunk_of(Vector2u::new(9, 4)), Some(5));
+    assert_eq!(grid.chunk_of(Vector2u::new(10, 0)), None);
+    assert_eq!(
+        grid.chunk_range(5),
+        (Vector2u::new(8, 4), Vector2u::new(10, 5))
+    );
+
+    grid.set_tile(
+        Vector2u::new(5, 1),
+        Some(GridTile::Static(Vector2u::new(3, 0))),
+    );
+    assert_eq!(grid.take_dirty_chunks(), vec![1]);
+    assert!(grid.take_dirty_chunks().is_empty());
+
+    grid.set_tile(
+        Vector2u::new(5, 1),
+        Some(GridTile::Static(Vector2u::new(3, 0))),
+    );
+    assert!(grid.take_dirty_chunks().is_empty());
+
+    grid.clear_tile(Vector2u::new(5, 1));
+    assert_eq!(grid.get_tile(Vector2u::new(5, 1)), None);
+    assert_eq!(grid.take_dirty_chunks(), vec![1]);
+    assert!(grid.chunk_tiles(1).is_empty());
+}
+
+#[test]
+fn animation_marks_chunks_using_it() {
+    let mut grid = TileGrid::new(Vector2u::new(8, 8), Vector2u::new(4, 4)).unwrap();
+    let water = grid.add_animation(TileAnimation::new(vec![
+        (Vector2u::new(0, 1), 10),
+        (Vector2u::new(1, 1), 10),
+    ]));
+
+    grid.set_tile(Vector2u::new(6, 6), Some(GridTile::Animated(water)));
+    grid.set_tile(
+        Vector2u::new(0, 0),
+        Some(GridTile::Static(Vector2u::new(0, 0))),
+    );
+    assert_eq!(grid.take_dirty_chunks(), vec![0, 3]);
+    assert_eq!(
+        grid.chunk_tiles(3),
+        vec![(Vector2u::new(6, 6), Vector2u::new(0, 1))]
+    );
+
+    grid.advance(5);
+    assert!(grid.take_dirty_chunks().is_empty());
+
+    grid.advance(12);
+    assert_eq!(grid.take_dirty_chunks(), vec![3]);
+    assert_eq!(
+        grid.chunk_tiles(3),
+        vec![(Vector2u::new(6, 6), Vector2u::new(1, 1))]
+    );
+
+    grid.clear_tile(Vector2u::new(6, 6));
+    grid.take_dirty_chunks();
+    grid.chunk_tiles(3);
+    grid.advance(20);
+    assert!(grid.take_dirty_chunks().is_empty());
+}
+
+#[test]
+fn oversized_grid_is_rejected() {
+    assert!(TileGrid::new(Vector2u::new(0x10000, 0x10000), Vector2u::new(16, 16)).is_err());
+
+    let grid = TileGrid::new(Vector2u::new(10, 5), Vector2u::new(4, 4)).unwrap();
+    let (begin, end) = grid.chunk_range(std::usize::MAX);
+    assert!(begin.x >= end.x || begin.y >= end.y);
+}
@@ -0,0 +1,226 @@
+extern crate torifune;
+
+use torifune::graphics::object::tile_map::*;
+use torifune::numeric;
+
+const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.2" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16">
+ <properties>
+  <property name="bgm" value="field"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="../images/ground.png" width="32" height="32"/>
+  <tile id="1">
+   <properties>
+    <property name="collision" type="bool" value="true"/>
+   </properties>
+  </tile>
+  <tile id="2">
+   <objectgroup><object x="0" y="0" width="16" height="16"/></objectgroup>
+  </tile>
+ </tileset>
+ <tileset firstgid="5" source="props.tsx"/>
+ <layer name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,1,
+1,3,2147483649
+</data>
+ </layer>
+ <layer name="upper" width="3" height="2" opacity="0.5">
+  <data encoding="base64">AAAAAAAAAAAFAAAAAAAAAAAAAAAAAAAA</data>
+ </layer>
+ <objectgroup name="events">
+  <object id="1" name="door" type="warp" x="16" y="0" width="16" height="16">
+   <properties>
+    <property name="to" value="town"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn parse_tmx_layers_and_objects() {
+    let map = parse_tmx(TMX).unwrap();
+    assert_eq!((map.width, map.height), (3, 2));
+    assert_eq!(map.properties.get("bgm").unwrap(), "field");
+
+    assert_eq!(map.tile_layers.len(), 2);
+    assert_eq!(map.tile_layers[0].tiles, vec![1, 2, 1, 1, 3, 1]);
+    assert_eq!(map.tile_layers[1].tiles, vec![0, 0, 5, 0, 0, 0]);
+    assert_eq!(map.tile_layers[1].opacity, 0.5);
+    assert_eq!(map.layer_index("upper"), Some(1));
+
+    assert_eq!(map.tilesets[0].image, "../images/ground.png");
+    assert_eq!(map.tilesets[1].source.as_ref().unwrap(), "props.tsx");
+    assert!(map.is_collision_gid(2));
+    assert!(map.is_collision_gid(3));
+    assert!(!map.is_collision_gid(1));
+
+    let door = map
+        .get_object_layer("events")
+        .unwrap()
+        .find_object("door")
+        .unwrap();
+    assert_eq!(door.kind, "warp");
+    assert_eq!(door.properties.get("to").unwrap(), "town");
+    assert_eq!(door.get_area(), numeric::Rect::new(16.0, 0.0, 16.0, 16.0));
+}
+
+#[test]
+fn parse_external_tileset() {
+    let mut map = parse_tmx(TMX).unwrap();
+    let tsx = r#"<tileset name="props" tilewidth="16" tileheight="32" tilecount="2" columns="2" spacing="1" margin="1">
+        <image source="props.png" width="35" height="34"/>
+    </tileset>"#;
+
+    let external = parse_tsx(tsx, 5).unwrap();
+    map.tilesets[1].merge_external(external);
+    assert_eq!(map.tilesets[1].first_gid, 5);
+    assert_eq!(map.tileset_for_gid(6).unwrap().name, "props");
+    assert_eq!(
+        map.tilesets[1].tile_image_position(1),
+        numeric::Vector2u::new(18, 1)
+    );
+}
+
+#[test]
+fn parse_json_map() {
+    let json = r#"{
+        "orientation": "orthogonal", "width": 2, "height": 2,
+        "tilewidth": 8, "tileheight": 8, "infinite": false,
+        "tilesets": [{
+            "firstgid": 1, "name": "t", "image": "t.png", "imagewidth": 16, "imageheight": 8,
+            "tilewidth": 8, "tileheight": 8, "tilecount": 2, "columns": 2,
+            "tiles": [{ "id": 1, "properties": [{ "name": "collision", "type": "bool", "value": true }] }]
+        }],
+        "layers": [
+            { "type": "tilelayer", "name": "a", "width": 2, "height": 2, "data": [1, 2, 0, 1] },
+            { "type": "tilelayer", "name": "b", "width": 2, "height": 2,
+              "encoding": "base64", "data": "AgAAAAAAAAAAAAAAAAAAAA==" },
+            { "type": "objectgroup", "name": "spawn", "objects": [
+                { "id": 3, "name": "player", "class": "start", "x": 4, "y": 12,
+                  "properties": [{ "name": "dir", "type": "int", "value": 2 }] }
+            ] }
+        ]
+    }"#;
+
+    let map = parse_tiled_json(json).unwrap();
+    assert_eq!(map.tile_layers[0].tiles, vec![1, 2, 0, 1]);
+    assert_eq!(map.tile_layers[1].tiles, vec![2, 0, 0, 0]);
+    assert!(map.is_collision_tile(numeric::Vector2u::new(1, 0)));
+    assert!(map.is_collision_tile(numeric::Vector2u::new(0, 0)));
+    assert!(!map.is_collision_tile(numeric::Vector2u::new(1, 1)));
+
+    let player = &map.get_object_layer("spawn").unwrap().objects[0];
+    assert_eq!(player.kind, "start");
+    assert_eq!(player.properties.get("dir").unwrap(), "2");
+}
+
+#[test]
+fn reject_unsupported_maps() {
+    assert!(decode_tile_data(Some("base64"), Some("zlib"), "", 0).is_err());
+    assert!(decode_tile_data(Some("csv"), None, "1,2", 3).is_err());
+    assert!(parse_tmx(&TMX.replace("orthogonal", "isometric")).is_err());
+    assert!(parse_tiled_json(
+        r#"{ "width": 1, "height": 1, "tilewidth": 8, "tileheight": 8, "infinite": true, "layers": [] }"#
+    )
+    .is_err());
+}
+
+#[test]
+fn tile_queries_and_visible_range() {
+    let map = parse_tmx(TMX).unwrap();
+
+    assert_eq!(
+        map.point_to_tile(numeric::Point2f::new(20.0, 17.0)),
+        Some(numeric::Vector2u::new(1, 1))
+    );
+    assert_eq!(map.point_to_tile(numeric::Point2f::new(48.0, 0.0)), None);
+    assert_eq!(map.point_to_tile(numeric::Point2f::new(-1.0, 0.0)), None);
+    assert_eq!(
+        map.tile_to_point(numeric::Vector2u::new(2, 1)),
+        numeric::Point2f::new(32.0, 16.0)
+    );
+    assert_eq!(map.tile_layers[0].get_tile(1, 0), Some(2));
+    assert_eq!(map.tile_layers[1].get_tile(0, 0), None);
+
+    let (begin, end) = map
+        .visible_tile_range(numeric::Rect::new(10.0, 0.0, 10.0, 10.0))
+        .unwrap();
+    assert_eq!((begin.x, begin.y, end.x, end.y), (0, 0, 2, 1));
+    assert!(map
+        .visible_tile_range(numeric::Rect::new(100.0, 0.0, 10.0, 10.0))
+        .is_none());
+}
+
+#[test]
+fn reject_tmx_tile_count_mismatch() {
+    let xml_tiles = TMX.replace(
+        r#"<data encoding="csv">
+1,2,1,
+1,3,2147483649
+</data>"#,
+        r#"<data><tile gid="1"/><tile gid="2"/><tile/></data>"#,
+    );
+    match parse_tmx(&xml_tiles) {
+        Err(torifune::error::Error::InvalidTileMap(message)) => {
+            assert!(message.starts_with("ground: expected 6 tiles, found 3"))
+        }
+        _ => panic!("<tile> count must match the layer size"),
+    }
+}
+
+#[test]
+fn tileset_image_size_fills_grid() {
+    let mut tileset = Tileset {
+        tile_width: 16,
+        tile_height: 16,
+        spacing: 2,
+        margin: 1,
+        ..Default::default()
+    };
+    tileset.set_image_size(53, 35);
+    assert_eq!((tileset.columns, tileset.tile_count), (3, 6));
+
+    // 余白が画像より大きい場合や、タイルの大きさが0の場合もパニックしない
+    let mut tileset = Tileset {
+        margin: 64,
+        tile_width: 16,
+        tile_height: 16,
+        ..Default::default()
+    };
+    tileset.set_image_size(32, 32);
+    assert_eq!((tileset.columns, tileset.tile_count), (0, 0));
+
+    let mut tileset = Tileset::default();
+    tileset.set_image_size(32, 32);
+    assert_eq!((tileset.columns, tileset.tile_count), (0, 0));
+}
+
+#[test]
+fn reject_oversized_layers_and_invalid_gids() {
+    let oversized = TMX.replace(
+        r#"<layer name="ground" width="3" height="2">"#,
+        r#"<layer name="ground" width="65536" height="65536">"#,
+    );
+    match parse_tmx(&oversized) {
+        Err(torifune::error::Error::InvalidTileMap(message)) => {
+            assert!(message.starts_with("ground: 65536x65536 is too large"))
+        }
+        _ => panic!("layer size must fit in u32"),
+    }
+
+    let json = r#"{ "width": 65536, "height": 65536, "tilewidth": 8, "tileheight": 8,
+        "layers": [{ "type": "tilelayer", "name": "a", "data": [] }] }"#;
+    assert!(parse_tiled_json(json).is_err());
+
+    let json = r#"{ "width": 2, "height": 1, "tilewidth": 8, "tileheight": 8,
+        "layers": [{ "type": "tilelayer", "name": "a", "width": 2, "height": 1, "data": [1, "x"] }] }"#;
+    match parse_tiled_json(json) {
+        Err(torifune::error::Error::InvalidTileMap(message)) => {
+            assert_eq!(message, r#"invalid gid: "x""#)
+        }
+        _ => panic!("non-numeric gid must be rejected"),
+    }
+}